@@ -0,0 +1,460 @@
+use std::sync::Arc;
+use argon2::{ Argon2, PasswordHash, PasswordHasher, PasswordVerifier };
+use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
+use axum::{ async_trait, Extension, Json, Router };
+use axum::http::StatusCode;
+use jsonwebtoken::{ encode, Algorithm, EncodingKey, Header };
+use rsa::RsaPrivateKey;
+use serde::{ Serialize, Deserialize };
+use serde_json::Value;
+use crate::{ FieldValue, KeyValue, RecordId, SchemaTrait };
+use crate::keys::UUID_KEY;
+use crate::api_keys::hash_key;
+use crate::extractors::{ AuthConfig, ACCESS_TOKEN_USE };
+use crate::revocations::Revocations;
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, FilterOp };
+
+pub const USERS_TABLE: &str = "janus_users";
+pub const REFRESH_TOKENS_TABLE: &str = "janus_refresh_tokens";
+const ACCOUNTS_KEY_ID: &str = "janus-accounts-key";
+const REFRESH_PREFIX: &str = "jr_";
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub struct AccountsSchema {}
+
+impl SchemaTrait for AccountsSchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            subscription TEXT NOT NULL DEFAULT 'none',
+            date_created TIMESTAMPTZ NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            token_hash TEXT NOT NULL UNIQUE,
+            expires_at TIMESTAMPTZ NOT NULL,
+            revoked BOOLEAN NOT NULL DEFAULT FALSE,
+            date_created TIMESTAMPTZ NOT NULL
+        );", USERS_TABLE, REFRESH_TOKENS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct User {
+    pub id: uuid::Uuid,
+    pub username: String,
+    pub password_hash: String,
+    pub subscription: String,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for User {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("username", FieldValue::STRING( &self.username )),
+            ("password_hash", FieldValue::STRING( &self.password_hash )),
+            ("subscription", FieldValue::STRING( &self.subscription )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub id: uuid::Uuid,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub revoked: bool,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for RefreshToken {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("user_id", FieldValue::STRING( &self.user_id )),
+            ("token_hash", FieldValue::STRING( &self.token_hash )),
+            ("expires_at", FieldValue::DATE( &self.expires_at )),
+            ("revoked", FieldValue::BOOLEAN( &self.revoked )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ]
+    }
+}
+
+#[derive(Clone)]
+pub enum TokenSigning {
+    HS256,
+    RS256( Box<RsaPrivateKey> )
+}
+
+#[derive(Clone)]
+pub struct AccountsConfig {
+    pub path: String,
+    pub signing: TokenSigning,
+    pub access_lifetime: u64,
+    pub refresh_lifetime: u64
+}
+
+impl AccountsConfig {
+    pub fn new( path: &str ) -> AccountsConfig {
+        AccountsConfig { path: path.trim_end_matches( '/' ).to_string(), signing: TokenSigning::HS256, access_lifetime: 900, refresh_lifetime: 30 * 24 * 3600 }
+    }
+
+    // RS256 tokens are checked through the clerk path, so CLERK_ISSUER must be the public url of `path`
+    pub fn rs256( mut self, key: RsaPrivateKey ) -> Self {
+        self.signing = TokenSigning::RS256( Box::new( key ) );
+        self
+    }
+
+    pub fn access_lifetime( mut self, seconds: u64 ) -> Self {
+        self.access_lifetime = seconds;
+        self
+    }
+
+    pub fn refresh_lifetime( mut self, seconds: u64 ) -> Self {
+        self.refresh_lifetime = seconds;
+        self
+    }
+}
+
+fn find_query<'a>( table: &'static str, column: &'static str, value: FieldValue<'a> ) -> ReadQuery<'a> {
+    ReadQuery {
+        table,
+        filters: vec![ Filter { column, op: FilterOp::EQ, value } ],
+        owner: None,
+        order_by: "id",
+        descending: true,
+        limit: Some( 1 ),
+        offset: None
+    }
+}
+
+#[async_trait]
+pub trait AccountStore: Send + Sync {
+    async fn find_user( &self, username: &str ) -> Result<Option<User>, StatusCode>;
+    async fn get_user( &self, id: uuid::Uuid ) -> Result<Option<User>, StatusCode>;
+    async fn insert_user( &self, user: &User ) -> Result<(), StatusCode>;
+    async fn find_refresh_token( &self, token_hash: &str ) -> Result<Option<RefreshToken>, StatusCode>;
+    async fn insert_refresh_token( &self, token: &RefreshToken ) -> Result<(), StatusCode>;
+    async fn revoke_refresh_token( &self, id: uuid::Uuid ) -> Result<u64, StatusCode>;
+}
+
+#[async_trait]
+impl<S> AccountStore for S where S: Storage, User: FromStorageRow<S>, RefreshToken: FromStorageRow<S> {
+    async fn find_user( &self, username: &str ) -> Result<Option<User>, StatusCode> {
+        let username = username.to_string();
+        match self.select( &find_query( USERS_TABLE, "username", FieldValue::STRING( &username ) ) ).await?.into_iter().next() {
+            Some( row ) => Ok( Some( User::from_storage_row( row )? ) ),
+            None => Ok( None )
+        }
+    }
+
+    async fn get_user( &self, id: uuid::Uuid ) -> Result<Option<User>, StatusCode> {
+        match self.select( &find_query( USERS_TABLE, "id", FieldValue::UUID( &id ) ) ).await?.into_iter().next() {
+            Some( row ) => Ok( Some( User::from_storage_row( row )? ) ),
+            None => Ok( None )
+        }
+    }
+
+    async fn insert_user( &self, user: &User ) -> Result<(), StatusCode> {
+        self.insert( USERS_TABLE, UUID_KEY, &user.key_value_pairs() ).await?;
+        Ok(())
+    }
+
+    async fn find_refresh_token( &self, token_hash: &str ) -> Result<Option<RefreshToken>, StatusCode> {
+        let token_hash = token_hash.to_string();
+        match self.select( &find_query( REFRESH_TOKENS_TABLE, "token_hash", FieldValue::STRING( &token_hash ) ) ).await?.into_iter().next() {
+            Some( row ) => Ok( Some( RefreshToken::from_storage_row( row )? ) ),
+            None => Ok( None )
+        }
+    }
+
+    async fn insert_refresh_token( &self, token: &RefreshToken ) -> Result<(), StatusCode> {
+        self.insert( REFRESH_TOKENS_TABLE, UUID_KEY, &token.key_value_pairs() ).await?;
+        Ok(())
+    }
+
+    async fn revoke_refresh_token( &self, id: uuid::Uuid ) -> Result<u64, StatusCode> {
+        // only the first of two racing refreshes flips the flag, the other updates nothing
+        let active = [ Filter { column: "revoked", op: FilterOp::EQ, value: FieldValue::BOOLEAN( &false ) } ];
+        self.update_where( REFRESH_TOKENS_TABLE, UUID_KEY, &RecordId::from( id ), &[ ("revoked", FieldValue::BOOLEAN( &true )) ], &active, None ).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountTokens {
+    pub user_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub subscription_token: Option<String>,
+    pub expires_in: u64
+}
+
+#[derive(Debug, Serialize)]
+struct AccessClaims<'a> {
+    sub: &'a str,
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String,
+    token_use: &'static str
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionClaims<'a> {
+    subscription: &'a str,
+    clerk_id: &'a str,
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+fn hashing_error<E: std::fmt::Debug>( error: E ) -> StatusCode {
+    println!("{:?}", error);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+// argon2 is deliberately slow, so it stays off the async workers
+async fn hash_password( password: String ) -> Result<String, StatusCode> {
+    tokio::task::spawn_blocking( move || {
+        let salt = SaltString::generate( &mut OsRng );
+        Argon2::default().hash_password( password.as_bytes(), &salt ).map( |hash| hash.to_string() )
+    } ).await.map_err( hashing_error )?.map_err( hashing_error )
+}
+
+static DUMMY_HASH: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+
+async fn dummy_hash() -> Result<&'static String, StatusCode> {
+    DUMMY_HASH.get_or_try_init( || hash_password( uuid::Uuid::new_v4().to_string() ) ).await
+}
+
+async fn verify_password( password: String, password_hash: String ) -> bool {
+    tokio::task::spawn_blocking( move || {
+        match PasswordHash::new( &password_hash ) {
+            Ok( parsed ) => Argon2::default().verify_password( password.as_bytes(), &parsed ).is_ok(),
+            Err(_) => false
+        }
+    } ).await.unwrap_or( false )
+}
+
+#[derive(Clone)]
+pub struct Accounts {
+    store: Arc<dyn AccountStore>,
+    auth: AuthConfig,
+    access_lifetime: u64,
+    refresh_lifetime: u64,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    issuer: String,
+    jwks: Option<Value>,
+    revocations: Option<Revocations>
+}
+
+impl Accounts {
+    pub fn new<K: AccountStore + 'static>( store: K, auth: AuthConfig, config: &AccountsConfig ) -> anyhow::Result<Accounts> {
+        let ( algorithm, encoding_key, issuer, jwks ) = match &config.signing {
+            TokenSigning::HS256 => {
+                if auth.jwt_secret.is_empty() || auth.jwt_issuer.is_empty() {
+                    anyhow::bail!( "HS256 account tokens need JWT_SECRET and JWT_ISSUER" );
+                }
+                ( Algorithm::HS256, EncodingKey::from_secret( auth.jwt_secret.as_bytes() ), auth.jwt_issuer.clone(), None )
+            },
+            TokenSigning::RS256( key ) => {
+                let ( encoding_key, jwks ) = crate::dev::rsa_signing_key( key, ACCOUNTS_KEY_ID )?;
+                ( Algorithm::RS256, encoding_key, auth.clerk_issuer.clone(), Some( jwks ) )
+            }
+        };
+
+        Ok( Accounts {
+            store: Arc::new( store ),
+            auth,
+            access_lifetime: config.access_lifetime,
+            refresh_lifetime: config.refresh_lifetime,
+            algorithm,
+            encoding_key,
+            issuer,
+            jwks,
+            revocations: None
+        } )
+    }
+
+    pub fn revocations( mut self, revocations: Revocations ) -> Self {
+        self.revocations = Some( revocations );
+        self
+    }
+
+    pub fn jwks( &self ) -> Option<&Value> {
+        self.jwks.as_ref()
+    }
+
+    fn sign<C: Serialize>( &self, algorithm: Algorithm, key: &EncodingKey, claims: &C ) -> Result<String, StatusCode> {
+        let mut header = Header::new( algorithm );
+        if algorithm == Algorithm::RS256 {
+            header.kid = Some( ACCOUNTS_KEY_ID.to_string() );
+        }
+        encode( &header, claims, key ).map_err( hashing_error )
+    }
+
+    async fn issue( &self, user: &User ) -> Result<AccountTokens, StatusCode> {
+        let user_id = user.id.to_string();
+        let issued_at = now();
+
+        let access_claims = AccessClaims { sub: &user_id, aud: &self.auth.audience, iss: &self.issuer, iat: issued_at, exp: issued_at + self.access_lifetime, jti: uuid::Uuid::new_v4().to_string(), token_use: ACCESS_TOKEN_USE };
+        let access_token = self.sign( self.algorithm, &self.encoding_key, &access_claims )?;
+
+        // subscriptions ride in the same HS256 token the extractor already reads from the Subscription header
+        let subscription_token = match user.subscription.as_str() {
+            "none" => None,
+            _ if self.auth.jwt_secret.is_empty() => None,
+            subscription => {
+                let claims = SubscriptionClaims { subscription, clerk_id: &user_id, aud: &self.auth.audience, iss: &self.auth.jwt_issuer, iat: issued_at, exp: issued_at + self.access_lifetime, jti: uuid::Uuid::new_v4().to_string() };
+                Some( self.sign( Algorithm::HS256, &EncodingKey::from_secret( self.auth.jwt_secret.as_bytes() ), &claims )? )
+            }
+        };
+
+        let refresh_token = format!( "{}{}{}", REFRESH_PREFIX, uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple() );
+        let stored = RefreshToken {
+            id: uuid::Uuid::new_v4(),
+            user_id: user_id.clone(),
+            token_hash: hash_key( &refresh_token ),
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds( self.refresh_lifetime as i64 ),
+            revoked: false,
+            date_created: chrono::Utc::now()
+        };
+        self.store.insert_refresh_token( &stored ).await?;
+
+        Ok( AccountTokens { user_id, access_token, refresh_token, subscription_token, expires_in: self.access_lifetime } )
+    }
+
+    pub async fn signup( &self, credentials: &Credentials ) -> Result<AccountTokens, StatusCode> {
+        if credentials.username.trim().is_empty() || credentials.password.len() < MIN_PASSWORD_LENGTH {
+            return Err( StatusCode::BAD_REQUEST );
+        }
+        if self.store.find_user( &credentials.username ).await?.is_some() {
+            return Err( StatusCode::CONFLICT );
+        }
+
+        let user = User {
+            id: uuid::Uuid::new_v4(),
+            username: credentials.username.clone(),
+            password_hash: hash_password( credentials.password.clone() ).await?,
+            subscription: "none".to_string(),
+            date_created: chrono::Utc::now()
+        };
+        // a concurrent signup can slip in between the lookup and the insert, the UNIQUE username catches it
+        if let Err( status ) = self.store.insert_user( &user ).await {
+            return match self.store.find_user( &credentials.username ).await? {
+                Some(_) => Err( StatusCode::CONFLICT ),
+                None => Err( status )
+            };
+        }
+
+        self.issue( &user ).await
+    }
+
+    pub async fn login( &self, credentials: &Credentials ) -> Result<AccountTokens, StatusCode> {
+        // unknown users still pay for a verification so the response time doesn't reveal which usernames exist
+        let Some( user ) = self.store.find_user( &credentials.username ).await? else {
+            verify_password( credentials.password.clone(), dummy_hash().await?.clone() ).await;
+            return Err( StatusCode::UNAUTHORIZED );
+        };
+        if !verify_password( credentials.password.clone(), user.password_hash.clone() ).await {
+            return Err( StatusCode::UNAUTHORIZED );
+        }
+
+        self.issue( &user ).await
+    }
+
+    // refresh tokens are single use, each refresh revokes the presented token and hands back a new pair
+    pub async fn refresh( &self, refresh_token: &str ) -> Result<AccountTokens, StatusCode> {
+        let stored = self.active_refresh_token( refresh_token ).await?;
+
+        // revoking a user also cuts off the refresh tokens they were issued before it
+        if let Some( revocations ) = &self.revocations {
+            if revocations.is_revoked( None, &stored.user_id, Some( stored.date_created.timestamp() as u64 ) ).await? {
+                return Err( StatusCode::UNAUTHORIZED );
+            }
+        }
+
+        if self.store.revoke_refresh_token( stored.id ).await? == 0 {
+            return Err( StatusCode::UNAUTHORIZED );
+        }
+
+        let user_id = uuid::Uuid::parse_str( &stored.user_id ).map_err( |_| StatusCode::UNAUTHORIZED )?;
+        let user = self.store.get_user( user_id ).await?.ok_or( StatusCode::UNAUTHORIZED )?;
+        self.issue( &user ).await
+    }
+
+    pub async fn logout( &self, refresh_token: &str ) -> Result<(), StatusCode> {
+        let stored = self.active_refresh_token( refresh_token ).await?;
+        self.store.revoke_refresh_token( stored.id ).await?;
+        Ok(())
+    }
+
+    async fn active_refresh_token( &self, refresh_token: &str ) -> Result<RefreshToken, StatusCode> {
+        if !refresh_token.starts_with( REFRESH_PREFIX ) {
+            return Err( StatusCode::UNAUTHORIZED );
+        }
+
+        match self.store.find_refresh_token( &hash_key( refresh_token ) ).await? {
+            Some( stored ) if !stored.revoked && stored.expires_at > chrono::Utc::now() => Ok( stored ),
+            _ => Err( StatusCode::UNAUTHORIZED )
+        }
+    }
+}
+
+async fn http_signup( Extension( accounts ): Extension<Accounts>, Json( credentials ): Json<Credentials> ) -> Result<Json<AccountTokens>, StatusCode> {
+    Ok( Json( accounts.signup( &credentials ).await? ) )
+}
+
+async fn http_login( Extension( accounts ): Extension<Accounts>, Json( credentials ): Json<Credentials> ) -> Result<Json<AccountTokens>, StatusCode> {
+    Ok( Json( accounts.login( &credentials ).await? ) )
+}
+
+async fn http_refresh( Extension( accounts ): Extension<Accounts>, Json( request ): Json<RefreshRequest> ) -> Result<Json<AccountTokens>, StatusCode> {
+    Ok( Json( accounts.refresh( &request.refresh_token ).await? ) )
+}
+
+async fn http_logout( Extension( accounts ): Extension<Accounts>, Json( request ): Json<RefreshRequest> ) -> StatusCode {
+    match accounts.logout( &request.refresh_token ).await {
+        Ok(()) => StatusCode::OK,
+        Err( status ) => status
+    }
+}
+
+async fn http_jwks( Extension( accounts ): Extension<Accounts> ) -> Result<Json<Value>, StatusCode> {
+    accounts.jwks().cloned().map( Json ).ok_or( StatusCode::NOT_FOUND )
+}
+
+pub fn accounts_router( path: &str ) -> Router {
+    Router::new()
+        .route( &format!( "{}/signup", path ), axum::routing::post( http_signup ) )
+        .route( &format!( "{}/login", path ), axum::routing::post( http_login ) )
+        .route( &format!( "{}/refresh", path ), axum::routing::post( http_refresh ) )
+        .route( &format!( "{}/logout", path ), axum::routing::post( http_logout ) )
+        .route( &format!( "{}/.well-known/jwks.json", path ), axum::routing::get( http_jwks ) )
+}
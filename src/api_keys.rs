@@ -0,0 +1,281 @@
+use std::sync::Arc;
+use axum::{ async_trait, Extension, Json, Router };
+use axum::extract::{ Path, Query };
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use sha2::{ Digest, Sha256 };
+use crate::{ EndpointVerb, FieldValue, KeyValue, RecordId, SchemaTrait };
+use crate::keys::UUID_KEY;
+use crate::extractors::AdminUser;
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, FilterOp, Owner, is_identifier };
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+pub const API_KEYS_TABLE: &str = "janus_api_keys";
+const KEY_PREFIX: &str = "jk_";
+const DISPLAY_PREFIX_LENGTH: usize = 11;
+
+pub struct ApiKeySchema {}
+
+impl SchemaTrait for ApiKeySchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            prefix TEXT NOT NULL,
+            key_hash TEXT NOT NULL UNIQUE,
+            scopes TEXT NOT NULL,
+            expires_at TIMESTAMPTZ,
+            revoked BOOLEAN NOT NULL DEFAULT FALSE,
+            date_created TIMESTAMPTZ NOT NULL
+        );", API_KEYS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: uuid::Uuid,
+    pub user_id: String,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked: bool,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for ApiKey {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("user_id", FieldValue::STRING( &self.user_id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("prefix", FieldValue::STRING( &self.prefix )),
+            ("key_hash", FieldValue::STRING( &self.key_hash )),
+            ("scopes", FieldValue::STRING( &self.scopes )),
+            ("revoked", FieldValue::BOOLEAN( &self.revoked )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ];
+
+        if let Some( expires_at ) = &self.expires_at {
+            pairs.push( ("expires_at", FieldValue::DATE( expires_at )) );
+        }
+
+        pairs
+    }
+}
+
+impl ApiKey {
+    pub fn scope_list( &self ) -> Vec<String> {
+        self.scopes.split_whitespace().map( |scope| scope.to_string() ).collect()
+    }
+
+    pub fn is_active( &self ) -> bool {
+        !self.revoked && self.expires_at.map( |expires_at| expires_at > chrono::Utc::now() ).unwrap_or( true )
+    }
+
+    pub fn info( &self ) -> ApiKeyInfo {
+        ApiKeyInfo {
+            id: self.id,
+            user_id: self.user_id.clone(),
+            name: self.name.clone(),
+            prefix: self.prefix.clone(),
+            scopes: self.scope_list(),
+            expires_at: self.expires_at,
+            revoked: self.revoked,
+            date_created: self.date_created
+        }
+    }
+}
+
+// scopes are `resource:VERB`, either side may be `*`, and a key without scopes may do anything its user can
+pub fn scope_allows( scopes: &[String], resource: &str, verb: EndpointVerb ) -> bool {
+    if scopes.is_empty() {
+        return true;
+    }
+
+    let verb = verb.name();
+    scopes.iter().any( |scope| match scope.split_once( ':' ) {
+        Some( ( scope_resource, scope_verb ) ) => ( scope_resource == "*" || scope_resource == resource ) && ( scope_verb == "*" || scope_verb == verb ),
+        None => false
+    } )
+}
+
+fn valid_scope( scope: &str ) -> bool {
+    match scope.split_once( ':' ) {
+        Some( ( resource, verb ) ) => ( resource == "*" || is_identifier( resource ) ) && matches!( verb, "*" | "GET" | "POST" | "PUT" | "DELETE" ),
+        None => false
+    }
+}
+
+pub fn hash_key( key: &str ) -> String {
+    hex::encode( Sha256::digest( key.as_bytes() ) )
+}
+
+fn generate_key() -> String {
+    format!( "{}{}{}", KEY_PREFIX, uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple() )
+}
+
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn find_key( &self, key_hash: &str ) -> Result<Option<ApiKey>, StatusCode>;
+    async fn insert_key( &self, key: &ApiKey ) -> Result<(), StatusCode>;
+    async fn list_keys( &self, user_id: Option<&str> ) -> Result<Vec<ApiKey>, StatusCode>;
+    async fn revoke_key( &self, id: uuid::Uuid ) -> Result<u64, StatusCode>;
+}
+
+#[async_trait]
+impl<S> ApiKeyStore for S where S: Storage, ApiKey: FromStorageRow<S> {
+    async fn find_key( &self, key_hash: &str ) -> Result<Option<ApiKey>, StatusCode> {
+        let key_hash = key_hash.to_string();
+        let query = ReadQuery {
+            table: API_KEYS_TABLE,
+            filters: vec![ Filter { column: "key_hash", op: FilterOp::EQ, value: FieldValue::STRING( &key_hash ) } ],
+            owner: None,
+            order_by: "id",
+            descending: true,
+            limit: Some( 1 ),
+            offset: None
+        };
+
+        match self.select( &query ).await?.into_iter().next() {
+            Some( row ) => Ok( Some( ApiKey::from_storage_row( row )? ) ),
+            None => Ok( None )
+        }
+    }
+
+    async fn insert_key( &self, key: &ApiKey ) -> Result<(), StatusCode> {
+        self.insert( API_KEYS_TABLE, UUID_KEY, &key.key_value_pairs() ).await?;
+        Ok(())
+    }
+
+    async fn list_keys( &self, user_id: Option<&str> ) -> Result<Vec<ApiKey>, StatusCode> {
+        let query = ReadQuery {
+            table: API_KEYS_TABLE,
+            filters: vec![],
+            owner: user_id.map( |user_id| Owner { column: "user_id", user_id: user_id.to_string(), shared: None } ),
+            order_by: "date_created",
+            descending: true,
+            limit: None,
+            offset: None
+        };
+
+        self.select( &query ).await?
+            .into_iter()
+            .map( ApiKey::from_storage_row )
+            .collect()
+    }
+
+    async fn revoke_key( &self, id: uuid::Uuid ) -> Result<u64, StatusCode> {
+        self.update( API_KEYS_TABLE, UUID_KEY, &RecordId::from( id ), &[ ("revoked", FieldValue::BOOLEAN( &true )) ], None ).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApiKey {
+    pub user_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyInfo {
+    pub id: uuid::Uuid,
+    pub user_id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked: bool,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+// the plaintext key is only ever returned here, storage keeps its hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo
+}
+
+#[derive(Clone)]
+pub struct ApiKeys {
+    store: Arc<dyn ApiKeyStore>
+}
+
+impl ApiKeys {
+    pub fn new<K: ApiKeyStore + 'static>( store: K ) -> ApiKeys {
+        ApiKeys { store: Arc::new( store ) }
+    }
+
+    pub async fn create( &self, request: &CreateApiKey ) -> Result<CreatedApiKey, StatusCode> {
+        if request.user_id.is_empty() || request.name.is_empty() || !request.scopes.iter().all( |scope| valid_scope( scope ) ) {
+            return Err( StatusCode::BAD_REQUEST );
+        }
+
+        let key = generate_key();
+        let api_key = ApiKey {
+            id: uuid::Uuid::new_v4(),
+            user_id: request.user_id.clone(),
+            name: request.name.clone(),
+            prefix: key[..DISPLAY_PREFIX_LENGTH].to_string(),
+            key_hash: hash_key( &key ),
+            scopes: request.scopes.join( " " ),
+            expires_at: request.expires_at,
+            revoked: false,
+            date_created: chrono::Utc::now()
+        };
+        self.store.insert_key( &api_key ).await?;
+
+        Ok( CreatedApiKey { key, info: api_key.info() } )
+    }
+
+    pub async fn authenticate( &self, key: &str ) -> Result<ApiKey, StatusCode> {
+        if !key.starts_with( KEY_PREFIX ) {
+            return Err( StatusCode::UNAUTHORIZED );
+        }
+
+        match self.store.find_key( &hash_key( key ) ).await? {
+            Some( api_key ) if api_key.is_active() => Ok( api_key ),
+            _ => Err( StatusCode::UNAUTHORIZED )
+        }
+    }
+
+    pub async fn list( &self, user_id: Option<&str> ) -> Result<Vec<ApiKeyInfo>, StatusCode> {
+        Ok( self.store.list_keys( user_id ).await?.iter().map( ApiKey::info ).collect() )
+    }
+
+    pub async fn revoke( &self, id: uuid::Uuid ) -> Result<u64, StatusCode> {
+        self.store.revoke_key( id ).await
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListApiKeysQuery {
+    pub user_id: Option<String>
+}
+
+async fn http_create_key( _admin: AdminUser, Extension( api_keys ): Extension<ApiKeys>, Json( request ): Json<CreateApiKey> ) -> Result<Json<CreatedApiKey>, StatusCode> {
+    Ok( Json( api_keys.create( &request ).await? ) )
+}
+
+async fn http_list_keys( _admin: AdminUser, Extension( api_keys ): Extension<ApiKeys>, Query( query ): Query<ListApiKeysQuery> ) -> Result<Json<Vec<ApiKeyInfo>>, StatusCode> {
+    Ok( Json( api_keys.list( query.user_id.as_deref() ).await? ) )
+}
+
+async fn http_revoke_key( _admin: AdminUser, Extension( api_keys ): Extension<ApiKeys>, Path( id ): Path<uuid::Uuid> ) -> StatusCode {
+    match api_keys.revoke( id ).await {
+        Ok( 0 ) => StatusCode::BAD_REQUEST,
+        Ok(_) => StatusCode::OK,
+        Err( status ) => status
+    }
+}
+
+pub fn api_keys_router( path: &str ) -> Router {
+    Router::new()
+        .route( path, axum::routing::post( http_create_key ).get( http_list_keys ) )
+        .route( &format!( "{}/:id", path ), axum::routing::delete( http_revoke_key ) )
+}
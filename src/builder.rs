@@ -0,0 +1,368 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use axum::{ Extension, Router, Json };
+use axum::body::BoxBody;
+use axum::http::header;
+use axum::response::{ IntoResponse, Response };
+use serde::{ Serialize, Deserialize };
+use sqlx::postgres::PgPool;
+use tower_http::cors::CorsLayer;
+use crate::{ CrudConfig, InputSerializer, OutputSerializer, KeyValue, SchemaTrait };
+use crate::storage::{ Storage, FromStorageRow };
+use crate::extractors::AuthConfig;
+use crate::registry::{ ResourceRegistry, ResourceInfo };
+use crate::openapi::OpenApiConfig;
+use crate::api_keys::{ ApiKey, ApiKeys };
+use crate::revocations::{ Revocation, Revocations };
+use crate::sharing::{ Grant, Sharing };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    PLAIN,
+    JSON
+}
+
+type ApiKeysFactory<S> = fn( &S ) -> ApiKeys;
+type RevocationsFactory<S> = fn( &S ) -> Revocations;
+type SharingFactory<S> = fn( &S ) -> Sharing;
+#[cfg(feature = "accounts")]
+type AccountsFactory<S> = fn( &S, AuthConfig, &crate::accounts::AccountsConfig ) -> anyhow::Result<crate::accounts::Accounts>;
+
+pub struct Janus {}
+
+impl Janus {
+    pub fn builder() -> JanusBuilder {
+        JanusBuilder::new()
+    }
+
+    pub fn with_storage<S: Storage>( storage: S ) -> JanusBuilder<S> {
+        JanusBuilder::new().storage( storage )
+    }
+}
+
+pub struct JanusBuilder<S: Storage = PgPool> {
+    router: Router,
+    registry: ResourceRegistry,
+    prefix: String,
+    storage: Option<S>,
+    auth: Option<AuthConfig>,
+    cors: Option<CorsLayer>,
+    error_format: ErrorFormat,
+    openapi: Option<OpenApiConfig>,
+    api_keys: Option<( String, ApiKeysFactory<S> )>,
+    revocations: Option<( String, RevocationsFactory<S> )>,
+    sharing: Option<( String, SharingFactory<S> )>,
+    #[cfg(feature = "accounts")]
+    accounts: Option<( crate::accounts::AccountsConfig, AccountsFactory<S> )>,
+    #[cfg(feature = "graphql")]
+    graphql: Option<String>,
+    #[cfg(feature = "graphql")]
+    graphql_parts: crate::graphql::GraphqlSchemaParts
+}
+
+impl<S: Storage> Default for JanusBuilder<S> {
+    fn default() -> Self {
+        JanusBuilder::new()
+    }
+}
+
+impl<S: Storage> JanusBuilder<S> {
+    pub fn new() -> JanusBuilder<S> {
+        JanusBuilder {
+            router: Router::new(),
+            registry: ResourceRegistry::new(),
+            prefix: String::new(),
+            storage: None,
+            auth: None,
+            cors: None,
+            error_format: ErrorFormat::PLAIN,
+            openapi: None,
+            api_keys: None,
+            revocations: None,
+            sharing: None,
+            #[cfg(feature = "accounts")]
+            accounts: None,
+            #[cfg(feature = "graphql")]
+            graphql: None,
+            #[cfg(feature = "graphql")]
+            graphql_parts: crate::graphql::GraphqlSchemaParts::default()
+        }
+    }
+
+    pub fn prefix( mut self, prefix: &str ) -> Self {
+        self.prefix = normalize_prefix( prefix );
+        self
+    }
+
+    pub fn storage( mut self, storage: S ) -> Self {
+        self.storage = Some( storage );
+        self
+    }
+
+    pub fn pool( self, pool: S ) -> Self {
+        self.storage( pool )
+    }
+
+    pub fn auth( mut self, auth: AuthConfig ) -> Self {
+        self.auth = Some( auth );
+        self
+    }
+
+    pub fn cors( mut self, cors: CorsLayer ) -> Self {
+        self.cors = Some( cors );
+        self
+    }
+
+    pub fn error_format( mut self, error_format: ErrorFormat ) -> Self {
+        self.error_format = error_format;
+        self
+    }
+
+    pub fn openapi( mut self, config: OpenApiConfig ) -> Self {
+        self.openapi = Some( config );
+        self
+    }
+
+    // the key store is captured here, where the storage is known to hold api key rows
+    pub fn api_keys( mut self, path: &str ) -> Self where ApiKey: FromStorageRow<S> {
+        self.api_keys = Some( ( path.to_string(), |storage: &S| ApiKeys::new( storage.clone() ) ) );
+        self
+    }
+
+    pub fn revocations( mut self, path: &str ) -> Self where Revocation: FromStorageRow<S> {
+        self.revocations = Some( ( path.to_string(), |storage: &S| Revocations::new( storage.clone() ) ) );
+        self
+    }
+
+    pub fn sharing( mut self, path: &str ) -> Self where Grant: FromStorageRow<S> {
+        self.sharing = Some( ( path.to_string(), |storage: &S| Sharing::new( storage.clone() ) ) );
+        self
+    }
+
+    #[cfg(feature = "accounts")]
+    pub fn accounts( mut self, config: crate::accounts::AccountsConfig ) -> Self where
+            crate::accounts::User: FromStorageRow<S>,
+            crate::accounts::RefreshToken: FromStorageRow<S> {
+        self.accounts = Some( ( config, |storage: &S, auth, config| crate::accounts::Accounts::new( storage.clone(), auth, config ) ) );
+        self
+    }
+
+    #[cfg(feature = "graphql")]
+    pub fn graphql( mut self, path: &str ) -> Self {
+        self.graphql = Some( path.to_string() );
+        self
+    }
+
+    pub fn resource<T, UP, QP>( self ) -> Self where
+            T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue,
+            UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue,
+            QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue {
+        self.resource_at::<T, UP, QP>( "" )
+    }
+
+    pub fn resource_at<T, UP, QP>( self, prefix: &str ) -> Self where
+            T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue,
+            UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue,
+            QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue {
+        self.resource_at_with_output::<T, UP, QP, T>( prefix )
+    }
+
+    pub fn resource_with_output<T, UP, QP, OP>( self ) -> Self where
+            T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue,
+            UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue,
+            QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue,
+            OP: Serialize + Send + 'static + OutputSerializer<T> {
+        self.resource_at_with_output::<T, UP, QP, OP>( "" )
+    }
+
+    pub fn resource_at_with_output<T, UP, QP, OP>( mut self, prefix: &str ) -> Self where
+            T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue,
+            UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue,
+            QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue,
+            OP: Serialize + Send + 'static + OutputSerializer<T> {
+        let full_prefix = format!( "{}{}", self.prefix, normalize_prefix( prefix ) );
+
+        // the model keeps the resource's name but describes what GET actually returns
+        let mut info = ResourceInfo::of::<T, UP, QP>( &full_prefix );
+        info.model.fields = OP::output_fields();
+
+        if let Some( ( path, other ) ) = self.registry.type_conflict( &info ) {
+            panic!( "{} and {} share a type name, rename one so their schemas stay apart", path, other );
+        }
+
+        #[cfg(feature = "graphql")]
+        if self.registry.get( info.endpoint_name ).is_none() {
+            crate::graphql::resource_schema::<S, T, UP, QP, OP>( &info, &mut self.graphql_parts );
+        }
+
+        if self.registry.register( info ) {
+            self.router = mount( self.router, &full_prefix, crate::create_storage_router_with_output::<S, T, UP, QP, OP>() );
+        }
+        self
+    }
+
+    pub fn tables<SC>( mut self, prefix: &str ) -> Self where SC: SchemaTrait + Send + Sync + 'static + Unpin + Serialize {
+        let full_prefix = format!( "{}{}", self.prefix, normalize_prefix( prefix ) );
+
+        self.router = mount( self.router, &full_prefix, crate::create_tables_router::<SC>() );
+        self
+    }
+
+    pub fn merge( mut self, router: Router ) -> Self {
+        self.router = self.router.merge( router );
+        self
+    }
+
+    pub fn registry( &self ) -> &ResourceRegistry {
+        &self.registry
+    }
+
+    pub fn openapi_config( &self ) -> Option<&OpenApiConfig> {
+        self.openapi.as_ref()
+    }
+
+    pub fn build( self ) -> Router {
+        let mut router = self.router;
+        if let Some( config ) = &self.openapi {
+            router = router.merge( crate::openapi::openapi_router( &self.registry, config ) );
+        }
+
+        #[cfg(feature = "graphql")]
+        if let Some( path ) = &self.graphql {
+            router = router.merge( crate::graphql::graphql_router::<S>( self.graphql_parts, path ) );
+        }
+
+        // every extension router is merged before any layer goes on, so each route sees every extension
+        let api_keys = match ( &self.api_keys, &self.storage ) {
+            ( Some( ( path, api_keys ) ), Some( storage ) ) => {
+                router = router.merge( crate::api_keys::api_keys_router( path ) );
+                Some( api_keys( storage ) )
+            },
+            _ => None
+        };
+
+        let revocations = match ( &self.revocations, &self.storage ) {
+            ( Some( ( path, revocations ) ), Some( storage ) ) => {
+                router = router.merge( crate::revocations::revocations_router( path ) );
+                Some( revocations( storage ) )
+            },
+            _ => None
+        };
+
+        let sharing = match ( &self.sharing, &self.storage ) {
+            ( Some( ( path, sharing ) ), Some( storage ) ) => {
+                router = router.merge( crate::sharing::sharing_router( path ) );
+                Some( sharing( storage ) )
+            },
+            _ => None
+        };
+
+        #[cfg(feature = "accounts")]
+        let accounts = match ( &self.accounts, &self.storage ) {
+            ( Some( ( config, accounts ) ), Some( storage ) ) => {
+                let auth = self.auth.clone().unwrap_or_else( AuthConfig::from_env );
+                let mut accounts = match accounts( storage, auth, config ) {
+                    Ok( accounts ) => accounts,
+                    Err( error ) => panic!( "Failed to set up accounts: {:?}", error )
+                };
+                if let Some( revocations ) = &revocations {
+                    accounts = accounts.revocations( revocations.clone() );
+                }
+                router = router.merge( crate::accounts::accounts_router( &config.path ) );
+                Some( accounts )
+            },
+            _ => None
+        };
+
+        if let Some( api_keys ) = api_keys {
+            router = router.layer( Extension( api_keys ) );
+        }
+
+        if let Some( revocations ) = revocations {
+            router = router.layer( Extension( revocations ) );
+        }
+
+        if let Some( sharing ) = sharing {
+            router = router.layer( Extension( sharing ) );
+        }
+
+        #[cfg(feature = "accounts")]
+        if let Some( accounts ) = accounts {
+            router = router.layer( Extension( accounts ) );
+        }
+
+        router = router.layer( Extension( Arc::new( self.registry ) ) );
+
+        if let Some( auth ) = self.auth {
+            router = router.layer( Extension( auth ) );
+        }
+
+        if let Some( storage ) = self.storage {
+            router = router.layer( Extension( storage ) );
+        }
+
+        if self.error_format == ErrorFormat::JSON {
+            router = router.layer( axum::middleware::map_response( json_error_response ) );
+        }
+
+        if let Some( cors ) = self.cors {
+            router = router.layer( cors );
+        }
+
+        router
+    }
+}
+
+fn normalize_prefix( prefix: &str ) -> String {
+    let trimmed = prefix.trim_matches( '/' );
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!( "/{}", trimmed )
+    }
+}
+
+fn mount( router: Router, prefix: &str, nested: Router ) -> Router {
+    if prefix.is_empty() {
+        router.merge( nested )
+    } else {
+        router.nest( prefix, nested )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    status: u16,
+    error: String
+}
+
+async fn json_error_response( response: Response<BoxBody> ) -> Response {
+    let status = response.status();
+    if !( status.is_client_error() || status.is_server_error() ) {
+        return response;
+    }
+
+    let is_json = response.headers().get( header::CONTENT_TYPE )
+        .and_then( |value| value.to_str().ok() )
+        .map( |value| value.starts_with( "application/json" ) )
+        .unwrap_or( false );
+    if is_json {
+        return response;
+    }
+
+    let bytes = hyper::body::to_bytes( response.into_body() ).await.unwrap_or_default();
+    let message = String::from_utf8_lossy( &bytes ).trim().to_string();
+    let error = if message.is_empty() {
+        status.canonical_reason().unwrap_or( "Error" ).to_string()
+    } else {
+        message
+    };
+
+    ( status, Json( ErrorBody { status: status.as_u16(), error } ) ).into_response()
+}
+
+impl<S: Storage> From<JanusBuilder<S>> for Router {
+    fn from( builder: JanusBuilder<S> ) -> Router {
+        builder.build()
+    }
+}
@@ -0,0 +1,89 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use crate::builder::JanusBuilder;
+use crate::dev::DevIssuer;
+use crate::storage::Storage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    SERVE,
+    OPENAPI( Option<PathBuf> ),
+    TYPESCRIPT( Option<PathBuf> ),
+    ISSUER( Option<String> )
+}
+
+pub const USAGE: &str = "usage: <binary> [serve | openapi [OUTPUT] | typescript [OUTPUT] | issuer [ADDR]]";
+
+const DEFAULT_ISSUER_ADDR: &str = "127.0.0.1:4000";
+
+pub fn parse_command<I: IntoIterator<Item = String>>( args: I ) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = args.next();
+    let output = args.next().map( PathBuf::from );
+
+    if let Some( extra ) = args.next() {
+        return Err( format!( "unexpected argument `{}`\n{}", extra, USAGE ) );
+    }
+
+    match ( command.as_deref(), output ) {
+        ( None, _ ) | ( Some( "serve" ), None ) => Ok( Command::SERVE ),
+        ( Some( "openapi" ), output ) => Ok( Command::OPENAPI( output ) ),
+        ( Some( "typescript" ), output ) => Ok( Command::TYPESCRIPT( output ) ),
+        ( Some( "issuer" ), addr ) => Ok( Command::ISSUER( addr.map( |addr| addr.display().to_string() ) ) ),
+        ( Some( "serve" ), Some( extra ) ) => Err( format!( "unexpected argument `{}`\n{}", extra.display(), USAGE ) ),
+        ( Some( other ), _ ) => Err( format!( "unknown command `{}`\n{}", other, USAGE ) )
+    }
+}
+
+fn write_output( output: Option<PathBuf>, contents: &str ) -> anyhow::Result<()> {
+    match output {
+        Some( path ) => {
+            std::fs::write( &path, contents )?;
+            println!( "wrote {}", path.display() );
+        },
+        None => println!( "{}", contents )
+    }
+    Ok(())
+}
+
+pub async fn run<S: Storage>( builder: JanusBuilder<S>, addr: SocketAddr ) -> anyhow::Result<()> {
+    let command = parse_command( std::env::args().skip(1) ).map_err( anyhow::Error::msg )?;
+    run_command( builder, addr, command ).await
+}
+
+pub async fn run_command<S: Storage>( builder: JanusBuilder<S>, addr: SocketAddr, command: Command ) -> anyhow::Result<()> {
+    match command {
+        Command::SERVE => {
+            println!( "listening on {}", addr );
+            axum::Server::bind( &addr )
+                .serve( builder.build().into_make_service() )
+                .await?;
+            Ok(())
+        },
+        Command::OPENAPI( output ) => {
+            let ( title, version ) = match builder.openapi_config() {
+                Some( config ) => ( config.title.clone(), config.version.clone() ),
+                None => ( "Janus API".to_string(), env!("CARGO_PKG_VERSION").to_string() )
+            };
+            let document = crate::openapi::openapi_document( builder.registry(), &title, &version );
+            write_output( output, &serde_json::to_string_pretty( &document )? )
+        },
+        Command::TYPESCRIPT( output ) => {
+            write_output( output, &crate::typescript::generate_typescript( builder.registry() ) )
+        },
+        Command::ISSUER( issuer_addr ) => {
+            let ( issuer, server ) = DevIssuer::bind( issuer_addr.as_deref().unwrap_or( DEFAULT_ISSUER_ADDR ) ).await?;
+            let config = issuer.auth_config();
+            println!( "dev issuer listening, point the api at it with:" );
+            println!( "JWT_AUDIENCE={}", config.audience );
+            println!( "CLERK_ISSUER={}", config.clerk_issuer );
+            println!( "AUTH0_AUDIENCE={}", config.auth0_audience );
+            println!( "AUTH0_ISSUER={}", config.auth0_issuer );
+            println!( "JWT_ISSUER={}", config.jwt_issuer );
+            println!( "JWT_SECRET={}", config.jwt_secret );
+            println!( "tokens: POST {}/token {{\"sub\": \"user_1\", \"permissions\": [\"read:admin\"], \"subscription\": \"pro\"}}", config.clerk_issuer );
+            server.await?;
+            Ok(())
+        }
+    }
+}
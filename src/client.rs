@@ -0,0 +1,228 @@
+use std::fmt;
+use std::marker::PhantomData;
+use axum::http::StatusCode;
+use serde::{ Serialize, de::DeserializeOwned };
+use crate::{ CrudConfig, InputSerializer, RecordId };
+
+#[derive(Debug)]
+pub enum ClientError {
+    TRANSPORT( reqwest::Error ),
+    UNAUTHORIZED( String ),
+    FORBIDDEN( String ),
+    NOTFOUND( String ),
+    BADREQUEST( String ),
+    UNPROCESSABLE( String ),
+    UNIMPLEMENTED( String ),
+    SERVER( StatusCode, String )
+}
+
+impl ClientError {
+    pub fn from_status( status: StatusCode, body: &str ) -> ClientError {
+        let message = match serde_json::from_str::<serde_json::Value>( body ) {
+            Ok( value ) => match value.get( "error" ).and_then( |error| error.as_str() ) {
+                Some( error ) => error.to_string(),
+                None => body.to_string()
+            },
+            Err(_) => body.to_string()
+        };
+
+        match status {
+            StatusCode::UNAUTHORIZED => ClientError::UNAUTHORIZED( message ),
+            StatusCode::FORBIDDEN => ClientError::FORBIDDEN( message ),
+            StatusCode::NOT_FOUND => ClientError::NOTFOUND( message ),
+            StatusCode::BAD_REQUEST => ClientError::BADREQUEST( message ),
+            StatusCode::UNPROCESSABLE_ENTITY => ClientError::UNPROCESSABLE( message ),
+            StatusCode::NOT_IMPLEMENTED => ClientError::UNIMPLEMENTED( message ),
+            _ => ClientError::SERVER( status, message )
+        }
+    }
+
+    pub fn status( &self ) -> Option<StatusCode> {
+        match self {
+            ClientError::TRANSPORT( error ) => error.status(),
+            ClientError::UNAUTHORIZED(_) => Some( StatusCode::UNAUTHORIZED ),
+            ClientError::FORBIDDEN(_) => Some( StatusCode::FORBIDDEN ),
+            ClientError::NOTFOUND(_) => Some( StatusCode::NOT_FOUND ),
+            ClientError::BADREQUEST(_) => Some( StatusCode::BAD_REQUEST ),
+            ClientError::UNPROCESSABLE(_) => Some( StatusCode::UNPROCESSABLE_ENTITY ),
+            ClientError::UNIMPLEMENTED(_) => Some( StatusCode::NOT_IMPLEMENTED ),
+            ClientError::SERVER( status, _ ) => Some( *status )
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        match self {
+            ClientError::TRANSPORT( error ) => write!( f, "request failed: {}", error ),
+            ClientError::SERVER( status, message ) => write!( f, "{}: {}", status, message ),
+            ClientError::UNAUTHORIZED( message ) | ClientError::FORBIDDEN( message ) | ClientError::NOTFOUND( message ) |
+            ClientError::BADREQUEST( message ) | ClientError::UNPROCESSABLE( message ) | ClientError::UNIMPLEMENTED( message ) => {
+                write!( f, "{}: {}", self.status().unwrap_or( StatusCode::INTERNAL_SERVER_ERROR ), message )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from( error: reqwest::Error ) -> ClientError {
+        ClientError::TRANSPORT( error )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: i32,
+    pub offset: i32
+}
+
+pub struct ResourceClient<T, UP, QP> {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    subscription: Option<String>,
+    _types: PhantomData<( T, UP, QP )>
+}
+
+impl<T, UP, QP> Clone for ResourceClient<T, UP, QP> {
+    fn clone( &self ) -> Self {
+        ResourceClient {
+            http: self.http.clone(),
+            base_url: self.base_url.clone(),
+            token: self.token.clone(),
+            subscription: self.subscription.clone(),
+            _types: PhantomData
+        }
+    }
+}
+
+impl<T, UP, QP> ResourceClient<T, UP, QP> where
+        T: CrudConfig + Serialize + DeserializeOwned,
+        UP: InputSerializer<T> + Serialize,
+        QP: Serialize {
+
+    pub fn new( base_url: &str ) -> Self {
+        ResourceClient {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches( '/' ).to_string(),
+            token: None,
+            subscription: None,
+            _types: PhantomData
+        }
+    }
+
+    pub fn with_http_client( mut self, http: reqwest::Client ) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn with_token( mut self, token: &str ) -> Self {
+        self.token = Some( token.to_string() );
+        self
+    }
+
+    pub fn with_subscription( mut self, token: &str ) -> Self {
+        self.subscription = Some( token.to_string() );
+        self
+    }
+
+    pub fn collection_url( &self ) -> String {
+        format!( "{}/{}", self.base_url, T::endpoint_name() )
+    }
+
+    fn item_url( &self, id: &RecordId ) -> String {
+        let segments = match id {
+            RecordId::COMPOSITE( parts ) => parts.iter().map( |part| encode_segment( &part.to_string() ) ).collect::<Vec<_>>(),
+            id => vec![ encode_segment( &id.to_string() ) ]
+        };
+        format!( "{}/{}", self.collection_url(), segments.join( "/" ) )
+    }
+
+    fn authorize( &self, mut request: reqwest::RequestBuilder ) -> reqwest::RequestBuilder {
+        if let Some( token ) = &self.token {
+            request = request.header( "Authorization", format!( "Bearer {}", token ) );
+        }
+        if let Some( subscription ) = &self.subscription {
+            request = request.header( "Subscription", format!( "Bearer {}", subscription ) );
+        }
+        request
+    }
+
+    async fn send( &self, request: reqwest::RequestBuilder ) -> Result<reqwest::Response, ClientError> {
+        let response = self.authorize( request ).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok( response );
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        Err( ClientError::from_status( status, &body ) )
+    }
+
+    pub async fn list( &self, query: &QP ) -> Result<Vec<T>, ClientError> {
+        let response = self.send( self.http.get( self.collection_url() ).query( query ) ).await?;
+        Ok( response.json::<Vec<T>>().await? )
+    }
+
+    pub async fn list_page( &self, query: &QP, page: Page ) -> Result<Vec<T>, ClientError> {
+        let request = self.http.get( self.collection_url() )
+            .query( query )
+            .query( &[ ( "limit", page.limit ), ( "offset", page.offset ) ] );
+        let response = self.send( request ).await?;
+        Ok( response.json::<Vec<T>>().await? )
+    }
+
+    pub async fn list_all( &self, query: &QP, page_size: i32 ) -> Result<Vec<T>, ClientError> {
+        let page_size = page_size.max( 1 );
+        let mut page = Page { limit: page_size, offset: 0 };
+        let mut objects = vec![];
+        let mut previous = None;
+
+        loop {
+            let rows = self.list_page( query, page ).await?;
+            let count = rows.len() as i32;
+
+            // a resource whose QP has no limit/offset fields ignores paging and returns the same rows every time
+            let current = serde_json::to_value( &rows ).ok();
+            if count == 0 || ( current.is_some() && current == previous ) {
+                return Ok( objects );
+            }
+            objects.extend( rows );
+
+            if count != page_size {
+                return Ok( objects );
+            }
+            previous = current;
+            page.offset += page_size;
+        }
+    }
+
+    pub async fn create( &self, input: &UP ) -> Result<RecordId, ClientError> {
+        let response = self.send( self.http.post( self.collection_url() ).json( input ) ).await?;
+        Ok( response.json::<RecordId>().await? )
+    }
+
+    pub async fn update( &self, id: &RecordId, input: &UP ) -> Result<(), ClientError> {
+        self.send( self.http.put( self.item_url( id ) ).json( input ) ).await?;
+        Ok(())
+    }
+
+    pub async fn delete( &self, id: &RecordId ) -> Result<(), ClientError> {
+        self.send( self.http.delete( self.item_url( id ) ) ).await?;
+        Ok(())
+    }
+}
+
+// keys are free text, so anything outside the unreserved set is escaped before it becomes a path segment
+fn encode_segment( segment: &str ) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push( byte as char ),
+            _ => encoded.push_str( &format!( "%{:02X}", byte ) )
+        }
+    }
+    encoded
+}
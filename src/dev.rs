@@ -0,0 +1,261 @@
+use axum::{ Extension, Json, Router };
+use axum::http::StatusCode;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{ encode, Algorithm, EncodingKey, Header };
+use rsa::{ RsaPrivateKey, traits::PublicKeyParts, pkcs1::EncodeRsaPrivateKey };
+use serde::{ Serialize, Deserialize };
+use serde_json::{ json, Value };
+use tokio::task::JoinHandle;
+use crate::extractors::AuthConfig;
+
+const DEV_KEY_ID: &str = "janus-dev-key";
+const DEV_AUDIENCE: &str = "janus-dev";
+const DEFAULT_LIFETIME: u64 = 3600;
+
+#[derive(Debug, Serialize)]
+struct UserClaims<'a> {
+    sub: &'a str,
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    org_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    org_role: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    groups: &'a [String],
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    permissions: &'a [String]
+}
+
+#[derive(Debug, Serialize)]
+struct AdminClaims<'a> {
+    sub: &'a str,
+    subscription: &'a str,
+    permissions: &'a [String],
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriptionClaims<'a> {
+    subscription: &'a str,
+    clerk_id: &'a str,
+    aud: &'a str,
+    iss: &'a str,
+    iat: u64,
+    exp: u64,
+    jti: String
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenRequest {
+    pub sub: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    pub subscription: Option<String>,
+    pub expires_in: Option<u64>,
+    pub org_id: Option<String>,
+    pub org_role: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub admin_token: String,
+    pub subscription_token: Option<String>
+}
+
+#[derive(Clone)]
+pub struct DevIssuer {
+    config: AuthConfig,
+    encoding_key: EncodingKey,
+    jwks: Value
+}
+
+pub fn generate_key() -> anyhow::Result<RsaPrivateKey> {
+    Ok( RsaPrivateKey::new( &mut rsa::rand_core::OsRng, 2048 )? )
+}
+
+pub(crate) fn rsa_signing_key( key: &RsaPrivateKey, kid: &str ) -> anyhow::Result<( EncodingKey, Value )> {
+    let encoding_key = EncodingKey::from_rsa_der( key.to_pkcs1_der()?.as_bytes() );
+    let jwks = json!({
+        "keys": [ {
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": kid,
+            "n": URL_SAFE_NO_PAD.encode( key.n().to_bytes_be() ),
+            "e": URL_SAFE_NO_PAD.encode( key.e().to_bytes_be() )
+        } ]
+    });
+
+    Ok( ( encoding_key, jwks ) )
+}
+
+fn issued_at() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+fn expires_at( lifetime: u64 ) -> u64 {
+    issued_at() + lifetime
+}
+
+fn token_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl DevIssuer {
+    pub fn new( base_url: &str ) -> anyhow::Result<DevIssuer> {
+        DevIssuer::with_key( base_url, &generate_key()? )
+    }
+
+    pub fn with_key( base_url: &str, key: &RsaPrivateKey ) -> anyhow::Result<DevIssuer> {
+        let base_url = base_url.trim_end_matches( '/' );
+        let ( encoding_key, jwks ) = rsa_signing_key( key, DEV_KEY_ID )?;
+
+        // clerk issuers are fetched as `{issuer}/.well-known/jwks.json`, auth0 issuers as `{issuer}.well-known/jwks.json`
+        let config = AuthConfig {
+            audience: DEV_AUDIENCE.to_string(),
+            clerk_issuer: base_url.to_string(),
+            jwt_issuer: DEV_AUDIENCE.to_string(),
+            jwt_secret: uuid::Uuid::new_v4().to_string(),
+            auth0_audience: DEV_AUDIENCE.to_string(),
+            auth0_issuer: format!( "{}/", base_url ),
+            admin_permission: "read:admin".to_string(),
+            impersonation_permission: "impersonate:users".to_string(),
+            tenant_claim: "org_id".to_string(),
+            tenant_role_claim: "org_role".to_string(),
+            tenant_admin_role: "org:admin".to_string(),
+            groups_claim: "groups".to_string()
+        };
+
+        Ok( DevIssuer { config, encoding_key, jwks } )
+    }
+
+    pub async fn bind( addr: &str ) -> anyhow::Result<( DevIssuer, JoinHandle<()> )> {
+        DevIssuer::bind_with_key( addr, &generate_key()? ).await
+    }
+
+    pub async fn bind_with_key( addr: &str, key: &RsaPrivateKey ) -> anyhow::Result<( DevIssuer, JoinHandle<()> )> {
+        let listener = std::net::TcpListener::bind( addr )?;
+        let issuer = DevIssuer::with_key( &format!( "http://{}", listener.local_addr()? ), key )?;
+
+        let server = axum::Server::from_tcp( listener )?.serve( issuer.router().into_make_service() );
+        let handle = tokio::spawn( async move {
+            if let Err( error ) = server.await {
+                println!( "{:?}", error );
+            }
+        } );
+
+        Ok( ( issuer, handle ) )
+    }
+
+    pub fn auth_config( &self ) -> AuthConfig {
+        self.config.clone()
+    }
+
+    pub fn jwks( &self ) -> &Value {
+        &self.jwks
+    }
+
+    fn sign<C: Serialize>( &self, claims: &C ) -> String {
+        let mut header = Header::new( Algorithm::RS256 );
+        header.kid = Some( DEV_KEY_ID.to_string() );
+        encode( &header, claims, &self.encoding_key ).expect( "Failed to sign dev token" )
+    }
+
+    pub fn user_token( &self, sub: &str ) -> String {
+        self.sign( &UserClaims { sub, aud: &self.config.audience, iss: &self.config.clerk_issuer, iat: issued_at(), exp: expires_at( DEFAULT_LIFETIME ), jti: token_id(), org_id: None, org_role: None, groups: &[], permissions: &[] } )
+    }
+
+    pub fn tenant_token( &self, sub: &str, org_id: &str, org_role: &str ) -> String {
+        self.sign( &UserClaims { sub, aud: &self.config.audience, iss: &self.config.clerk_issuer, iat: issued_at(), exp: expires_at( DEFAULT_LIFETIME ), jti: token_id(), org_id: Some( org_id ), org_role: Some( org_role ), groups: &[], permissions: &[] } )
+    }
+
+    pub fn group_token( &self, sub: &str, groups: &[String] ) -> String {
+        self.sign( &UserClaims { sub, aud: &self.config.audience, iss: &self.config.clerk_issuer, iat: issued_at(), exp: expires_at( DEFAULT_LIFETIME ), jti: token_id(), org_id: None, org_role: None, groups, permissions: &[] } )
+    }
+
+    pub fn permission_token( &self, sub: &str, permissions: &[String] ) -> String {
+        self.sign( &UserClaims { sub, aud: &self.config.audience, iss: &self.config.clerk_issuer, iat: issued_at(), exp: expires_at( DEFAULT_LIFETIME ), jti: token_id(), org_id: None, org_role: None, groups: &[], permissions } )
+    }
+
+    pub fn admin_token( &self, sub: &str, permissions: &[String] ) -> String {
+        self.sign( &AdminClaims {
+            sub,
+            subscription: "none",
+            permissions,
+            aud: &self.config.auth0_audience,
+            iss: &self.config.auth0_issuer,
+            iat: issued_at(),
+            exp: expires_at( DEFAULT_LIFETIME ),
+            jti: token_id()
+        } )
+    }
+
+    pub fn subscription_token( &self, sub: &str, subscription: &str ) -> String {
+        let claims = SubscriptionClaims { subscription, clerk_id: sub, aud: &self.config.audience, iss: &self.config.jwt_issuer, iat: issued_at(), exp: expires_at( DEFAULT_LIFETIME ), jti: token_id() };
+        encode( &Header::new( Algorithm::HS256 ), &claims, &EncodingKey::from_secret( self.config.jwt_secret.as_bytes() ) ).expect( "Failed to sign dev token" )
+    }
+
+    pub fn issue( &self, request: &TokenRequest ) -> TokenResponse {
+        let exp = expires_at( request.expires_in.unwrap_or( DEFAULT_LIFETIME ) );
+        let subscription = request.subscription.as_deref().unwrap_or( "none" );
+
+        let access_token = self.sign( &UserClaims {
+            sub: &request.sub,
+            aud: &self.config.audience,
+            iss: &self.config.clerk_issuer,
+            iat: issued_at(),
+            exp,
+            jti: token_id(),
+            org_id: request.org_id.as_deref(),
+            org_role: request.org_role.as_deref(),
+            groups: &request.groups,
+            permissions: &request.permissions
+        } );
+        let admin_token = self.sign( &AdminClaims {
+            sub: &request.sub,
+            subscription,
+            permissions: &request.permissions,
+            aud: &self.config.auth0_audience,
+            iss: &self.config.auth0_issuer,
+            iat: issued_at(),
+            exp,
+            jti: token_id()
+        } );
+        let subscription_token = request.subscription.as_ref().map( |subscription| {
+            let claims = SubscriptionClaims { subscription, clerk_id: &request.sub, aud: &self.config.audience, iss: &self.config.jwt_issuer, iat: issued_at(), exp, jti: token_id() };
+            encode( &Header::new( Algorithm::HS256 ), &claims, &EncodingKey::from_secret( self.config.jwt_secret.as_bytes() ) ).expect( "Failed to sign dev token" )
+        } );
+
+        TokenResponse { access_token, admin_token, subscription_token }
+    }
+
+    pub fn router( &self ) -> Router {
+        Router::new()
+            .route( "/.well-known/jwks.json", axum::routing::get( jwks_handler ) )
+            .route( "/token", axum::routing::post( token_handler ) )
+            .layer( Extension( self.clone() ) )
+    }
+}
+
+async fn jwks_handler( Extension( issuer ): Extension<DevIssuer> ) -> Json<Value> {
+    Json( issuer.jwks.clone() )
+}
+
+async fn token_handler( Extension( issuer ): Extension<DevIssuer>, Json( request ): Json<TokenRequest> ) -> Result<Json<TokenResponse>, StatusCode> {
+    if request.sub.is_empty() {
+        return Err( StatusCode::BAD_REQUEST );
+    }
+    Ok( Json( issuer.issue( &request ) ) )
+}
@@ -1,11 +1,36 @@
-use axum::{  Extension, http::StatusCode, Json, extract::{Query, Path}};
+use axum::{  Extension, http::{ StatusCode, HeaderMap, Request, request::Parts }, Json, body::Body, extract::{Query, Path, FromRequestParts}};
 use serde::Serialize;
-use sqlx::{Row, FromRow, Result, postgres::{ PgRow, PgPool }};
+use serde_json::{ json, Value };
+use sqlx::{ Result, postgres::PgPool };
 use std::fmt::Debug;
-use crate::{ InputSerializer, CrudConfig, EndpointVerb, ObjectPermission, SchemaTrait, KeyValue, FieldValue };
-use crate::extractors::{ AuthUser, AdminUser };
+use crate::{ Action, ActionTarget, CrudError, InputSerializer, OutputSerializer, RequestContext, StateMachine, Transition, CrudConfig, EndpointVerb, ObjectPermission, AccessPermission, FieldPermission, SchemaTrait, KeyValue, FieldValue, RecordId };
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, FilterOp, Owner, Session, RequestTransaction };
+use crate::extractors::{ AuthUser, AdminUser, MaybeUser, Tenant };
+use crate::impersonation::record_impersonation;
+use crate::transitions::record_transition;
+use crate::sharing::{ grantees, shared_owner, GrantLevel };
 use serde::Deserialize;
 
+// who a generated operation runs for, the tenant only comes from user tokens
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user_id: String,
+    pub tenant: Option<Tenant>,
+    pub groups: Vec<String>
+}
+
+impl Caller {
+    fn user( user_id: &str ) -> Caller {
+        Caller { user_id: user_id.to_string(), tenant: None, groups: vec![] }
+    }
+}
+
+impl From<AuthUser> for Caller {
+    fn from( user: AuthUser ) -> Caller {
+        Caller { user_id: user.user_id, tenant: user.tenant, groups: user.groups }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResetTableInput {
     pub table_name: String,
@@ -81,369 +106,738 @@ pub async fn reset_tables<S: SchemaTrait> (
     Ok(())
 }
 
-pub async fn http_get_any<T, QP>( 
-    Extension( connection_pool ): Extension<PgPool>, 
-    Query( parameters ): Query<QP> ) -> Result<Json<Vec<T>>, StatusCode> 
-    where T: for<'r> FromRow<'r, PgRow> + Send + Unpin + CrudConfig, QP: Serialize + Debug + KeyValue + Send + Sync + Unpin {
+pub async fn http_get_any<S, T, QP, OP>( 
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Query( parameters ): Query<QP> ) -> Result<Json<Vec<Value>>, StatusCode> 
+    where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig + KeyValue, QP: Serialize + Debug + KeyValue + Send + Sync + Unpin, OP: Serialize + OutputSerializer<T> {
 
-    Ok( _http_get::<T, QP>( &connection_pool, parameters, None).await? )
+    let context = user_context( None, headers );
+    _http_get::<S, T, QP, OP>( &storage, parameters, None, context ).await
 }
 
-pub async fn http_get_auth<T, QP>( 
+pub async fn http_get_auth<S, T, QP, OP>( 
     user: AuthUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Query( params ): Query<QP> ) -> Result<Json<Vec<T>>, StatusCode> 
-    where T: for<'r> FromRow<'r, PgRow> + Send + Unpin + CrudConfig, QP: Serialize + KeyValue + Send + Sync + Unpin {
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Query( params ): Query<QP> ) -> Result<Json<Vec<Value>>, StatusCode> 
+    where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig + KeyValue, QP: Serialize + KeyValue + Send + Sync + Unpin, OP: Serialize + OutputSerializer<T> {
+
+    let context = user_context( Some( &user ), headers );
+    scope_check::<T>( &user, EndpointVerb::GET )?;
+    _http_get::<S, T, QP, OP>( &storage, params, Some( Caller::from( user ) ), context ).await
+}
+
+pub async fn http_get_optional<S, T, QP, OP>( 
+    MaybeUser( user ): MaybeUser,
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Query( params ): Query<QP> ) -> Result<Json<Vec<Value>>, StatusCode> 
+    where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig + KeyValue, QP: Serialize + KeyValue + Send + Sync + Unpin, OP: Serialize + OutputSerializer<T> {
 
-    Ok( _http_get::<T, QP>( &connection_pool, params, Some(user.user_id)).await? )
+    let context = user_context( user.as_ref(), headers );
+    let caller = optional_caller::<T>( user, EndpointVerb::GET )?;
+    _http_get::<S, T, QP, OP>( &storage, params, caller, context ).await
 }
 
-pub async fn http_get_admin<T, QP>( 
+pub async fn http_get_admin<S, T, QP, OP>( 
     user: AdminUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Query( params ): Query<QP> ) -> Result<Json<Vec<T>>, StatusCode> 
-    where T: for<'r> FromRow<'r, PgRow> + Send + Unpin + CrudConfig, QP: Serialize + KeyValue + Send + Sync + Unpin {
-
-    Ok( _http_get::<T, QP>( &connection_pool, params, Some(user.user_id)).await? )
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Query( params ): Query<QP> ) -> Result<Json<Vec<Value>>, StatusCode> 
+    where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig + KeyValue, QP: Serialize + KeyValue + Send + Sync + Unpin, OP: Serialize + OutputSerializer<T> {
+
+    let context = admin_context( &user, headers );
+    let caller = admin_caller( &user, EndpointVerb::GET );
+    _http_get::<S, T, QP, OP>( &storage, params, caller, context ).await
 }
 
-async fn _http_get<T, QP>( connection_pool: &PgPool, params: QP, user_id: Option<String> ) -> Result<Json<Vec<T>>, StatusCode> 
-    where T: for<'r> FromRow<'r, PgRow> + Send + Unpin + CrudConfig, QP: Serialize + KeyValue + Send + Sync + Unpin {
+pub(crate) async fn _http_get<S, T, QP, OP>( storage: &S, params: QP, caller: Option<Caller>, context: RequestContext ) -> Result<Json<Vec<Value>>, StatusCode> 
+    where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig + KeyValue, QP: Serialize + KeyValue + Send + Sync + Unpin, OP: Serialize + OutputSerializer<T> {
     
-    let user_id_matched = match T::get_object_permissions( &EndpointVerb::GET ) {
-        ObjectPermission::ALL => None,
-        ObjectPermission::OWNER => user_id,
+    let scope = row_scope::<T>( EndpointVerb::GET, caller.as_ref(), context.admin )?;
+    let storage = &session_storage( storage, caller.as_ref(), context.admin );
+    let context = request_transaction( storage, context );
+    audit_impersonation::<S, T>( storage, &context, EndpointVerb::GET, None ).await?;
+
+    let rows = match T::is_custom( &EndpointVerb::GET ) {
+        true => T::custom_read::<S, T, QP>( storage, &context, params, scope.map( |scope| scope.user_id ) ).await,
+        false => read::<S, T, QP>( storage, params, scope ).await
     };
+    let Json( rows ) = finish_request::<S, _, _>( &context, rows ).await?;
 
-    match T::is_custom( &EndpointVerb::GET ) {
-        true => T::custom_read::<T, QP>( &connection_pool, params, user_id_matched ).await,
-        false => read::<T, QP>( &connection_pool, params, user_id_matched ).await
-    }
+    Ok( Json( rows.into_iter().map( |row| shape_row::<T, OP>( row, &context ) ).collect::<Result<Vec<Value>, StatusCode>>()? ) )
 }
 
 
 
 
-pub async fn http_post_any<T, UP>( 
-    Extension( connection_pool ): Extension<PgPool>, 
-    Json( params ): Json<UP> ) -> Result<Json<uuid::Uuid>, StatusCode> 
-    where T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue  {
+pub async fn http_post_any<S, T, UP>( 
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Json( params ): Json<UP> ) -> Result<Json<RecordId>, CrudError> 
+    where S: Storage, T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue  {
     
-    Ok( _http_post::<T, UP>( &connection_pool, params, None ).await? )
+    let context = user_context( None, headers );
+    _http_post::<S, T, UP>( &storage, params, None, context ).await
 }
 
-pub async fn http_post_auth<T, UP>( 
+pub async fn http_post_auth<S, T, UP>( 
     user: AuthUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Json( params ): Json<UP> ) -> Result<Json<uuid::Uuid>, StatusCode> 
-    where T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Json( params ): Json<UP> ) -> Result<Json<RecordId>, CrudError> 
+    where S: Storage, T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
     
-    Ok( _http_post::<T, UP>( &connection_pool, params, Some( user.user_id ) ).await? )
+    let context = user_context( Some( &user ), headers );
+    scope_check::<T>( &user, EndpointVerb::POST )?;
+    _http_post::<S, T, UP>( &storage, params, Some( Caller::from( user ) ), context ).await
 }
 
-pub async fn http_post_admin<T, UP>( 
-    user: AdminUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Json( params ): Json<UP> ) -> Result<Json<uuid::Uuid>, StatusCode> 
-    where T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
+pub async fn http_post_optional<S, T, UP>( 
+    MaybeUser( user ): MaybeUser,
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Json( params ): Json<UP> ) -> Result<Json<RecordId>, CrudError> 
+    where S: Storage, T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
+    
+    let context = user_context( user.as_ref(), headers );
+    let caller = optional_caller::<T>( user, EndpointVerb::POST )?;
+    _http_post::<S, T, UP>( &storage, params, caller, context ).await
+}
 
-    Ok( _http_post::<T, UP>( &connection_pool, params, Some( user.user_id ) ).await? )
+pub async fn http_post_admin<S, T, UP>( 
+    user: AdminUser,
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Json( params ): Json<UP> ) -> Result<Json<RecordId>, CrudError> 
+    where S: Storage, T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
+
+    let context = admin_context( &user, headers );
+    let caller = admin_caller( &user, EndpointVerb::POST );
+    _http_post::<S, T, UP>( &storage, params, caller, context ).await
 }
 
-async fn _http_post<T, UP>( connection_pool: &PgPool, params: UP, user_id: Option<String> ) -> Result<Json<uuid::Uuid>, StatusCode> 
-    where T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
-    let full_object = params.add_set_values(user_id);
+pub(crate) async fn _http_post<S, T, UP>( storage: &S, params: UP, caller: Option<Caller>, context: RequestContext ) -> Result<Json<RecordId>, CrudError> 
+    where S: Storage, T: CrudConfig + Serialize + KeyValue + Send + Sync + Unpin, UP: InputSerializer<T> + Serialize + KeyValue {
+    let scope = row_scope::<T>( EndpointVerb::POST, caller.as_ref(), context.admin )?;
+    let storage = &session_storage( storage, caller.as_ref(), context.admin );
+    let context = request_transaction( storage, context );
+    audit_impersonation::<S, T>( storage, &context, EndpointVerb::POST, None ).await?;
+    let full_object = params.add_set_values( caller.map( |caller| caller.user_id ) );
+
+    // add_set_values only knows the user, so tenant rows are stamped with their tenant here
+    let stamp = match T::get_object_permissions( &EndpointVerb::POST ) {
+        ObjectPermission::TENANT | ObjectPermission::TENANTADMIN => scope,
+        _ => None
+    };
 
     if params.verify() {
-        match T::is_custom( &EndpointVerb::POST ) {
-            true => T::custom_create::<T>( &connection_pool, full_object ).await,
-            false => create::<T>( &connection_pool, full_object ).await
-        }
+        let id = match T::is_custom( &EndpointVerb::POST ) {
+            true => T::custom_create::<S, T>( storage, &context, full_object ).await.map_err( CrudError::from ),
+            false => create::<S, T>( storage, &context, full_object, stamp ).await
+        };
+        finish_request::<S, _, _>( &context, id ).await
     } else {
-        Err( StatusCode::BAD_REQUEST )
+        Err( StatusCode::BAD_REQUEST.into() )
     }
 }
 
 
 
-pub async fn http_put_any<T, UP>( 
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid>,
-    Json( params ): Json<UP> ) -> StatusCode
-    where T: CrudConfig + Serialize, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin {
+pub async fn http_put_any<S, T, UP>( 
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>>,
+    Json( params ): Json<UP> ) -> Result<StatusCode, CrudError>
+    where S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin + 'static {
 
-    _http_put::<T, UP>( &connection_pool, id, params, None ).await
+    let context = user_context( None, headers );
+    let id = T::primary_key().parse( &id )?;
+    _http_put::<S, T, UP>( &storage, id, params, None, context ).await
 }
 
-pub async fn http_put_auth<T, UP>( 
+pub async fn http_put_auth<S, T, UP>( 
     user: AuthUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid>,
-    Json( params ): Json<UP> ) -> StatusCode
-    where T: CrudConfig + Serialize, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin {
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>>,
+    Json( params ): Json<UP> ) -> Result<StatusCode, CrudError>
+    where S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin + 'static {
+
+    let context = user_context( Some( &user ), headers );
+    scope_check::<T>( &user, EndpointVerb::PUT )?;
+    let id = T::primary_key().parse( &id )?;
+    _http_put::<S, T, UP>( &storage, id, params, Some( Caller::from( user ) ), context ).await
+}
 
-    _http_put::<T, UP>( &connection_pool, id, params, Some( user.user_id ) ).await
+pub async fn http_put_optional<S, T, UP>( 
+    MaybeUser( user ): MaybeUser,
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>>,
+    Json( params ): Json<UP> ) -> Result<StatusCode, CrudError>
+    where S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin + 'static {
+
+    let context = user_context( user.as_ref(), headers );
+    let caller = optional_caller::<T>( user, EndpointVerb::PUT )?;
+    let id = T::primary_key().parse( &id )?;
+    _http_put::<S, T, UP>( &storage, id, params, caller, context ).await
 }
 
-pub async fn http_put_admin<T, UP>( 
+pub async fn http_put_admin<S, T, UP>( 
     user: AdminUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid>,
-    Json( params ): Json<UP> ) -> StatusCode
-    where T: CrudConfig + Serialize, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin {
-
-    _http_put::<T, UP>( &connection_pool, id, params, Some( user.user_id ) ).await
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>>,
+    Json( params ): Json<UP> ) -> Result<StatusCode, CrudError>
+    where S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin + 'static {
+
+    let context = admin_context( &user, headers );
+    let id = T::primary_key().parse( &id )?;
+    let caller = admin_caller( &user, EndpointVerb::PUT );
+    _http_put::<S, T, UP>( &storage, id, params, caller, context ).await
 }
 
-async fn _http_put<T, UP>( connection_pool: &PgPool, id: uuid::Uuid, params: UP, user_id: Option<String> ) -> StatusCode
-    where T: CrudConfig + Serialize, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin {
+pub(crate) async fn _http_put<S, T, UP>( storage: &S, id: RecordId, params: UP, caller: Option<Caller>, context: RequestContext ) -> Result<StatusCode, CrudError>
+    where S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + Serialize + KeyValue + Send + Sync + Unpin + 'static {
 
-    let user_id_matched = match T::get_object_permissions( &EndpointVerb::PUT ) {
-        ObjectPermission::ALL => None,
-        ObjectPermission::OWNER => user_id,
-    };
+    let scope = row_scope::<T>( EndpointVerb::PUT, caller.as_ref(), context.admin )?;
+    let storage = &session_storage( storage, caller.as_ref(), context.admin );
+    let context = request_transaction( storage, context );
+    audit_impersonation::<S, T>( storage, &context, EndpointVerb::PUT, Some( &id ) ).await?;
 
     if params.verify() {
-        match T::is_custom( &EndpointVerb::PUT ) {
-            true => T::custom_update::<UP>( &connection_pool, id, params, user_id_matched ).await,
-            false => update::<T, UP>( &connection_pool, id, params, user_id_matched ).await
-        }
+        let status = match T::is_custom( &EndpointVerb::PUT ) {
+            true => ok_status( T::custom_update::<S, UP>( storage, &context, id, params, scope.map( |scope| scope.user_id ) ).await ),
+            false => update::<S, T, UP>( storage, &context, id, params, scope, caller.as_ref() ).await
+        };
+        finish_request::<S, _, _>( &context, status ).await
     } else {
-        StatusCode::BAD_REQUEST
+        Err( StatusCode::BAD_REQUEST.into() )
     }
 }
 
 
 
 
-pub async fn http_delete_any<T>( 
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid> ) -> StatusCode where T: CrudConfig {
+pub async fn http_delete_any<S, T>( 
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>> ) -> Result<StatusCode, CrudError> where S: Storage, T: CrudConfig {
     
-    _http_delete::<T>( &connection_pool, id, None ).await
+    let context = user_context( None, headers );
+    let id = T::primary_key().parse( &id )?;
+    _http_delete::<S, T>( &storage, id, None, context ).await
 }
 
-pub async fn http_delete_auth<T>( 
+pub async fn http_delete_auth<S, T>( 
     user: AuthUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid> ) -> StatusCode where T: CrudConfig {
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>> ) -> Result<StatusCode, CrudError> where S: Storage, T: CrudConfig {
+    
+    let context = user_context( Some( &user ), headers );
+    scope_check::<T>( &user, EndpointVerb::DELETE )?;
+    let id = T::primary_key().parse( &id )?;
+    _http_delete::<S, T>( &storage, id, Some( Caller::from( user ) ), context ).await
+}
+
+pub async fn http_delete_optional<S, T>( 
+    MaybeUser( user ): MaybeUser,
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>> ) -> Result<StatusCode, CrudError> where S: Storage, T: CrudConfig {
     
-    _http_delete::<T>( &connection_pool, id, Some( user.user_id ) ).await
+    let context = user_context( user.as_ref(), headers );
+    let caller = optional_caller::<T>( user, EndpointVerb::DELETE )?;
+    let id = T::primary_key().parse( &id )?;
+    _http_delete::<S, T>( &storage, id, caller, context ).await
 }
 
-pub async fn http_delete_admin<T>( 
+pub async fn http_delete_admin<S, T>( 
     user: AdminUser,
-    Extension( connection_pool ): Extension<PgPool>, 
-    Path( id ): Path<uuid::Uuid>,) -> StatusCode where T: CrudConfig {
+    headers: HeaderMap,
+    Extension( storage ): Extension<S>, 
+    Path( id ): Path<Vec<String>>,) -> Result<StatusCode, CrudError> where S: Storage, T: CrudConfig {
     
-    _http_delete::<T>( &connection_pool, id, Some( user.user_id ) ).await
+    let context = admin_context( &user, headers );
+    let id = T::primary_key().parse( &id )?;
+    let caller = admin_caller( &user, EndpointVerb::DELETE );
+    _http_delete::<S, T>( &storage, id, caller, context ).await
 }
 
-async fn _http_delete<T>( connection_pool: &PgPool, id: uuid::Uuid, user_id: Option<String> ) -> StatusCode where T: CrudConfig {
-    let ids = vec![id];
+pub(crate) async fn _http_delete<S, T>( storage: &S, id: RecordId, caller: Option<Caller>, context: RequestContext ) -> Result<StatusCode, CrudError> where S: Storage, T: CrudConfig {
+    let scope = row_scope::<T>( EndpointVerb::DELETE, caller.as_ref(), context.admin )?;
+    let storage = &session_storage( storage, caller.as_ref(), context.admin );
+    let context = request_transaction( storage, context );
+    audit_impersonation::<S, T>( storage, &context, EndpointVerb::DELETE, Some( &id ) ).await?;
 
-    let user_id_matched = match T::get_object_permissions( &EndpointVerb::DELETE ) {
-        ObjectPermission::ALL => None,
-        ObjectPermission::OWNER => user_id,
+    let status = match T::is_custom( &EndpointVerb::DELETE ) {
+        true => ok_status( T::custom_delete::<S>( storage, &context, vec![ id ], scope.map( |scope| scope.user_id ) ).await ),
+        false => delete::<S, T>( storage, &context, id, scope ).await
     };
+    finish_request::<S, _, _>( &context, status ).await
+}
+
 
-    match T::is_custom( &EndpointVerb::DELETE ) {
-        true => T::custom_delete( &connection_pool, ids, user_id_matched ).await,
-        false => delete::<T>( &connection_pool, ids, user_id_matched ).await
+
+pub(crate) async fn http_action<S: Storage, T: CrudConfig + FromStorageRow<S> + KeyValue>( action: Action, Extension( storage ): Extension<S>, request: Request<Body> ) -> Result<Json<Value>, CrudError> {
+    let ( mut parts, body ) = request.into_parts();
+    let id = match action.target {
+        ActionTarget::ITEM => {
+            let Path( segments ) = Path::<Vec<String>>::from_request_parts( &mut parts, &() ).await.map_err( |_| StatusCode::BAD_REQUEST )?;
+            Some( T::primary_key().parse( &segments )? )
+        },
+        ActionTarget::COLLECTION => None
+    };
+    let ( caller, context ) = request_caller::<T>( &mut parts, action.access, action.verb() ).await?;
+
+    // actions take any json body, or none at all
+    let bytes = hyper::body::to_bytes( body ).await.map_err( |_| StatusCode::BAD_REQUEST )?;
+    let input = match bytes.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_slice( &bytes ).map_err( |_| StatusCode::BAD_REQUEST )?
+    };
+
+    let scope = object_scope::<T>( action.object, action.verb(), caller.as_ref(), context.admin )?;
+    let storage = &session_storage( &storage, caller.as_ref(), context.admin );
+    let context = request_transaction( storage, context );
+    audit_impersonation::<S, T>( storage, &context, action.verb(), id.as_ref() ).await?;
+    if let ( Some( id ), Some( machine ) ) = ( &id, T::state_machine() ) {
+        if let Some( transition ) = machine.get( action.name ) {
+            let result = run_transition::<S, T>( storage, &context, id, machine.column, transition, scope ).await;
+            return finish_request::<S, _, _>( &context, result ).await;
+        }
     }
+    if let Some( id ) = &id {
+        if !record_exists::<S, T>( storage, id, None, scope ).await? {
+            return Err( StatusCode::BAD_REQUEST.into() );
+        }
+    }
+
+    let result = T::run_action( storage, &context, action.name, id, input ).await;
+    finish_request::<S, _, _>( &context, result ).await
 }
 
 
 
-async fn read<T, QP>( connection_pool: &PgPool, filters: QP, user_id: Option<String> ) -> Result <Json<Vec<T>>, StatusCode> where T: for<'r> FromRow<'r, PgRow> + Send + Unpin + CrudConfig, QP: Serialize + KeyValue {
-    let mut query = format!("SELECT * FROM {} WHERE ", T::table_name() );
-    let mut count = 1;
-    let mut order_col = "id";
-    let mut order_dir = "DESC";
+// api keys may be restricted to some resources and verbs, jwt users never are
+pub(crate) fn scope_check<T: CrudConfig>( user: &AuthUser, verb: EndpointVerb ) -> Result<(), StatusCode> {
+    match user.allows( T::endpoint_name(), verb ) {
+        true => Ok(()),
+        false => Err( StatusCode::FORBIDDEN )
+    }
+}
 
-    if let Some(_) = user_id {
-        query = query + "user_id = $" + &count.to_string() + " AND ";
-        count += 1;
+fn optional_caller<T: CrudConfig>( user: Option<AuthUser>, verb: EndpointVerb ) -> Result<Option<Caller>, StatusCode> {
+    match user {
+        Some( user ) => {
+            scope_check::<T>( &user, verb )?;
+            Ok( Some( Caller::from( user ) ) )
+        },
+        None => Ok( None )
     }
+}
 
-    let mut bindings: Vec<FieldValue> = vec![]; 
-    for (key, value ) in filters.key_value_pairs() {
-        if key == "order_by" {
-            if let FieldValue::STRING(value) = value {
-                order_col = value;
-            }
-            continue;
+// admins see past owner scoping, unless they act as a user in which case they get exactly that user's view
+pub(crate) fn admin_caller( admin: &AdminUser, verb: EndpointVerb ) -> Option<Caller> {
+    match &admin.acting_as {
+        Some( user_id ) => Some( Caller { user_id: user_id.clone(), tenant: admin.acting_tenant.clone(), groups: vec![] } ),
+        None => match verb {
+            EndpointVerb::POST => Some( Caller::user( &admin.user_id ) ),
+            _ => None
         }
+    }
+}
 
-        if key == "order_dir" {
-            if let FieldValue::STRING(value) = value {
-                match value.as_str() {
-                    "asc" => { order_dir = "ASC"; },
-                    _ => {}
-                }
-            }
-            continue;
-        }
+// written through the request's transaction, so an impersonated request that fails leaves no audit row behind
+async fn audit_impersonation<S: Storage, T: CrudConfig>( storage: &S, context: &RequestContext, verb: EndpointVerb, object_id: Option<&RecordId> ) -> Result<(), StatusCode> {
+    let ( admin_id, user_id ) = match ( &context.impersonated_by, &context.user_id ) {
+        ( Some( admin_id ), Some( user_id ) ) => ( admin_id, user_id ),
+        _ => return Ok(())
+    };
+
+    let transaction = context.transaction( storage ).await?;
+    record_impersonation( &transaction, admin_id, user_id, T::endpoint_name(), verb, object_id.map( |id| id.to_string() ) ).await?;
+    transaction.commit().await
+}
+
+fn row_scope<T: CrudConfig>( verb: EndpointVerb, caller: Option<&Caller>, admin: bool ) -> Result<Option<Owner>, StatusCode> {
+    object_scope::<T>( T::get_object_permissions( &verb ), verb, caller, admin )
+}
+
+// without a caller only reads and admin requests go unscoped, otherwise rows are matched on the owner or tenant column or through a grant
+fn object_scope<T: CrudConfig>( permission: ObjectPermission, verb: EndpointVerb, caller: Option<&Caller>, admin: bool ) -> Result<Option<Owner>, StatusCode> {
+    let caller = match caller {
+        Some( caller ) => caller,
+        None if admin || permission == ObjectPermission::ALL => return Ok( None ),
+        None if matches!( verb, EndpointVerb::PUT | EndpointVerb::DELETE ) => return Err( StatusCode::UNAUTHORIZED ),
+        None => return Ok( None )
+    };
+    let level = GrantLevel::for_verb( verb );
+
+    match ( permission, &caller.tenant ) {
+        ( ObjectPermission::ALL, _ ) => Ok( None ),
+        ( ObjectPermission::OWNER, _ ) => Ok( Some( Owner { column: T::owner_column(), user_id: caller.user_id.clone(), shared: None } ) ),
+        ( ObjectPermission::TENANT, Some( tenant ) ) => Ok( Some( Owner { column: T::tenant_column(), user_id: tenant.id.clone(), shared: None } ) ),
+        ( ObjectPermission::TENANTADMIN, Some( tenant ) ) if tenant.admin => Ok( Some( Owner { column: T::tenant_column(), user_id: tenant.id.clone(), shared: None } ) ),
+        ( ObjectPermission::SHARED, _ ) => Ok( Some( shared_owner( T::endpoint_name(), T::owner_column(), T::primary_key().first_column(), &caller.user_id, &caller.groups, level ) ) ),
+        _ => Err( StatusCode::FORBIDDEN )
+    }
+}
 
-        if key.len() < 3 {
-            query = query + &key + " = $" + &count.to_string() + " AND ";
-        } else {
-            match &key[key.len() - 3..] {
-                "_gt" => { query = query + &key[0..key.len() - 3] + " > $" + &count.to_string() + " AND "; },
-                "_lt" => { query = query + &key[0..key.len() - 3] + " < $" + &count.to_string() + " AND "; },
-                "_ge" => { query = query + &key[0..key.len() - 3] + " >= $" + &count.to_string() + " AND "; },
-                "_le" => { query = query + &key[0..key.len() - 3] + " <= $" + &count.to_string() + " AND "; },
-                _ => { query = query + &key + " = $" + &count.to_string() + " AND "; }
-            }
+// resolves the caller the way the generated routes' extractors do, for routes whose access level is only known at runtime
+pub(crate) async fn request_caller<T: CrudConfig>( parts: &mut Parts, access: AccessPermission, verb: EndpointVerb ) -> Result<( Option<Caller>, RequestContext ), CrudError> {
+    let user = match access {
+        AccessPermission::ANY => None,
+        AccessPermission::OPTIONAL => MaybeUser::from_request_parts( parts, &() ).await?.0,
+        AccessPermission::AUTHENTICATED => Some( AuthUser::from_request_parts( parts, &() ).await? ),
+        AccessPermission::ADMIN => {
+            let admin = AdminUser::from_request_parts( parts, &() ).await?;
+            let caller = admin_caller( &admin, verb );
+            return Ok( ( caller, admin_context( &admin, parts.headers.clone() ) ) );
         }
+    };
+
+    let context = user_context( user.as_ref(), parts.headers.clone() );
+    match user {
+        Some( user ) => {
+            scope_check::<T>( &user, verb )?;
+            Ok( ( Some( Caller::from( user ) ), context ) )
+        },
+        None => Ok( ( None, context ) )
+    }
+}
 
-        bindings.push( value );
-        count += 1;
+fn field_visible<T: CrudConfig>( field: &str, admin: bool, owns: bool ) -> bool {
+    match T::field_permission( field ) {
+        FieldPermission::PUBLIC | FieldPermission::READONLY => true,
+        FieldPermission::OWNERONLY => admin || owns,
+        FieldPermission::ADMINONLY => admin,
+        FieldPermission::HIDDEN => false
     }
-    query = query.chars().take(query.len() - 5).collect();
-    query = query + " ORDER BY " + &order_col + " " + &order_dir;
+}
 
-    let mut q = sqlx::query_as::<_, T>( &query );
-    if let Some(user_id) = user_id {
-        q = q.bind(user_id);
+fn field_writable<T: CrudConfig>( field: &str, admin: bool, owns: bool ) -> bool {
+    match T::field_permission( field ) {
+        FieldPermission::READONLY | FieldPermission::HIDDEN => false,
+        _ => field_visible::<T>( field, admin, owns )
     }
+}
 
-    for binding in bindings {
-        match binding {
-            FieldValue::UUID( value ) => { q = q.bind( value ); },
-            FieldValue::STRING( value ) => { q = q.bind( value ); },
-            FieldValue::INTEGER( value ) => { q = q.bind( value ); },
-            FieldValue::DATE( value ) => { q = q.bind( value ); },
-            FieldValue::BOOLEAN( value ) => { q = q.bind( value ); },
-            FieldValue::FLOAT( value ) => { q = q.bind( value ); }
+pub(crate) const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// an id sent by the caller is kept so both sides can match up their logs, otherwise one is made up
+fn request_id( headers: &HeaderMap ) -> String {
+    headers.get( REQUEST_ID_HEADER )
+        .and_then( |value| value.to_str().ok() )
+        .filter( |value| !value.is_empty() )
+        .map( str::to_string )
+        .unwrap_or_else( || uuid::Uuid::new_v4().to_string() )
+}
+
+pub(crate) fn user_context( user: Option<&AuthUser>, headers: HeaderMap ) -> RequestContext {
+    let request_id = request_id( &headers );
+    match user {
+        Some( user ) => RequestContext {
+            user_id: Some( user.user_id.clone() ),
+            subscription: Some( user.subscription.clone() ),
+            scopes: user.scopes.clone(),
+            tenant: user.tenant.clone(),
+            groups: user.groups.clone(),
+            permissions: user.permissions.clone(),
+            headers,
+            request_id,
+            ..Default::default()
+        },
+        None => RequestContext { headers, request_id, ..Default::default() }
+    }
+}
+
+// an admin acting as a user works as that user, keeping their own permissions on the context but not
+// admin access, so admin-only transitions and field rules are refused until they stop impersonating
+pub(crate) fn admin_context( admin: &AdminUser, headers: HeaderMap ) -> RequestContext {
+    RequestContext {
+        user_id: Some( admin.acting_as.clone().unwrap_or_else( || admin.user_id.clone() ) ),
+        subscription: Some( admin.subscription.clone() ),
+        permissions: admin.permissions.clone(),
+        admin: admin.acting_as.is_none(),
+        impersonated_by: admin.acting_as.as_ref().map( |_| admin.user_id.clone() ),
+        tenant: admin.acting_tenant.clone(),
+        request_id: request_id( &headers ),
+        headers,
+        ..Default::default()
+    }
+}
+
+// ownership is read off the stored row, field rules then apply to the keys of the output it is turned into
+fn shape_row<T: CrudConfig + KeyValue, OP: Serialize + OutputSerializer<T>>( row: T, context: &RequestContext ) -> Result<Value, StatusCode> {
+    let owns = match &context.user_id {
+        Some( user_id ) => row.key_value_pairs().iter().any( |( column, value )| *column == T::owner_column() && matches!( value, FieldValue::STRING( owner ) if *owner == user_id ) ),
+        None => false
+    };
+
+    let mut value = serde_json::to_value( OP::from_row( row, context ) ).map_err( |_| StatusCode::INTERNAL_SERVER_ERROR )?;
+    if let Value::Object( fields ) = &mut value {
+        fields.retain( |field, _| field_visible::<T>( field, context.admin, owns ) );
+    }
+    Ok( value )
+}
+
+// only admins acting as themselves keep the storage's own access, anonymous requests get an empty session
+fn session_storage<S: Storage>( storage: &S, caller: Option<&Caller>, admin: bool ) -> S {
+    match ( admin, caller ) {
+        ( true, _ ) => storage.clone(),
+        ( false, Some( caller ) ) => storage.with_session( Session {
+            user_id: caller.user_id.clone(),
+            tenant_id: caller.tenant.as_ref().map( |tenant| tenant.id.clone() ),
+            tenant_admin: caller.tenant.as_ref().map( |tenant| tenant.admin ).unwrap_or( false ),
+            grantees: grantees( &caller.user_id, &caller.groups )
+        } ),
+        ( false, None ) => storage.with_session( Session::default() )
+    }
+}
+
+// operations share one transaction through the context, begun lazily and committed only once the request succeeds
+fn request_transaction<S: Storage>( storage: &S, context: RequestContext ) -> RequestContext {
+    RequestContext { transaction: Some( RequestTransaction::new( storage.clone() ) ), ..context }
+}
+
+// rolled back explicitly rather than on drop, a hook may hold on to a clone of the context
+async fn finish_request<S: Storage, R, E: From<StatusCode>>( context: &RequestContext, result: Result<R, E> ) -> Result<R, E> {
+    let lazy = match context.transaction.as_ref().and_then( |transaction| transaction.lazy::<S>() ) {
+        Some( lazy ) => lazy,
+        None => return result
+    };
+
+    match result {
+        Ok( value ) => {
+            lazy.commit().await?;
+            Ok( value )
+        },
+        Err( error ) => {
+            lazy.rollback().await?;
+            Err( error )
         }
     }
+}
+
+fn affected_status( result: Result<u64, StatusCode> ) -> StatusCode {
+    match result {
+        Ok( 0 ) => StatusCode::BAD_REQUEST,
+        Ok(_) => StatusCode::OK,
+        Err( status ) => status
+    }
+}
+
+fn ok_status( status: StatusCode ) -> Result<StatusCode, CrudError> {
+    match status {
+        StatusCode::OK => Ok( status ),
+        status => Err( status.into() )
+    }
+}
+
+async fn read<S, T, QP>( storage: &S, filters: QP, owner: Option<Owner> ) -> Result <Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin + CrudConfig, QP: Serialize + KeyValue {
+    let query = ReadQuery::parse_ordered( T::table_name(), &filters, owner, T::primary_key().first_column() )?;
 
-    let rows = q.fetch_all(connection_pool).await
-                        .map_err(|error| {
-                            println!("{:?}", error);
-                            StatusCode::INTERNAL_SERVER_ERROR 
-                        })?;
+    let rows = storage.select( &query ).await?
+        .into_iter()
+        .map( T::from_storage_row )
+        .collect::<Result<Vec<T>, StatusCode>>()?;
 
     Ok( Json( rows ) )
 }
 
-async fn create<T: CrudConfig + KeyValue>( connection_pool: &PgPool, values: T ) -> Result <Json<uuid::Uuid>, StatusCode> {
-    let mut query_part1 = format!( "INSERT INTO {} (" , T::table_name() );
-    let mut query_part2 = " ) VALUES ( ".to_string();
-    let mut count = 0;
-    for (key, _) in values.key_value_pairs() {
-        query_part1 = query_part1 + key + ", ";
-        count += 1;
-        query_part2 = query_part2 + "$" + &count.to_string() + ", ";
-    }
-    query_part1 = query_part1.chars().take(query_part1.len() - 2).collect();
-    query_part2 = query_part2.chars().take(query_part2.len() - 2).collect();
-    let query = format!("{}{} ) RETURNING id", query_part1, query_part2);
-
-    let mut q = sqlx::query( &query );
-    for (_, value) in values.key_value_pairs() {
-        match value {
-            FieldValue::UUID( value ) => { q = q.bind( value ); },
-            FieldValue::STRING( value ) => { q = q.bind( value ); },
-            FieldValue::INTEGER( value ) => { q = q.bind( value ); },
-            FieldValue::DATE( value ) => { q = q.bind( value ); },
-            FieldValue::BOOLEAN( value ) => { q = q.bind( value ); },
-            FieldValue::FLOAT( value ) => { q = q.bind( value ); }
-        }
+async fn create<S: Storage, T: CrudConfig + KeyValue + Send + Sync>( storage: &S, context: &RequestContext, mut values: T, stamp: Option<Owner> ) -> Result <Json<RecordId>, CrudError> {
+    let transaction = context.transaction( storage ).await?;
+    T::before_create( &transaction, context, &mut values ).await?;
+
+    let mut pairs = values.key_value_pairs();
+    if let Some( stamp ) = &stamp {
+        pairs.retain( |( column, _ )| *column != stamp.column );
+        pairs.push( ( stamp.column, FieldValue::STRING( &stamp.user_id ) ) );
     }
 
-    let id = q.fetch_one(connection_pool).await
-                    .map_err(|error| {
-                        println!("{:?}", error);
-                        StatusCode::INTERNAL_SERVER_ERROR 
-                    })?
-                    .try_get::<uuid::Uuid, _>(0)
-                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR )?;
-    
+    let id = transaction.insert( T::table_name(), T::primary_key(), &pairs ).await?;
+    T::after_create( &transaction, context, &id, &values ).await?;
+    transaction.commit().await?;
     Ok( Json(id) )
 }
 
-async fn update<T: CrudConfig, UP: KeyValue>( connection_pool: &PgPool, id: uuid::Uuid, values: UP, user_id: Option<String> ) -> StatusCode {
-    let mut query = format!( "UPDATE {} SET " , T::table_name() );
-    let mut count = 0;
-    for (key, _) in values.key_value_pairs() {
-        count += 1;
-        query = query + key + " = $" + &count.to_string() + ", ";
+async fn update<S: Storage, T: CrudConfig + Serialize + FromStorageRow<S> + KeyValue, UP: InputSerializer<T> + KeyValue + Send + Sync + 'static>( storage: &S, context: &RequestContext, id: RecordId, mut values: UP, owner: Option<Owner>, caller: Option<&Caller> ) -> Result<StatusCode, CrudError> {
+    let transaction = context.transaction( storage ).await?;
+    T::before_update( &transaction, context, &id, &mut values ).await?;
+
+    let admin = context.admin;
+    let mut pairs = values.key_value_pairs();
+    pairs.retain( |( column, _ )| field_writable::<T>( column, admin, true ) );
+
+    // owner only columns are kept when the caller owns this row, unless the update is already narrowed to their rows
+    let owner_scoped = owner.as_ref().map( |owner| owner.column == T::owner_column() && owner.shared.is_none() ).unwrap_or( false );
+    if !admin && !owner_scoped && pairs.iter().any( |( column, _ )| T::field_permission( column ) == FieldPermission::OWNERONLY ) {
+        let owns = match caller {
+            Some( caller ) => record_exists::<S::Transaction, T>( &transaction, &id, Some( &caller.user_id ), owner.clone() ).await?,
+            None => false
+        };
+        pairs.retain( |( column, _ )| field_writable::<T>( column, admin, owns ) );
     }
-    query = query.chars().take(query.len() - 2).collect();
-    query = query + &format!(" WHERE id = ${}", &( count + 1 ).to_string());
 
-    if let Some(_) = user_id {
-        query = query + " AND user_id = $" + &( count + 2 ).to_string();
+    let immutable = T::immutable_columns();
+    if pairs.iter().any( |( column, _ )| immutable.contains( column ) ) {
+        let stored = fetch_record::<S, T>( &transaction, &id, owner.clone() ).await?.ok_or( StatusCode::BAD_REQUEST )?;
+        let stored = stored.key_value_pairs();
+        if let Some( ( column, _ ) ) = pairs.iter().find( |( column, value )| immutable.contains( column ) && !stored.iter().any( |pair| pair == &( *column, *value ) ) ) {
+            return Err( CrudError::forbidden( &format!( "{} cannot be changed", column ) ) );
+        }
+        pairs.retain( |( column, _ )| !immutable.contains( column ) );
     }
 
-    let mut q = sqlx::query( &query );
-    for (_, value) in values.key_value_pairs() {
-        match value {
-            FieldValue::UUID( value ) => { q = q.bind( value ); },
-            FieldValue::STRING( value ) => { q = q.bind( value ); },
-            FieldValue::INTEGER( value ) => { q = q.bind( value ); },
-            FieldValue::DATE( value ) => { q = q.bind( value ); },
-            FieldValue::BOOLEAN( value ) => { q = q.bind( value ); },
-            FieldValue::FLOAT( value ) => { q = q.bind( value ); }
+    // an update that only repeats what it may not change leaves the row as it is, its hooks still run in pairs
+    if pairs.is_empty() {
+        if !record_exists::<S::Transaction, T>( &transaction, &id, None, owner ).await? {
+            return Err( StatusCode::BAD_REQUEST.into() );
         }
+        T::after_update( &transaction, context, &id, &values ).await?;
+        transaction.commit().await?;
+        return Ok( StatusCode::OK );
     }
 
-    q = q.bind(id);
-    if let Some(user_id) = user_id {
-        q = q.bind(user_id);
+    let stamps = values.update_set_values( context.user_id.clone() );
+    pairs.retain( |( column, _ )| !stamps.iter().any( |( stamped, _ )| stamped == column ) );
+    pairs.extend( stamps.iter().map( |( column, value )| ( *column, value.field_value() ) ) );
+
+    let machine = T::state_machine();
+    let moved = match &machine {
+        Some( machine ) => state_change::<S, T>( &transaction, context, &id, machine, &pairs, owner.clone() ).await?,
+        None => None
+    };
+    match ( &machine, &moved ) {
+        ( Some( machine ), Some( ( _, from ) ) ) => {
+            let guard = state_guard( machine.column, from );
+            let affected = transaction.update_where( T::table_name(), T::primary_key(), &id, &pairs, &guard, owner.as_ref() ).await?;
+            still_in_state( affected, machine.column, from )?;
+        },
+        _ => {
+            ok_status( affected_status( transaction.update( T::table_name(), T::primary_key(), &id, &pairs, owner.as_ref() ).await ) )?;
+        }
     }
-    q.execute(connection_pool)
-        .await
-        .map_err(|error| {
-            println!("{:?}", error);
-            StatusCode::INTERNAL_SERVER_ERROR 
-        })
-        .map(|resp| {
-            if resp.rows_affected() == 0 {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::OK
-            }
-        })
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR ).unwrap()
+    if let Some( ( transition, from ) ) = moved {
+        record_transition( &transaction, T::endpoint_name(), id.to_string(), &transition, &from, context.user_id.clone() ).await?;
+    }
+
+    T::after_update( &transaction, context, &id, &values ).await?;
+    transaction.commit().await?;
+    Ok( StatusCode::OK )
 }
 
-async fn delete<T: CrudConfig>( connection_pool: &PgPool, ids: Vec<uuid::Uuid>, user_id: Option<String> ) -> StatusCode {
-    let mut query = format!( "DELETE FROM {} WHERE id IN (" , T::table_name() );
-    let mut count = 0;
-    for _ in &ids {
-        count += 1;
-        query = query + "$" + &count.to_string() + ", ";
+async fn record_exists<S: Storage, T: CrudConfig>( storage: &S, id: &RecordId, owned_by: Option<&str>, scope: Option<Owner> ) -> Result<bool, StatusCode> {
+    let owned_by = owned_by.map( str::to_string );
+    let query = record_query::<T>( id, owned_by.as_ref(), scope )?;
+    Ok( !storage.select( &query ).await?.is_empty() )
+}
+
+fn record_query<'a, T: CrudConfig>( id: &'a RecordId, owned_by: Option<&'a String>, scope: Option<Owner> ) -> Result<ReadQuery<'a>, StatusCode> {
+    let mut filters = id.key_values( T::primary_key() ).ok_or( StatusCode::BAD_REQUEST )?
+        .into_iter()
+        .map( |( column, value )| Filter { column, op: FilterOp::EQ, value } )
+        .collect::<Vec<_>>();
+    if let Some( user_id ) = owned_by {
+        filters.push( Filter { column: T::owner_column(), op: FilterOp::EQ, value: FieldValue::STRING( user_id ) } );
     }
-    query = query.chars().take(query.len() - 2).collect();
-    query = query + ")";
 
-    if let Some(_) = user_id {
-        query = query + " AND user_id = $" + &( count + 1 ).to_string();
+    Ok( ReadQuery {
+        table: T::table_name(),
+        filters,
+        owner: scope,
+        order_by: T::primary_key().first_column(),
+        descending: true,
+        limit: Some( 1 ),
+        offset: None
+    } )
+}
+
+async fn fetch_record<S, T>( storage: &S::Transaction, id: &RecordId, scope: Option<Owner> ) -> Result<Option<T>, StatusCode> where S: Storage, T: CrudConfig + FromStorageRow<S> {
+    let query = record_query::<T>( id, None, scope )?;
+    storage.select( &query ).await?.into_iter().next().map( T::from_storage_row ).transpose()
+}
+
+async fn current_state<S, T>( storage: &S::Transaction, id: &RecordId, column: &str, scope: Option<Owner> ) -> Result<Option<String>, StatusCode> where S: Storage, T: CrudConfig + FromStorageRow<S> + KeyValue {
+    let record = match fetch_record::<S, T>( storage, id, scope ).await? {
+        Some( record ) => record,
+        None => return Ok( None )
+    };
+
+    let state = record.key_value_pairs().into_iter().find_map( |( name, value )| match value {
+        FieldValue::STRING( state ) if name == column => Some( state.clone() ),
+        _ => None
+    } );
+    Ok( state )
+}
+
+async fn check_transition<S: Storage, T: CrudConfig>( storage: &S, context: &RequestContext, id: &RecordId, transition: &Transition, from: &str ) -> Result<(), CrudError> {
+    if !transition.permits( context ) {
+        return Err( CrudError::forbidden( &format!( "not allowed to {}", transition.name ) ) );
     }
+    T::guard_transition( storage, context, id, transition, from ).await
+}
 
-    let mut q = sqlx::query( &query );
-    for id in ids {
-        q = q.bind(id);
+async fn run_transition<S, T>( storage: &S, context: &RequestContext, id: &RecordId, column: &'static str, transition: Transition, scope: Option<Owner> ) -> Result<Json<Value>, CrudError>
+    where S: Storage, T: CrudConfig + FromStorageRow<S> + KeyValue {
+
+    let transaction = context.transaction( storage ).await?;
+    let from = current_state::<S, T>( &transaction, id, column, scope.clone() ).await?.ok_or( StatusCode::BAD_REQUEST )?;
+    if !transition.leaves( &from ) {
+        return Err( CrudError::conflict( &format!( "cannot {} while {} is '{}'", transition.name, column, from ) ) );
     }
-    if let Some(user_id) = user_id {
-        q = q.bind(user_id);
+    check_transition::<S::Transaction, T>( &transaction, context, id, &transition, &from ).await?;
+
+    let to = transition.to.to_string();
+    let affected = transaction.update_where( T::table_name(), T::primary_key(), id, &[ ( column, FieldValue::STRING( &to ) ) ], &state_guard( column, &from ), scope.as_ref() ).await?;
+    still_in_state( affected, column, &from )?;
+    record_transition( &transaction, T::endpoint_name(), id.to_string(), &transition, &from, context.user_id.clone() ).await?;
+    transaction.commit().await?;
+    Ok( Json( json!({ "transition": transition.name, "from": from, "to": to }) ) )
+}
+
+// state writes only land on a row still in the state the transition was checked against
+fn state_guard<'a>( column: &'static str, from: &'a String ) -> [Filter<'a>; 1] {
+    [ Filter { column, op: FilterOp::EQ, value: FieldValue::STRING( from ) } ]
+}
+
+fn still_in_state( affected: u64, column: &str, from: &str ) -> Result<(), CrudError> {
+    match affected {
+        0 => Err( CrudError::conflict( &format!( "{} is no longer '{}'", column, from ) ) ),
+        _ => Ok(())
     }
+}
 
-    q.execute(connection_pool)
-        .await
-        .map_err(|error| {
-            println!("{:?}", error);
-            StatusCode::INTERNAL_SERVER_ERROR 
-        })
-        .map(|resp| {
-            if resp.rows_affected() == 0 {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::OK
-            }
-        })
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR ).unwrap()
-}
\ No newline at end of file
+// an update may only change the state column along a declared transition the caller is allowed to take
+async fn state_change<S, T>( storage: &S::Transaction, context: &RequestContext, id: &RecordId, machine: &StateMachine, pairs: &[(&'static str, FieldValue<'_>)], scope: Option<Owner> ) -> Result<Option<( Transition, String )>, CrudError>
+    where S: Storage, T: CrudConfig + FromStorageRow<S> + KeyValue {
+
+    let to = match pairs.iter().find( |( column, _ )| *column == machine.column ) {
+        Some( ( _, FieldValue::STRING( to ) ) ) => to.to_string(),
+        Some(_) => return Err( StatusCode::BAD_REQUEST.into() ),
+        None => return Ok( None )
+    };
+
+    let from = current_state::<S, T>( storage, id, machine.column, scope ).await?.ok_or( StatusCode::BAD_REQUEST )?;
+    if from == to {
+        return Ok( None );
+    }
+
+    let transition = machine.between( &from, &to ).ok_or_else( || CrudError::conflict( &format!( "{} cannot change from '{}' to '{}'", machine.column, from, to ) ) )?;
+    check_transition::<S::Transaction, T>( storage, context, id, &transition, &from ).await?;
+    Ok( Some( ( transition, from ) ) )
+}
+
+async fn delete<S: Storage, T: CrudConfig>( storage: &S, context: &RequestContext, id: RecordId, owner: Option<Owner> ) -> Result<StatusCode, CrudError> {
+    let transaction = context.transaction( storage ).await?;
+    T::before_delete( &transaction, context, &id ).await?;
+    ok_status( affected_status( transaction.delete( T::table_name(), T::primary_key(), std::slice::from_ref( &id ), owner.as_ref() ).await ) )?;
+
+    T::after_delete( &transaction, context, &id ).await?;
+    transaction.commit().await?;
+    Ok( StatusCode::OK )
+}
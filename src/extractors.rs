@@ -7,6 +7,54 @@ use axum::http::request::Parts;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation, TokenData};
 use reqwest;
 use serde::Deserialize;
+use serde_json::{ Map, Value };
+use crate::EndpointVerb;
+use crate::api_keys::{ ApiKeys, API_KEY_HEADER, scope_allows };
+use crate::revocations::Revocations;
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub audience: String,
+    pub clerk_issuer: String,
+    pub jwt_issuer: String,
+    pub jwt_secret: String,
+    pub auth0_audience: String,
+    pub auth0_issuer: String,
+    pub admin_permission: String,
+    pub impersonation_permission: String,
+    pub tenant_claim: String,
+    pub tenant_role_claim: String,
+    pub tenant_admin_role: String,
+    pub groups_claim: String
+}
+
+impl AuthConfig {
+    pub fn from_env() -> AuthConfig {
+        dotenv::dotenv().ok();
+
+        AuthConfig {
+            audience: std::env::var("JWT_AUDIENCE").unwrap_or_default(),
+            clerk_issuer: std::env::var("CLERK_ISSUER").unwrap_or_default(),
+            jwt_issuer: std::env::var("JWT_ISSUER").unwrap_or_default(),
+            jwt_secret: std::env::var("JWT_SECRET").unwrap_or_default(),
+            auth0_audience: std::env::var("AUTH0_AUDIENCE").unwrap_or_default(),
+            auth0_issuer: std::env::var("AUTH0_ISSUER").unwrap_or_default(),
+            admin_permission: "read:admin".to_string(),
+            impersonation_permission: "impersonate:users".to_string(),
+            tenant_claim: std::env::var("TENANT_CLAIM").unwrap_or( "org_id".to_string() ),
+            tenant_role_claim: std::env::var("TENANT_ROLE_CLAIM").unwrap_or( "org_role".to_string() ),
+            tenant_admin_role: std::env::var("TENANT_ADMIN_ROLE").unwrap_or( "org:admin".to_string() ),
+            groups_claim: std::env::var("GROUPS_CLAIM").unwrap_or( "groups".to_string() )
+        }
+    }
+}
+
+fn auth_config( parts: &Parts ) -> AuthConfig {
+    match parts.extensions.get::<AuthConfig>() {
+        Some( config ) => config.clone(),
+        None => AuthConfig::from_env()
+    }
+}
 
 #[derive(Debug, Deserialize)]
 struct Jwk {
@@ -65,8 +113,43 @@ async fn decode_clerk_token(token: &str, audience: &str, issuer: &str) -> Result
     Ok( token_data )
 }
 
+pub(crate) const ACCESS_TOKEN_USE: &str = "access";
+
+// HS256 access tokens only exist once the accounts module is mounted
+#[cfg(feature = "accounts")]
+fn first_party_enabled( parts: &Parts ) -> bool {
+    parts.extensions.get::<crate::accounts::Accounts>().is_some()
+}
+
+#[cfg(not(feature = "accounts"))]
+fn first_party_enabled( _parts: &Parts ) -> bool {
+    false
+}
+
+// first-party tokens from the accounts module share the subscription token's issuer and secret,
+// so only those marked as access tokens are accepted in the Authorization header
+async fn decode_first_party_token( token: &str, audience: &str, issuer: &str, secret: &str ) -> Result<TokenData<ClerkClaims>, StatusCode > {
+    if secret.is_empty() || issuer.is_empty() || audience.is_empty() {
+        return Err( StatusCode::UNAUTHORIZED );
+    }
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience( &[ audience ] );
+    validation.set_issuer( &[ issuer ] );
+    let token_data = decode::<ClerkClaims>(token, &DecodingKey::from_secret( secret.as_bytes() ), &validation).map_err( | err | { println!("{:?}", err ); StatusCode::UNAUTHORIZED } )?;
+
+    match token_data.claims.token_use.as_deref() {
+        Some( ACCESS_TOKEN_USE ) => Ok( token_data ),
+        _ => Err( StatusCode::UNAUTHORIZED )
+    }
+}
 
+// an unset secret would otherwise verify tokens signed with the empty key
 async fn decode_custom_token( token: &str, audience: &str, issuer: &str, secret: &str ) -> Result<TokenData<CustomClaims>, StatusCode > {
+    if secret.is_empty() || issuer.is_empty() || audience.is_empty() {
+        return Err( StatusCode::UNAUTHORIZED );
+    }
+
     let mut validation = Validation::new(Algorithm::HS256);
     validation.set_audience( &vec![ audience ] );
     validation.set_issuer(&vec![ issuer ]);
@@ -81,28 +164,94 @@ struct Auth0Claims {
     sub: String,
     subscription: String,
     permissions: Vec<String>,
+    jti: Option<String>,
+    iat: Option<u64>
 }
 
 #[derive(Debug, Deserialize)]
 struct ClerkClaims {
-    sub: String
+    sub: String,
+    jti: Option<String>,
+    iat: Option<u64>,
+    token_use: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>
 }
 
 #[derive(Debug, Deserialize)]
 struct CustomClaims {
     subscription: String,
-    clerk_id: String
+    clerk_id: String,
+    jti: Option<String>,
+    iat: Option<u64>
+}
+
+async fn check_revoked( parts: &Parts, jti: Option<&str>, subject: &str, issued_at: Option<u64> ) -> Result<(), (StatusCode, &'static str)> {
+    let revocations = match parts.extensions.get::<Revocations>() {
+        Some( revocations ) => revocations,
+        None => return Ok(())
+    };
+
+    match revocations.is_revoked( jti, subject, issued_at ).await {
+        Ok( false ) => Ok(()),
+        Ok( true ) => Err( (StatusCode::UNAUTHORIZED, "Token revoked") ),
+        Err( status ) => Err( (status, "Error checking revocations") )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub id: String,
+    pub role: Option<String>,
+    pub admin: bool
+}
+
+// claim names default to clerk's active organization claims, `org_id` and `org_role`
+fn claims_tenant( config: &AuthConfig, claims: &Map<String, Value> ) -> Option<Tenant> {
+    let id = claims.get( &config.tenant_claim )?.as_str().filter( |id| !id.is_empty() )?;
+    let role = claims.get( &config.tenant_role_claim ).and_then( Value::as_str ).map( |role| role.to_string() );
+    let admin = role.as_deref() == Some( config.tenant_admin_role.as_str() );
+
+    Some( Tenant { id: id.to_string(), role, admin } )
+}
+
+fn claims_list( claims: &Map<String, Value>, claim: &str ) -> Vec<String> {
+    match claims.get( claim ) {
+        Some( Value::Array( groups ) ) => groups.iter().filter_map( Value::as_str ).map( |group| group.to_string() ).collect(),
+        _ => vec![]
+    }
 }
 
 pub struct AuthUser {
     pub user_id: String,
-    pub subscription: String
+    pub subscription: String,
+    pub scopes: Option<Vec<String>>,
+    pub tenant: Option<Tenant>,
+    pub groups: Vec<String>,
+    pub permissions: Vec<String>
+}
+
+impl AuthUser {
+    pub fn allows( &self, resource: &str, verb: EndpointVerb ) -> bool {
+        match &self.scopes {
+            Some( scopes ) => scope_allows( scopes, resource, verb ),
+            None => true
+        }
+    }
 }
 
+pub struct MaybeUser( pub Option<AuthUser> );
+
+pub const ACT_AS_HEADER: &str = "X-Act-As-User";
+pub const ACT_AS_TENANT_HEADER: &str = "X-Act-As-Tenant";
+pub const ACT_AS_TENANT_ROLE_HEADER: &str = "X-Act-As-Tenant-Role";
+
 pub struct AdminUser {
     pub user_id: String,
     pub subscription: String,
-    pub permissions: Vec<String>
+    pub permissions: Vec<String>,
+    pub acting_as: Option<String>,
+    pub acting_tenant: Option<Tenant>
 }
 
 async fn pull_header_token( parts: &mut Parts, key: &str ) -> Result<String, (StatusCode, &'static str) > {
@@ -125,6 +274,20 @@ impl<S> FromRequestParts<S> for AuthUser where S: Send + Sync {
     type Rejection = (StatusCode, &'static str);
 
     async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        if let Some( key ) = parts.headers.get( API_KEY_HEADER ) {
+            let key = key.to_str().map_err( |_| (StatusCode::UNAUTHORIZED, "Invalid api key") )?;
+            let api_keys = parts.extensions.get::<ApiKeys>()
+                .ok_or( (StatusCode::UNAUTHORIZED, "Api keys not enabled") )?;
+            let api_key = api_keys.authenticate( key ).await
+                .map_err( |status| (status, "Invalid api key") )?;
+            // revoking a user also cuts off the keys they held before it, keys have no jti of their own
+            check_revoked( parts, None, &api_key.user_id, Some( api_key.date_created.timestamp() as u64 ) ).await?;
+
+            return Ok(
+                AuthUser { user_id: api_key.user_id.clone(), subscription: "none".to_string(), scopes: Some( api_key.scope_list() ), tenant: None, groups: vec![], permissions: vec![] }
+            )
+        }
+
         let access_token = pull_header_token( parts, "Authorization" ).await?;
 
         let mut subscription_token = "none".to_string();
@@ -132,32 +295,38 @@ impl<S> FromRequestParts<S> for AuthUser where S: Send + Sync {
             subscription_token = pull_header_token( parts, "Subscription" ).await?;
         }
 
-        dotenv::dotenv().ok();
-
         //println!("{:?}", token);
 
-        let audience = std::env::var("JWT_AUDIENCE").unwrap();
-        let clerk_issuer = std::env::var("CLERK_ISSUER").unwrap();
-        let jwt_issuer = std::env::var("JWT_ISSUER").unwrap();
-        let secret = std::env::var("JWT_SECRET").unwrap();
-        let decoded_access_token = decode_clerk_token( &access_token, &audience, &clerk_issuer ).await
-            .map_err( |status| (status, "Error decoding auth token") )?;
+        let config = auth_config( parts );
+        let header = jsonwebtoken::decode_header( &access_token ).map_err( |_| (StatusCode::UNAUTHORIZED, "Error decoding auth token") )?;
+        let decoded_access_token = match header.alg {
+            Algorithm::HS256 if !first_party_enabled( parts ) => Err( StatusCode::UNAUTHORIZED ),
+            Algorithm::HS256 => decode_first_party_token( &access_token, &config.audience, &config.jwt_issuer, &config.jwt_secret ).await,
+            _ => decode_clerk_token( &access_token, &config.audience, &config.clerk_issuer ).await
+        }.map_err( |status| (status, "Error decoding auth token") )?;
+        let access_claims = &decoded_access_token.claims;
+        check_revoked( parts, access_claims.jti.as_deref(), &access_claims.sub, access_claims.iat ).await?;
+        let tenant = claims_tenant( &config, &access_claims.extra );
+        let groups = claims_list( &access_claims.extra, &config.groups_claim );
+        let permissions = claims_list( &access_claims.extra, "permissions" );
 
         if subscription_token == "none" {
             return Ok(
-                AuthUser { user_id: decoded_access_token.claims.sub, subscription: subscription_token }
+                AuthUser { user_id: decoded_access_token.claims.sub, subscription: subscription_token, scopes: None, tenant, groups, permissions }
             )
         }
 
-        let decoded_sub_token = decode_custom_token( &subscription_token, &audience, &jwt_issuer, &secret ).await
+        let decoded_sub_token = decode_custom_token( &subscription_token, &config.audience, &config.jwt_issuer, &config.jwt_secret ).await
             .map_err( |status| (status, "Error decoding sub token") )?;
+        let sub_claims = &decoded_sub_token.claims;
+        check_revoked( parts, sub_claims.jti.as_deref(), &sub_claims.clerk_id, sub_claims.iat ).await?;
 
         if decoded_access_token.claims.sub != decoded_sub_token.claims.clerk_id {
             return Err( (StatusCode::UNAUTHORIZED, "invalid permissions") );
         }
 
         return Ok(
-            AuthUser { user_id: decoded_access_token.claims.sub, subscription: decoded_sub_token.claims.subscription }
+            AuthUser { user_id: decoded_access_token.claims.sub, subscription: decoded_sub_token.claims.subscription, scopes: None, tenant, groups, permissions }
         )
     }
 }
@@ -178,19 +347,59 @@ impl<S> FromRequestParts<S> for AdminUser where S: Send + Sync {
             token = token.replace( prefix, "");
         }
 
-        dotenv::dotenv().ok();
-
-        let audience = std::env::var("AUTH0_AUDIENCE").unwrap();
-        let issuer = std::env::var("AUTH0_ISSUER").unwrap();
-        let decoded_token = decode_auth0_token( &token, &audience, &issuer ).await
+        let config = auth_config( parts );
+        let decoded_token = decode_auth0_token( &token, &config.auth0_audience, &config.auth0_issuer ).await
             .map_err( |status| (status, "Error decoding token") )?;
+        check_revoked( parts, decoded_token.claims.jti.as_deref(), &decoded_token.claims.sub, decoded_token.claims.iat ).await?;
 
-        if !decoded_token.claims.permissions.contains( &"read:admin".to_string() ) {
+        if !decoded_token.claims.permissions.contains( &config.admin_permission ) {
             return Err( (StatusCode::UNAUTHORIZED, "invalid permissions") );
         }
 
+        // impersonation is refused outright rather than ignored, so an admin never silently acts as themselves
+        let acting_as = match parts.headers.get( ACT_AS_HEADER ) {
+            Some( header ) => {
+                if !decoded_token.claims.permissions.contains( &config.impersonation_permission ) {
+                    return Err( (StatusCode::FORBIDDEN, "invalid permissions") );
+                }
+                match header.to_str() {
+                    Ok( user_id ) if !user_id.is_empty() => Some( user_id.to_string() ),
+                    _ => return Err( (StatusCode::BAD_REQUEST, "Invalid impersonation header") )
+                }
+            },
+            None => None
+        };
+
+        // a tenant only narrows an impersonation, the role decides whether the user is acted as its admin
+        let acting_tenant = match ( &acting_as, parts.headers.get( ACT_AS_TENANT_HEADER ) ) {
+            ( _, None ) => None,
+            ( Some(_), Some( header ) ) => match header.to_str() {
+                Ok( id ) if !id.is_empty() => {
+                    let role = parts.headers.get( ACT_AS_TENANT_ROLE_HEADER ).and_then( |role| role.to_str().ok() ).map( |role| role.to_string() );
+                    let admin = role.as_deref() == Some( config.tenant_admin_role.as_str() );
+                    Some( Tenant { id: id.to_string(), role, admin } )
+                },
+                _ => return Err( (StatusCode::BAD_REQUEST, "Invalid impersonation header") )
+            },
+            ( None, Some(_) ) => return Err( (StatusCode::BAD_REQUEST, "Invalid impersonation header") )
+        };
+
         return Ok(
-            AdminUser { user_id: decoded_token.claims.sub, permissions: decoded_token.claims.permissions, subscription: decoded_token.claims.subscription  }
+            AdminUser { user_id: decoded_token.claims.sub, permissions: decoded_token.claims.permissions, subscription: decoded_token.claims.subscription, acting_as, acting_tenant }
         )
     }
 }
+
+#[async_trait]
+impl<S> FromRequestParts<S> for MaybeUser where S: Send + Sync {
+    type Rejection = (StatusCode, &'static str);
+
+    // anonymous callers pass through, but a token that was sent still has to be valid
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if !parts.headers.contains_key( "Authorization" ) && !parts.headers.contains_key( API_KEY_HEADER ) {
+            return Ok( MaybeUser( None ) );
+        }
+
+        AuthUser::from_request_parts( parts, state ).await.map( |user| MaybeUser( Some( user ) ) )
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use async_graphql::dynamic::{ Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, Scalar, Schema, SchemaError, TypeRef };
+use async_graphql::http::{ playground_source, GraphQLPlaygroundConfig };
+use async_graphql::{ Error, ErrorExtensions };
+use axum::{ Extension, Json, Router };
+use axum::body::Body;
+use axum::http::{ Request, StatusCode, request::Parts };
+use axum::response::Html;
+use serde::{ Serialize, Deserialize };
+use serde_json::{ Map, Value };
+use tokio::sync::Mutex;
+use crate::{ AccessPermission, CrudConfig, CrudError, RequestContext, EndpointVerb, FieldType, InputSerializer, OutputSerializer, KeyValue, RecordId };
+use crate::endpoints::{ self, Caller };
+use crate::registry::ResourceInfo;
+use crate::storage::{ Storage, FromStorageRow };
+
+const JSON_SCALAR: &str = "JSON";
+
+#[derive(Default)]
+pub struct GraphqlSchemaParts {
+    resources: Vec<String>,
+    types: Vec<Object>,
+    inputs: Vec<InputObject>,
+    queries: Vec<Field>,
+    mutations: Vec<Field>
+}
+
+// the request's own parts, extensions included, so every resolver authenticates exactly like the REST routes do
+struct GraphqlRequestData<S: Storage> {
+    storage: Option<S>,
+    parts: Mutex<Parts>
+}
+
+fn status_error( status: StatusCode ) -> Error {
+    Error::new( status.canonical_reason().unwrap_or( "Error" ) ).extend_with( |_, extensions| extensions.set( "status", status.as_u16() ) )
+}
+
+fn crud_error( error: CrudError ) -> Error {
+    match error.message.is_empty() {
+        true => status_error( error.status ),
+        false => Error::new( error.message ).extend_with( |_, extensions| extensions.set( "status", error.status.as_u16() ) )
+    }
+}
+
+fn graphql_type( field_type: FieldType ) -> &'static str {
+    match field_type {
+        FieldType::UUID => TypeRef::ID,
+        FieldType::STRING => TypeRef::STRING,
+        FieldType::INTEGER | FieldType::BIGINT => TypeRef::INT,
+        FieldType::DATE => TypeRef::STRING,
+        FieldType::BOOLEAN => TypeRef::BOOLEAN,
+        FieldType::FLOAT => TypeRef::FLOAT
+    }
+}
+
+fn request_storage<S: Storage>( ctx: &ResolverContext<'_> ) -> Result<S, Error> {
+    let data = ctx.data::<Arc<GraphqlRequestData<S>>>()?;
+    data.storage.clone().ok_or_else( || status_error( StatusCode::INTERNAL_SERVER_ERROR ) )
+}
+
+async fn request_user<S: Storage, T: CrudConfig>( data: Arc<GraphqlRequestData<S>>, access: AccessPermission, verb: EndpointVerb ) -> Result<( Option<Caller>, RequestContext ), Error> {
+    let mut parts = data.parts.lock().await;
+    endpoints::request_caller::<T>( &mut parts, access, verb ).await.map_err( crud_error )
+}
+
+fn arguments_json( ctx: &ResolverContext<'_> ) -> Map<String, Value> {
+    let mut arguments = Map::new();
+    for ( name, value ) in ctx.args.iter() {
+        if !value.is_null() {
+            arguments.insert( name.to_string(), value.as_value().clone().into_json().unwrap_or( Value::Null ) );
+        }
+    }
+    arguments
+}
+
+fn argument_json( ctx: &ResolverContext<'_>, name: &str ) -> Value {
+    match ctx.args.get( name ) {
+        Some( value ) => value.as_value().clone().into_json().unwrap_or( Value::Null ),
+        None => Value::Null
+    }
+}
+
+// ids take the same textual form as the item path, composite parts joined by `/`
+fn record_id<T: CrudConfig>( ctx: &ResolverContext<'_> ) -> Result<RecordId, Error> {
+    let id = match argument_json( ctx, "id" ) {
+        Value::String( id ) => id,
+        value => value.to_string()
+    };
+    T::primary_key().parse_str( &id ).map_err( status_error )
+}
+
+fn parse_json<D: for<'de> Deserialize<'de>>( value: Value ) -> Result<D, Error> {
+    serde_json::from_value( value ).map_err( |error| Error::new( error.to_string() ).extend_with( |_, extensions| extensions.set( "status", 422 ) ) )
+}
+
+fn output_object( info: &ResourceInfo ) -> Object {
+    let mut object = Object::new( info.model.name );
+    for ( name, field_type ) in &info.model.fields {
+        let key = name.to_string();
+        object = object.field( Field::new( *name, TypeRef::named( graphql_type( *field_type ) ), move |ctx| {
+            let key = key.clone();
+            FieldFuture::new( async move {
+                let parent = ctx.parent_value.try_downcast_ref::<Value>()?;
+                match parent.get( &key ) {
+                    Some( Value::Null ) | None => Ok( None ),
+                    Some( value ) => Ok( Some( FieldValue::value( async_graphql::Value::from_json( value.clone() )? ) ) )
+                }
+            } )
+        } ) );
+    }
+    object
+}
+
+fn input_object( info: &ResourceInfo ) -> InputObject {
+    let mut input = InputObject::new( format!( "{}Input", info.model.name ) );
+    for ( name, field_type ) in &info.input.fields {
+        input = input.field( InputValue::new( *name, TypeRef::named_nn( graphql_type( *field_type ) ) ) );
+    }
+    input
+}
+
+pub fn resource_schema<S, T, UP, QP, OP>( info: &ResourceInfo, parts: &mut GraphqlSchemaParts ) where
+        S: Storage,
+        T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue,
+        UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue,
+        QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue,
+        OP: Serialize + Send + 'static + OutputSerializer<T> {
+
+    parts.resources.push( info.endpoint_name.to_string() );
+
+    let typed_output = !info.model.fields.is_empty();
+    let typed_input = !info.input.fields.is_empty();
+    let output_type = match typed_output {
+        true => info.model.name.to_string(),
+        false => JSON_SCALAR.to_string()
+    };
+    let input_type = match typed_input {
+        true => format!( "{}Input", info.model.name ),
+        false => JSON_SCALAR.to_string()
+    };
+
+    if typed_output {
+        parts.types.push( output_object( info ) );
+    }
+    if typed_input && ( info.verb( EndpointVerb::POST ).is_some() || info.verb( EndpointVerb::PUT ).is_some() ) {
+        parts.inputs.push( input_object( info ) );
+    }
+
+    for verb in &info.verbs {
+        let access = verb.access;
+        match verb.verb {
+            EndpointVerb::GET => {
+                let mut field = Field::new( info.endpoint_name, TypeRef::named_nn_list_nn( output_type.clone() ), move |ctx| {
+                    FieldFuture::new( async move {
+                        let data = ctx.data::<Arc<GraphqlRequestData<S>>>()?.clone();
+                        let storage = request_storage::<S>( &ctx )?;
+                        let filters = match ctx.args.get( "filter" ) {
+                            Some( filter ) => filter.as_value().clone().into_json()?,
+                            None => Value::Object( arguments_json( &ctx ) )
+                        };
+                        let params = parse_json::<QP>( filters )?;
+                        let ( caller, context ) = request_user::<S, T>( data, access, EndpointVerb::GET ).await?;
+
+                        let Json( values ) = endpoints::_http_get::<S, T, QP, OP>( &storage, params, caller, context ).await.map_err( status_error )?;
+                        Ok( Some( FieldValue::list( values.into_iter().map( |value| match typed_output {
+                            true => FieldValue::owned_any( value ),
+                            false => FieldValue::value( async_graphql::Value::from_json( value ).unwrap_or_default() )
+                        } ) ) ) )
+                    } )
+                } );
+
+                if info.query.fields.is_empty() {
+                    field = field.argument( InputValue::new( "filter", TypeRef::named( JSON_SCALAR ) ) );
+                }
+                for ( name, field_type ) in &info.query.fields {
+                    field = field.argument( InputValue::new( *name, TypeRef::named( graphql_type( *field_type ) ) ) );
+                }
+                parts.queries.push( field );
+            },
+            EndpointVerb::POST => {
+                let field = Field::new( format!( "create{}", info.model.name ), TypeRef::named_nn( TypeRef::ID ), move |ctx| {
+                    FieldFuture::new( async move {
+                        let data = ctx.data::<Arc<GraphqlRequestData<S>>>()?.clone();
+                        let storage = request_storage::<S>( &ctx )?;
+                        let input = parse_json::<UP>( argument_json( &ctx, "input" ) )?;
+                        let ( caller, context ) = request_user::<S, T>( data, access, EndpointVerb::POST ).await?;
+
+                        let Json( id ) = endpoints::_http_post::<S, T, UP>( &storage, input, caller, context ).await.map_err( crud_error )?;
+                        Ok( Some( FieldValue::value( id.to_string() ) ) )
+                    } )
+                } ).argument( InputValue::new( "input", TypeRef::named_nn( input_type.clone() ) ) );
+                parts.mutations.push( field );
+            },
+            EndpointVerb::PUT => {
+                let field = Field::new( format!( "update{}", info.model.name ), TypeRef::named_nn( TypeRef::BOOLEAN ), move |ctx| {
+                    FieldFuture::new( async move {
+                        let data = ctx.data::<Arc<GraphqlRequestData<S>>>()?.clone();
+                        let storage = request_storage::<S>( &ctx )?;
+                        let id = record_id::<T>( &ctx )?;
+                        let input = parse_json::<UP>( argument_json( &ctx, "input" ) )?;
+                        let ( caller, context ) = request_user::<S, T>( data, access, EndpointVerb::PUT ).await?;
+
+                        endpoints::_http_put::<S, T, UP>( &storage, id, input, caller, context ).await.map_err( crud_error )?;
+                        Ok( Some( FieldValue::value( true ) ) )
+                    } )
+                } )
+                .argument( InputValue::new( "id", TypeRef::named_nn( TypeRef::ID ) ) )
+                .argument( InputValue::new( "input", TypeRef::named_nn( input_type.clone() ) ) );
+                parts.mutations.push( field );
+            },
+            EndpointVerb::DELETE => {
+                let field = Field::new( format!( "delete{}", info.model.name ), TypeRef::named_nn( TypeRef::BOOLEAN ), move |ctx| {
+                    FieldFuture::new( async move {
+                        let data = ctx.data::<Arc<GraphqlRequestData<S>>>()?.clone();
+                        let storage = request_storage::<S>( &ctx )?;
+                        let id = record_id::<T>( &ctx )?;
+                        let ( caller, context ) = request_user::<S, T>( data, access, EndpointVerb::DELETE ).await?;
+
+                        endpoints::_http_delete::<S, T>( &storage, id, caller, context ).await.map_err( crud_error )?;
+                        Ok( Some( FieldValue::value( true ) ) )
+                    } )
+                } ).argument( InputValue::new( "id", TypeRef::named_nn( TypeRef::ID ) ) );
+                parts.mutations.push( field );
+            }
+        }
+    }
+}
+
+pub fn build_schema( parts: GraphqlSchemaParts ) -> Result<Schema, SchemaError> {
+    let has_mutations = !parts.mutations.is_empty();
+    let resources = parts.resources;
+    let mut query = Object::new( "Query" ).field( Field::new( "janusResources", TypeRef::named_nn_list_nn( TypeRef::STRING ), move |_| {
+        FieldFuture::from_value( Some( async_graphql::Value::List( resources.iter().map( |name| async_graphql::Value::from( name.as_str() ) ).collect() ) ) )
+    } ) );
+    for field in parts.queries {
+        query = query.field( field );
+    }
+
+    let mut builder = Schema::build( "Query", if has_mutations { Some( "Mutation" ) } else { None }, None )
+        .register( Scalar::new( JSON_SCALAR ) )
+        .register( query );
+
+    if has_mutations {
+        let mut mutation = Object::new( "Mutation" );
+        for field in parts.mutations {
+            mutation = mutation.field( field );
+        }
+        builder = builder.register( mutation );
+    }
+
+    for object in parts.types {
+        builder = builder.register( object );
+    }
+    for input in parts.inputs {
+        builder = builder.register( input );
+    }
+
+    builder.finish()
+}
+
+async fn graphql_handler<S: Storage>( Extension( schema ): Extension<Schema>, request: Request<Body> ) -> Result<Json<async_graphql::Response>, StatusCode> {
+    let ( parts, body ) = request.into_parts();
+    let bytes = hyper::body::to_bytes( body ).await.map_err( |_| StatusCode::BAD_REQUEST )?;
+    let request: async_graphql::Request = serde_json::from_slice( &bytes ).map_err( |_| StatusCode::BAD_REQUEST )?;
+
+    let data = GraphqlRequestData {
+        storage: parts.extensions.get::<S>().cloned(),
+        parts: Mutex::new( parts )
+    };
+
+    Ok( Json( schema.execute( request.data( Arc::new( data ) ) ).await ) )
+}
+
+pub fn graphql_router<S: Storage>( parts: GraphqlSchemaParts, path: &str ) -> Router {
+    if parts.resources.is_empty() {
+        return Router::new();
+    }
+
+    let schema = match build_schema( parts ) {
+        Ok( schema ) => schema,
+        Err( error ) => panic!( "Failed to build GraphQL schema: {:?}", error )
+    };
+
+    let playground = playground_source( GraphQLPlaygroundConfig::new( path ) );
+    Router::new()
+        .route( path, axum::routing::post( graphql_handler::<S> ).get( move || async move { Html( playground ) } ) )
+        .layer( Extension( schema ) )
+}
@@ -0,0 +1,69 @@
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use crate::{ EndpointVerb, FieldValue, KeyValue, SchemaTrait };
+use crate::keys::UUID_KEY;
+use crate::storage::Storage;
+
+pub const IMPERSONATIONS_TABLE: &str = "janus_impersonations";
+
+pub struct ImpersonationSchema {}
+
+impl SchemaTrait for ImpersonationSchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            admin_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            resource TEXT NOT NULL,
+            verb TEXT NOT NULL,
+            object_id TEXT,
+            date_created TIMESTAMPTZ NOT NULL
+        );", IMPERSONATIONS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Impersonation {
+    pub id: uuid::Uuid,
+    pub admin_id: String,
+    pub user_id: String,
+    pub resource: String,
+    pub verb: String,
+    pub object_id: Option<String>,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for Impersonation {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("admin_id", FieldValue::STRING( &self.admin_id )),
+            ("user_id", FieldValue::STRING( &self.user_id )),
+            ("resource", FieldValue::STRING( &self.resource )),
+            ("verb", FieldValue::STRING( &self.verb )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ];
+
+        if let Some( object_id ) = &self.object_id {
+            pairs.push( ("object_id", FieldValue::STRING( object_id )) );
+        }
+
+        pairs
+    }
+}
+
+// the action only goes ahead once its audit row is written, and the row is only kept if the action commits
+pub async fn record_impersonation<S: Storage>( storage: &S, admin_id: &str, user_id: &str, resource: &str, verb: EndpointVerb, object_id: Option<String> ) -> Result<(), StatusCode> {
+    let impersonation = Impersonation {
+        id: uuid::Uuid::new_v4(),
+        admin_id: admin_id.to_string(),
+        user_id: user_id.to_string(),
+        resource: resource.to_string(),
+        verb: verb.name().to_string(),
+        object_id,
+        date_created: chrono::Utc::now()
+    };
+
+    storage.insert( IMPERSONATIONS_TABLE, UUID_KEY, &impersonation.key_value_pairs() ).await?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use crate::{ FieldType, FieldValue };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum KeyType {
+    UUID,
+    SERIAL,
+    TEXT
+}
+
+impl KeyType {
+    pub fn field_type( &self ) -> FieldType {
+        match self {
+            KeyType::UUID => FieldType::UUID,
+            KeyType::SERIAL => FieldType::BIGINT,
+            KeyType::TEXT => FieldType::STRING
+        }
+    }
+
+    pub fn parse( &self, segment: &str ) -> Result<RecordId, StatusCode> {
+        match self {
+            KeyType::UUID => uuid::Uuid::parse_str( segment ).map( RecordId::UUID ).map_err( |_| StatusCode::BAD_REQUEST ),
+            KeyType::SERIAL => segment.parse::<i64>().map( RecordId::INTEGER ).map_err( |_| StatusCode::BAD_REQUEST ),
+            KeyType::TEXT if !segment.is_empty() => Ok( RecordId::TEXT( segment.to_string() ) ),
+            KeyType::TEXT => Err( StatusCode::BAD_REQUEST )
+        }
+    }
+}
+
+// the columns that address a row, in path order, more than one for a composite key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PrimaryKey {
+    pub columns: &'static [( &'static str, KeyType )]
+}
+
+pub const UUID_KEY: PrimaryKey = PrimaryKey { columns: &[ ( "id", KeyType::UUID ) ] };
+
+impl PrimaryKey {
+    pub fn is_composite( &self ) -> bool {
+        self.columns.len() > 1
+    }
+
+    pub fn first_column( &self ) -> &'static str {
+        self.columns.first().map( |( column, _ )| *column ).unwrap_or( "id" )
+    }
+
+    pub fn single_type( &self ) -> Option<KeyType> {
+        match self.columns {
+            [ ( _, key_type ) ] => Some( *key_type ),
+            _ => None
+        }
+    }
+
+    // single keys keep the `:id` segment, composite keys get one segment per column
+    pub fn path( &self ) -> String {
+        match self.is_composite() {
+            true => self.columns.iter().map( |( column, _ )| format!( ":{}", column ) ).collect::<Vec<_>>().join( "/" ),
+            false => ":id".to_string()
+        }
+    }
+
+    pub fn path_names( &self ) -> Vec<&'static str> {
+        match self.is_composite() {
+            true => self.columns.iter().map( |( column, _ )| *column ).collect(),
+            false => vec![ "id" ]
+        }
+    }
+
+    pub fn parse( &self, segments: &[String] ) -> Result<RecordId, StatusCode> {
+        if segments.len() != self.columns.len() {
+            return Err( StatusCode::BAD_REQUEST );
+        }
+
+        let mut parts = self.columns.iter()
+            .zip( segments )
+            .map( |( ( _, key_type ), segment )| key_type.parse( segment ) )
+            .collect::<Result<Vec<_>, StatusCode>>()?;
+
+        match self.is_composite() {
+            true => Ok( RecordId::COMPOSITE( parts ) ),
+            false => Ok( parts.remove( 0 ) )
+        }
+    }
+
+    // the textual form used outside of paths, composite parts joined by `/` like the route
+    pub fn parse_str( &self, id: &str ) -> Result<RecordId, StatusCode> {
+        let segments = id.splitn( self.columns.len(), '/' ).map( str::to_string ).collect::<Vec<_>>();
+        self.parse( &segments )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordId {
+    UUID( uuid::Uuid ),
+    INTEGER( i64 ),
+    TEXT( String ),
+    COMPOSITE( Vec<RecordId> )
+}
+
+impl RecordId {
+    pub fn as_uuid( &self ) -> Option<uuid::Uuid> {
+        match self {
+            RecordId::UUID( id ) => Some( *id ),
+            _ => None
+        }
+    }
+
+    fn field_value( &self, key_type: KeyType ) -> Option<FieldValue<'_>> {
+        match ( self, key_type ) {
+            ( RecordId::UUID( id ), KeyType::UUID ) => Some( FieldValue::UUID( id ) ),
+            ( RecordId::INTEGER( id ), KeyType::SERIAL ) => Some( FieldValue::BIGINT( id ) ),
+            ( RecordId::TEXT( id ), KeyType::TEXT ) => Some( FieldValue::STRING( id ) ),
+            _ => None
+        }
+    }
+
+    // pairs each key column with its part of the id, None when the id doesn't have the key's shape
+    pub fn key_values( &self, key: PrimaryKey ) -> Option<Vec<(&'static str, FieldValue<'_>)>> {
+        let parts = match self {
+            RecordId::COMPOSITE( parts ) => parts.iter().collect::<Vec<_>>(),
+            _ => vec![ self ]
+        };
+        if parts.len() != key.columns.len() {
+            return None;
+        }
+
+        key.columns.iter()
+            .zip( parts )
+            .map( |( ( column, key_type ), part )| part.field_value( *key_type ).map( |value| ( *column, value ) ) )
+            .collect()
+    }
+}
+
+impl From<uuid::Uuid> for RecordId {
+    fn from( id: uuid::Uuid ) -> RecordId {
+        RecordId::UUID( id )
+    }
+}
+
+impl fmt::Display for RecordId {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        match self {
+            RecordId::UUID( id ) => write!( f, "{}", id ),
+            RecordId::INTEGER( id ) => write!( f, "{}", id ),
+            RecordId::TEXT( id ) => write!( f, "{}", id ),
+            RecordId::COMPOSITE( parts ) => write!( f, "{}", parts.iter().map( |part| part.to_string() ).collect::<Vec<_>>().join( "/" ) )
+        }
+    }
+}
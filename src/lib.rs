@@ -1,17 +1,42 @@
 use std::fmt::Debug;
 use axum::Router;
 use serde::{Serialize, Deserialize};
-use sqlx::{FromRow, postgres::{PgRow, PgConnectOptions, PgPool}};
+use sqlx::postgres::{PgConnectOptions, PgPool};
 use axum::http::StatusCode;
 use axum::async_trait;
 use axum::Json;
+use axum::response::{ IntoResponse, Response };
 
 pub mod extractors;
+pub mod registry;
+pub mod builder;
+pub mod openapi;
+pub mod typescript;
+pub mod cli;
+pub mod client;
+pub mod storage;
+pub mod testing;
+pub mod dev;
+pub mod api_keys;
+pub mod revocations;
+pub mod impersonation;
+pub mod transitions;
+pub mod sharing;
+pub mod keys;
+#[cfg(feature = "accounts")]
+pub mod accounts;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 mod endpoints;
 
+pub use builder::{ Janus, JanusBuilder, ErrorFormat };
+pub use registry::{ ResourceRegistry, ResourceInfo, VerbInfo, ActionInfo };
+pub use storage::{ Storage, FromStorageRow };
+pub use keys::{ KeyType, PrimaryKey, RecordId };
+
 mod tests;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum EndpointVerb {
     GET,
     POST,
@@ -19,33 +44,325 @@ pub enum EndpointVerb {
     DELETE
 } 
 
+impl EndpointVerb {
+    pub fn name( &self ) -> &'static str {
+        match self {
+            EndpointVerb::GET => "GET",
+            EndpointVerb::POST => "POST",
+            EndpointVerb::PUT => "PUT",
+            EndpointVerb::DELETE => "DELETE"
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum FieldValue<'a> {
     UUID(&'a uuid::Uuid),
     STRING(&'a String),
     INTEGER(&'a i32),
+    BIGINT(&'a i64),
     DATE(&'a chrono::DateTime<chrono::Utc>),
     BOOLEAN(&'a bool),
     FLOAT(&'a f64)
 }
 
+impl<'a> FieldValue<'a> {
+    pub fn field_type( &self ) -> FieldType {
+        match self {
+            FieldValue::UUID(_) => FieldType::UUID,
+            FieldValue::STRING(_) => FieldType::STRING,
+            FieldValue::INTEGER(_) => FieldType::INTEGER,
+            FieldValue::BIGINT(_) => FieldType::BIGINT,
+            FieldValue::DATE(_) => FieldType::DATE,
+            FieldValue::BOOLEAN(_) => FieldType::BOOLEAN,
+            FieldValue::FLOAT(_) => FieldType::FLOAT
+        }
+    }
+}
+
+// an owned field value, for columns the server fills in rather than the input
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetValue {
+    UUID(uuid::Uuid),
+    STRING(String),
+    INTEGER(i32),
+    BIGINT(i64),
+    DATE(chrono::DateTime<chrono::Utc>),
+    BOOLEAN(bool),
+    FLOAT(f64)
+}
+
+impl SetValue {
+    pub fn field_value( &self ) -> FieldValue<'_> {
+        match self {
+            SetValue::UUID( value ) => FieldValue::UUID( value ),
+            SetValue::STRING( value ) => FieldValue::STRING( value ),
+            SetValue::INTEGER( value ) => FieldValue::INTEGER( value ),
+            SetValue::BIGINT( value ) => FieldValue::BIGINT( value ),
+            SetValue::DATE( value ) => FieldValue::DATE( value ),
+            SetValue::BOOLEAN( value ) => FieldValue::BOOLEAN( value ),
+            SetValue::FLOAT( value ) => FieldValue::FLOAT( value )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FieldType {
+    UUID,
+    STRING,
+    INTEGER,
+    BIGINT,
+    DATE,
+    BOOLEAN,
+    FLOAT
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum ObjectPermission {
     ALL,
-    OWNER
+    OWNER,
+    TENANT,
+    TENANTADMIN,
+    SHARED
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum AccessPermission {
     ANY,
+    OPTIONAL,
     AUTHENTICATED,
     ADMIN
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ActionTarget {
+    COLLECTION,
+    ITEM
+}
+
+// an operation beyond CRUD, posted to the collection or to one record and answered by `run_action`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Action {
+    pub name: &'static str,
+    pub target: ActionTarget,
+    pub access: AccessPermission,
+    pub object: ObjectPermission
+}
+
+impl Action {
+    pub fn collection( name: &'static str, access: AccessPermission, object: ObjectPermission ) -> Action {
+        Action { name, target: ActionTarget::COLLECTION, access, object }
+    }
+
+    pub fn item( name: &'static str, access: AccessPermission, object: ObjectPermission ) -> Action {
+        Action { name, target: ActionTarget::ITEM, access, object }
+    }
+
+    // api key scopes, grant levels and impersonation records treat an action as the verb it is closest to
+    pub fn verb( &self ) -> EndpointVerb {
+        match self.target {
+            ActionTarget::COLLECTION => EndpointVerb::POST,
+            ActionTarget::ITEM => EndpointVerb::PUT
+        }
+    }
+}
+
+// a named move of a resource's state column, posted to one record like an item action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    pub name: &'static str,
+    pub from: &'static [&'static str],
+    pub to: &'static str,
+    pub access: AccessPermission,
+    pub object: ObjectPermission,
+    pub permission: Option<&'static str>
+}
+
+impl Transition {
+    pub fn new( name: &'static str, from: &'static [&'static str], to: &'static str ) -> Transition {
+        Transition { name, from, to, access: AccessPermission::AUTHENTICATED, object: ObjectPermission::OWNER, permission: None }
+    }
+
+    pub fn access( mut self, access: AccessPermission, object: ObjectPermission ) -> Transition {
+        self.access = access;
+        self.object = object;
+        self
+    }
+
+    pub fn requires( mut self, permission: &'static str ) -> Transition {
+        self.permission = Some( permission );
+        self
+    }
+
+    pub fn action( &self ) -> Action {
+        Action::item( self.name, self.access, self.object )
+    }
+
+    pub fn leaves( &self, state: &str ) -> bool {
+        self.from.contains( &state )
+    }
+
+    // updates change the state column outside the transition's route, so its admin access and permission are checked again
+    pub fn permits( &self, context: &RequestContext ) -> bool {
+        ( self.access != AccessPermission::ADMIN || context.admin ) &&
+            self.permission.map( |permission| context.permissions.iter().any( |held| held == permission ) ).unwrap_or( true )
+    }
+}
+
+// the only ways the state column may change, whether through a transition route or a generated update
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StateMachine {
+    pub column: &'static str,
+    pub transitions: Vec<Transition>
+}
+
+impl StateMachine {
+    pub fn new( column: &'static str ) -> StateMachine {
+        StateMachine { column, transitions: vec![] }
+    }
+
+    pub fn transition( mut self, transition: Transition ) -> StateMachine {
+        self.transitions.push( transition );
+        self
+    }
+
+    pub fn get( &self, name: &str ) -> Option<Transition> {
+        self.transitions.iter().find( |transition| transition.name == name ).copied()
+    }
+
+    pub fn between( &self, from: &str, to: &str ) -> Option<Transition> {
+        self.transitions.iter().find( |transition| transition.to == to && transition.leaves( from ) ).copied()
+    }
+}
+
+// declared actions followed by the state machine's transitions, which are mounted the same way
+pub(crate) fn resource_actions<T: CrudConfig>() -> Vec<Action> {
+    let mut actions = T::actions();
+    if let Some( machine ) = T::state_machine() {
+        actions.extend( machine.transitions.iter().map( Transition::action ) );
+    }
+    actions
+}
+
+// who may see a field in generated responses and change it through a generated update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FieldPermission {
+    PUBLIC,
+    READONLY,
+    OWNERONLY,
+    ADMINONLY,
+    HIDDEN
+}
+
 pub trait InputSerializer<T: CrudConfig + Serialize > {
     fn verify(&self) -> bool;
     fn add_set_values(&self, user_id: Option<String>) -> T;
+
+    // written by every generated update, replacing anything the input carried for those columns
+    #[allow(unused_variables)]
+    fn update_set_values(&self, user_id: Option<String>) -> Vec<(&'static str, SetValue)> {
+        vec![]
+    }
+}
+
+// everything known about the request a generated operation runs for, `admin` is only set for admins working as themselves
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub user_id: Option<String>,
+    pub subscription: Option<String>,
+    pub permissions: Vec<String>,
+    pub scopes: Option<Vec<String>>,
+    pub tenant: Option<extractors::Tenant>,
+    pub groups: Vec<String>,
+    pub admin: bool,
+    pub impersonated_by: Option<String>,
+    pub headers: axum::http::HeaderMap,
+    pub request_id: String,
+    pub transaction: Option<storage::RequestTransaction>
+}
+
+impl RequestContext {
+    // joins the request's transaction when it runs on this kind of storage, otherwise begins one of its own,
+    // commit the handle either way since a joined one leaves committing to the request
+    pub async fn transaction<S: Storage>( &self, storage: &S ) -> Result<S::Transaction, StatusCode> {
+        match self.transaction.as_ref().and_then( |transaction| transaction.lazy::<S>() ) {
+            Some( lazy ) => lazy.get().await,
+            None => storage.transaction().await
+        }
+    }
+}
+
+// a refused operation, the message becomes the response body so clients see why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrudError {
+    pub status: StatusCode,
+    pub message: String
+}
+
+impl CrudError {
+    pub fn new( status: StatusCode, message: &str ) -> CrudError {
+        CrudError { status, message: message.to_string() }
+    }
+
+    pub fn bad_request( message: &str ) -> CrudError {
+        CrudError::new( StatusCode::BAD_REQUEST, message )
+    }
+
+    pub fn forbidden( message: &str ) -> CrudError {
+        CrudError::new( StatusCode::FORBIDDEN, message )
+    }
+
+    pub fn conflict( message: &str ) -> CrudError {
+        CrudError::new( StatusCode::CONFLICT, message )
+    }
+}
+
+impl From<StatusCode> for CrudError {
+    fn from( status: StatusCode ) -> CrudError {
+        CrudError { status, message: String::new() }
+    }
+}
+
+impl From<( StatusCode, &'static str )> for CrudError {
+    fn from( ( status, message ): ( StatusCode, &'static str ) ) -> CrudError {
+        CrudError::new( status, message )
+    }
+}
+
+impl IntoResponse for CrudError {
+    fn into_response( self ) -> Response {
+        match self.message.is_empty() {
+            true => self.status.into_response(),
+            false => ( self.status, self.message ).into_response()
+        }
+    }
+}
+
+pub trait OutputSerializer<T> {
+    fn from_row( row: T, context: &RequestContext ) -> Self;
+
+    fn output_fields() -> Vec<(&'static str, FieldType)> where Self: Sized {
+        vec![]
+    }
+}
+
+// rows are their own representation unless a resource is registered with a separate output type
+impl<T: KeyValue> OutputSerializer<T> for T {
+    fn from_row( row: T, _context: &RequestContext ) -> T {
+        row
+    }
+
+    fn output_fields() -> Vec<(&'static str, FieldType)> {
+        T::field_types()
+    }
 }
 
 pub trait KeyValue {
     fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)>;
+
+    fn field_types() -> Vec<(&'static str, FieldType)> where Self: Sized {
+        vec![]
+    }
 }
 
 #[async_trait]
@@ -59,70 +376,184 @@ pub trait CrudConfig {
     fn get_object_permissions( verb: &EndpointVerb ) -> ObjectPermission;
     fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission;
 
+    fn tenant_column() -> &'static str {
+        "tenant_id"
+    }
+
+    fn owner_column() -> &'static str {
+        "user_id"
+    }
+
+    fn primary_key() -> PrimaryKey {
+        keys::UUID_KEY
+    }
+
+    #[allow(unused_variables)]
+    fn field_permission( field: &str ) -> FieldPermission {
+        FieldPermission::PUBLIC
+    }
+
+    // set on create and never changed, a generated update may only repeat the stored value,
+    // one that changes nothing else leaves the row alone and does not stamp `update_set_values` like updated_at or updated_by
+    fn immutable_columns() -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn actions() -> Vec<Action> {
+        vec![]
+    }
+
+    fn state_machine() -> Option<StateMachine> {
+        None
+    }
+
+    // before hooks may change what is written or refuse it, after hooks see the written record,
+    // both run in the write's transaction so an error from either rolls the write back,
+    // update inputs are generic so a hook reaches its own input type through `Any`
+    #[allow(unused_variables)]
+    async fn before_create<S: Storage>( storage: &S, context: &RequestContext, values: &mut Self ) -> Result<(), CrudError> where Self: Sized + Send + Sync {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn after_create<S: Storage>( storage: &S, context: &RequestContext, id: &RecordId, values: &Self ) -> Result<(), CrudError> where Self: Sized + Send + Sync {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn before_update<S: Storage, UP: Send + Sync + KeyValue + 'static>( storage: &S, context: &RequestContext, id: &RecordId, values: &mut UP ) -> Result<(), CrudError> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn after_update<S: Storage, UP: Send + Sync + KeyValue + 'static>( storage: &S, context: &RequestContext, id: &RecordId, values: &UP ) -> Result<(), CrudError> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn before_delete<S: Storage>( storage: &S, context: &RequestContext, id: &RecordId ) -> Result<(), CrudError> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn after_delete<S: Storage>( storage: &S, context: &RequestContext, id: &RecordId ) -> Result<(), CrudError> {
+        Ok(())
+    }
+
+    // runs once the record is known to be in one of the transition's source states, before anything is written
     #[allow(unused_variables)]
-    async fn custom_create<T>( connection_pool: &PgPool, values: T ) -> Result<Json<uuid::Uuid>, StatusCode> where T : Send + Sync + Unpin + KeyValue { 
+    async fn guard_transition<S: Storage>( storage: &S, context: &RequestContext, id: &RecordId, transition: &Transition, from: &str ) -> Result<(), CrudError> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_create<S, T>( storage: &S, context: &RequestContext, values: T ) -> Result<Json<RecordId>, StatusCode> where S: Storage, T : Send + Sync + Unpin + KeyValue { 
         Err( StatusCode::NOT_IMPLEMENTED ) 
     } 
 
     #[allow(unused_variables)]
-    async fn custom_read<T, QP>( connection_pool: &PgPool, filters: QP, user_id: Option<String> ) 
-        -> Result<Json<Vec<T>>, StatusCode> where T: for<'r> FromRow<'r, PgRow> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
+    async fn custom_read<S, T, QP>( storage: &S, context: &RequestContext, filters: QP, user_id: Option<String> ) 
+        -> Result<Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
         Err( StatusCode::NOT_IMPLEMENTED )
     }
 
     #[allow(unused_variables)]
-    async fn custom_update<UP>( connection_pool: &PgPool, id: uuid::Uuid, values: UP, user_id: Option<String> ) -> StatusCode where UP: Send + Sync + Unpin + KeyValue {
+    async fn custom_update<S, UP>( storage: &S, context: &RequestContext, id: RecordId, values: UP, user_id: Option<String> ) -> StatusCode where S: Storage, UP: Send + Sync + Unpin + KeyValue {
         StatusCode::NOT_IMPLEMENTED
     }
 
+    // item actions only run once the record is known to be within the action's object permission
+    #[allow(unused_variables)]
+    async fn run_action<S: Storage>( storage: &S, context: &RequestContext, action: &str, id: Option<RecordId>, input: serde_json::Value ) -> Result<Json<serde_json::Value>, CrudError> {
+        Err( StatusCode::NOT_IMPLEMENTED.into() )
+    }
+
     #[allow(unused_variables)]
-    async fn custom_delete( connection_pool: &PgPool, ids: Vec<uuid::Uuid>, user_id: Option<String> ) -> StatusCode {
+    async fn custom_delete<S>( storage: &S, context: &RequestContext, ids: Vec<RecordId>, user_id: Option<String> ) -> StatusCode where S: Storage {
         StatusCode::NOT_IMPLEMENTED
     }
 }
 
 
 pub fn create_endpoint_router<T, UP, QP>() -> Router where 
-        T: for<'r> FromRow<'r, PgRow> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue, 
+        T: FromStorageRow<PgPool> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue, 
         UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue, 
         QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue {
+    create_storage_router::<PgPool, T, UP, QP>()
+}
+
+pub fn create_endpoint_router_with_output<T, UP, QP, OP>() -> Router where 
+        T: FromStorageRow<PgPool> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue, 
+        UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue, 
+        QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue,
+        OP: Serialize + Send + 'static + OutputSerializer<T> {
+    create_storage_router_with_output::<PgPool, T, UP, QP, OP>()
+}
+
+pub fn create_storage_router<S, T, UP, QP>() -> Router where 
+        S: Storage,
+        T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue, 
+        UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue, 
+        QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue {
+    create_storage_router_with_output::<S, T, UP, QP, T>()
+}
+
+pub fn create_storage_router_with_output<S, T, UP, QP, OP>() -> Router where 
+        S: Storage,
+        T: FromStorageRow<S> + Send + Sync + 'static + Unpin + CrudConfig + Serialize + KeyValue, 
+        UP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + InputSerializer<T> + KeyValue, 
+        QP: Serialize + for<'de> Deserialize<'de> + Send + Sync + Unpin + 'static + Debug + KeyValue,
+        OP: Serialize + Send + 'static + OutputSerializer<T> {
     let mut router = Router::new();
+    let item_path = format!( "/{}/{}", T::endpoint_name(), T::primary_key().path() );
 
     for verb in vec![ EndpointVerb::GET, EndpointVerb::POST, EndpointVerb::PUT, EndpointVerb::DELETE ] {
         if T::include_endpoint( &verb ) {
             match verb {
                 EndpointVerb::GET => {
                     match T::get_access_permissions( &verb ) {
-                        AccessPermission::ANY => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_any::<T, QP> ) ); },
-                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_auth::<T, QP> ) ); },
-                        AccessPermission::ADMIN => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_admin::<T, QP> ) ); }
+                        AccessPermission::ANY => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_any::<S, T, QP, OP> ) ); },
+                        AccessPermission::OPTIONAL => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_optional::<S, T, QP, OP> ) ); },
+                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_auth::<S, T, QP, OP> ) ); },
+                        AccessPermission::ADMIN => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::get( endpoints::http_get_admin::<S, T, QP, OP> ) ); }
                     }
                 },
                 EndpointVerb::POST => {
                     match T::get_access_permissions( &verb ) {
-                        AccessPermission::ANY => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_any::<T, UP> ) ); },
-                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_auth::<T, UP> ) ); },
-                        AccessPermission::ADMIN => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_admin::<T, UP> ) ); }
+                        AccessPermission::ANY => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_any::<S, T, UP> ) ); },
+                        AccessPermission::OPTIONAL => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_optional::<S, T, UP> ) ); },
+                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_auth::<S, T, UP> ) ); },
+                        AccessPermission::ADMIN => { router = router.route( &format!("/{}", T::endpoint_name()), axum::routing::post( endpoints::http_post_admin::<S, T, UP> ) ); }
                     }
                 },
                 EndpointVerb::PUT => {
                     match T::get_access_permissions( &verb ) {
-                        AccessPermission::ANY => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::put( endpoints::http_put_any::<T, UP> ) ); },
-                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::put( endpoints::http_put_auth::<T, UP> ) ); },
-                        AccessPermission::ADMIN => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::put( endpoints::http_put_admin::<T, UP> ) ); }
+                        AccessPermission::ANY => { router = router.route( &item_path, axum::routing::put( endpoints::http_put_any::<S, T, UP> ) ); },
+                        AccessPermission::OPTIONAL => { router = router.route( &item_path, axum::routing::put( endpoints::http_put_optional::<S, T, UP> ) ); },
+                        AccessPermission::AUTHENTICATED => { router = router.route( &item_path, axum::routing::put( endpoints::http_put_auth::<S, T, UP> ) ); },
+                        AccessPermission::ADMIN => { router = router.route( &item_path, axum::routing::put( endpoints::http_put_admin::<S, T, UP> ) ); }
                     }
                 },
                 EndpointVerb::DELETE => {
                     match T::get_access_permissions( &verb ) {
-                        AccessPermission::ANY => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::delete( endpoints::http_delete_any::<T> ) ); },
-                        AccessPermission::AUTHENTICATED => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::delete( endpoints::http_delete_auth::<T> ) ); },
-                        AccessPermission::ADMIN => { router = router.route( &format!("/{}/:id", T::endpoint_name()), axum::routing::delete( endpoints::http_delete_admin::<T> ) ); }
+                        AccessPermission::ANY => { router = router.route( &item_path, axum::routing::delete( endpoints::http_delete_any::<S, T> ) ); },
+                        AccessPermission::OPTIONAL => { router = router.route( &item_path, axum::routing::delete( endpoints::http_delete_optional::<S, T> ) ); },
+                        AccessPermission::AUTHENTICATED => { router = router.route( &item_path, axum::routing::delete( endpoints::http_delete_auth::<S, T> ) ); },
+                        AccessPermission::ADMIN => { router = router.route( &item_path, axum::routing::delete( endpoints::http_delete_admin::<S, T> ) ); }
                     }
                 }
             }
         }
     }
 
+    for action in resource_actions::<T>() {
+        let path = match action.target {
+            ActionTarget::COLLECTION => format!( "/{}/{}", T::endpoint_name(), action.name ),
+            ActionTarget::ITEM => format!( "{}/{}", item_path, action.name )
+        };
+        router = router.route( &path, axum::routing::post( move |storage: axum::Extension<S>, request: axum::http::Request<axum::body::Body>| endpoints::http_action::<S, T>( action, storage, request ) ) );
+    }
+
     return router;
 }
 
@@ -1,25 +1,25 @@
 use axum::{routing::get, Router};
 use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
+use janus::Janus;
 
 
 #[tokio::main]
 async fn main() {
     let cors = CorsLayer::new().allow_origin(Any);
 
-    let app = Router::new()
-        .route("/", get(root))
-        .layer(cors);
+    let app = Janus::builder()
+        .merge( Router::new().route("/", get(root)) )
+        .cors(cors);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 4747));
-    println!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    if let Err( error ) = janus::cli::run( app, addr ).await {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
 
 async fn root() -> &'static str {
     "Hello, World!"
-}
\ No newline at end of file
+}
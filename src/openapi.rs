@@ -0,0 +1,302 @@
+use axum::{ Json, Router };
+use axum::response::Html;
+use serde_json::{ json, Map, Value };
+use crate::{ EndpointVerb, AccessPermission, ActionTarget, FieldType };
+use crate::extractors::{ ACT_AS_HEADER, ACT_AS_TENANT_HEADER, ACT_AS_TENANT_ROLE_HEADER };
+use crate::registry::{ ResourceRegistry, ResourceInfo, TypeInfo, VerbInfo, ActionInfo };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocsUi {
+    SWAGGER,
+    REDOC
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenApiConfig {
+    pub title: String,
+    pub version: String,
+    pub spec_path: String,
+    pub docs: Option<(DocsUi, String)>
+}
+
+impl OpenApiConfig {
+    pub fn new( title: &str, version: &str ) -> OpenApiConfig {
+        OpenApiConfig { title: title.to_string(), version: version.to_string(), spec_path: "/openapi.json".to_string(), docs: None }
+    }
+
+    pub fn spec_path( mut self, path: &str ) -> Self {
+        self.spec_path = path.to_string();
+        self
+    }
+
+    pub fn docs( mut self, ui: DocsUi, path: &str ) -> Self {
+        self.docs = Some( ( ui, path.to_string() ) );
+        self
+    }
+}
+
+pub fn openapi_document( registry: &ResourceRegistry, title: &str, version: &str ) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+
+    for resource in registry.resources() {
+        schemas.insert( resource.model.name.to_string(), object_schema( &resource.model, false ) );
+        schemas.insert( resource.input.name.to_string(), object_schema( &resource.input, true ) );
+
+        for verb in &resource.verbs {
+            let path = openapi_path( &verb.path );
+            let entry = paths.entry( path ).or_insert_with( || Value::Object( Map::new() ) );
+            if let Value::Object( operations ) = entry {
+                operations.insert( method_name( verb.verb ).to_string(), operation( resource, verb ) );
+            }
+        }
+
+        for action in &resource.actions {
+            let entry = paths.entry( openapi_path( &action.path ) ).or_insert_with( || Value::Object( Map::new() ) );
+            if let Value::Object( operations ) = entry {
+                operations.insert( "post".to_string(), action_operation( resource, action ) );
+            }
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "userAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                "subscriptionToken": { "type": "apiKey", "in": "header", "name": "Subscription", "description": "Bearer token carrying the caller's subscription claims" },
+                "adminAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                "apiKeyAuth": { "type": "apiKey", "in": "header", "name": "X-Api-Key", "description": "Janus-issued API key for machine clients" }
+            }
+        }
+    })
+}
+
+pub fn openapi_router( registry: &ResourceRegistry, config: &OpenApiConfig ) -> Router {
+    let document = openapi_document( registry, &config.title, &config.version );
+    let mut router = Router::new().route( &config.spec_path, axum::routing::get( move || async move { Json( document ) } ) );
+
+    if let Some( ( ui, path ) ) = &config.docs {
+        let page = docs_page( *ui, &config.title, &config.spec_path );
+        router = router.route( path, axum::routing::get( move || async move { Html( page ) } ) );
+    }
+
+    router
+}
+
+pub fn field_schema( field_type: FieldType ) -> Value {
+    match field_type {
+        FieldType::UUID => json!({ "type": "string", "format": "uuid" }),
+        FieldType::STRING => json!({ "type": "string" }),
+        FieldType::INTEGER => json!({ "type": "integer", "format": "int32" }),
+        FieldType::BIGINT => json!({ "type": "integer", "format": "int64" }),
+        FieldType::DATE => json!({ "type": "string", "format": "date-time" }),
+        FieldType::BOOLEAN => json!({ "type": "boolean" }),
+        FieldType::FLOAT => json!({ "type": "number", "format": "double" })
+    }
+}
+
+fn object_schema( info: &TypeInfo, all_required: bool ) -> Value {
+    if info.fields.is_empty() {
+        return json!({ "type": "object", "additionalProperties": true });
+    }
+
+    let mut properties = Map::new();
+    for ( name, field_type ) in &info.fields {
+        properties.insert( name.to_string(), field_schema( *field_type ) );
+    }
+
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if all_required {
+        schema["required"] = json!( info.fields.iter().map( |( name, _ )| *name ).collect::<Vec<&str>>() );
+    }
+    schema
+}
+
+fn openapi_path( path: &str ) -> String {
+    path.split( '/' )
+        .map( |segment| match segment.strip_prefix( ':' ) {
+            Some( name ) => format!( "{{{}}}", name ),
+            None => segment.to_string()
+        } )
+        .collect::<Vec<String>>()
+        .join( "/" )
+}
+
+fn method_name( verb: EndpointVerb ) -> &'static str {
+    match verb {
+        EndpointVerb::GET => "get",
+        EndpointVerb::POST => "post",
+        EndpointVerb::PUT => "put",
+        EndpointVerb::DELETE => "delete"
+    }
+}
+
+fn filter_description( name: &str ) -> String {
+    match name {
+        "order_by" => return "Column to order results by".to_string(),
+        "order_dir" => return "Order direction, `asc` or `desc`".to_string(),
+        "limit" => return "Maximum number of rows to return".to_string(),
+        "offset" => return "Number of rows to skip before returning results".to_string(),
+        _ => {}
+    }
+
+    if name.len() > 3 {
+        let ( column, suffix ) = name.split_at( name.len() - 3 );
+        match suffix {
+            "_gt" => return format!( "Only return rows where `{}` is greater than the value", column ),
+            "_lt" => return format!( "Only return rows where `{}` is less than the value", column ),
+            "_ge" => return format!( "Only return rows where `{}` is greater than or equal to the value", column ),
+            "_le" => return format!( "Only return rows where `{}` is less than or equal to the value", column ),
+            _ => {}
+        }
+    }
+
+    format!( "Only return rows where `{}` equals the value", name )
+}
+
+fn query_parameters( query: &TypeInfo ) -> Vec<Value> {
+    query.fields.iter().map( |( name, field_type )| {
+        let mut schema = field_schema( *field_type );
+        if *name == "order_dir" {
+            schema = json!({ "type": "string", "enum": [ "asc", "desc" ] });
+        }
+
+        json!({ "name": name, "in": "query", "required": false, "description": filter_description( name ), "schema": schema })
+    } ).collect()
+}
+
+fn security( access: AccessPermission ) -> Option<Value> {
+    match access {
+        AccessPermission::ANY => None,
+        AccessPermission::OPTIONAL => Some( json!([ {}, { "userAuth": [] }, { "userAuth": [], "subscriptionToken": [] }, { "apiKeyAuth": [] } ]) ),
+        AccessPermission::AUTHENTICATED => Some( json!([ { "userAuth": [] }, { "userAuth": [], "subscriptionToken": [] }, { "apiKeyAuth": [] } ]) ),
+        AccessPermission::ADMIN => Some( json!([ { "adminAuth": [] } ]) )
+    }
+}
+
+fn schema_ref( name: &str ) -> Value {
+    json!({ "$ref": format!( "#/components/schemas/{}", name ) })
+}
+
+fn id_parameters( resource: &ResourceInfo ) -> Vec<Value> {
+    resource.key.path_names().into_iter()
+        .zip( resource.key.columns )
+        .map( |( name, ( _, key_type ) )| json!({ "name": name, "in": "path", "required": true, "schema": field_schema( key_type.field_type() ) }) )
+        .collect()
+}
+
+fn operation( resource: &ResourceInfo, verb: &VerbInfo ) -> Value {
+    let id_parameters = id_parameters( resource );
+    let id_schema = match resource.key.single_type() {
+        Some( key_type ) => field_schema( key_type.field_type() ),
+        None => json!({ "type": "array", "items": {} })
+    };
+    let mut responses = Map::new();
+
+    let operation = match verb.verb {
+        EndpointVerb::GET => {
+            responses.insert( "200".to_string(), json!({
+                "description": format!( "List of {}", resource.endpoint_name ),
+                "content": { "application/json": { "schema": { "type": "array", "items": schema_ref( resource.model.name ) } } }
+            }) );
+            json!({ "operationId": format!( "list_{}", resource.endpoint_name ), "parameters": query_parameters( &resource.query ) })
+        },
+        EndpointVerb::POST => {
+            responses.insert( "200".to_string(), json!({
+                "description": "Id of the created object",
+                "content": { "application/json": { "schema": id_schema } }
+            }) );
+            responses.insert( "400".to_string(), json!({ "description": "Input failed verification" }) );
+            json!({
+                "operationId": format!( "create_{}", resource.endpoint_name ),
+                "requestBody": { "required": true, "content": { "application/json": { "schema": schema_ref( resource.input.name ) } } }
+            })
+        },
+        EndpointVerb::PUT => {
+            responses.insert( "200".to_string(), json!({ "description": "Object updated" }) );
+            responses.insert( "400".to_string(), json!({ "description": "Input failed verification or object not found" }) );
+            json!({
+                "operationId": format!( "update_{}", resource.endpoint_name ),
+                "parameters": id_parameters,
+                "requestBody": { "required": true, "content": { "application/json": { "schema": schema_ref( resource.input.name ) } } }
+            })
+        },
+        EndpointVerb::DELETE => {
+            responses.insert( "200".to_string(), json!({ "description": "Object deleted" }) );
+            responses.insert( "400".to_string(), json!({ "description": "Object not found" }) );
+            json!({ "operationId": format!( "delete_{}", resource.endpoint_name ), "parameters": id_parameters })
+        }
+    };
+
+    secured( resource, operation, responses, verb.access )
+}
+
+fn action_operation( resource: &ResourceInfo, action: &ActionInfo ) -> Value {
+    let mut responses = Map::new();
+    responses.insert( "200".to_string(), json!({ "description": format!( "Result of {}", action.name ), "content": { "application/json": { "schema": {} } } }) );
+
+    let mut operation = json!({
+        "operationId": format!( "{}_{}", action.name, resource.endpoint_name ),
+        "requestBody": { "required": false, "content": { "application/json": { "schema": {} } } }
+    });
+    if action.target == ActionTarget::ITEM {
+        responses.insert( "400".to_string(), json!({ "description": "Object not found" }) );
+        operation["parameters"] = Value::Array( id_parameters( resource ) );
+    }
+
+    secured( resource, operation, responses, action.access )
+}
+
+fn secured( resource: &ResourceInfo, mut operation: Value, mut responses: Map<String, Value>, access: AccessPermission ) -> Value {
+    if let Some( security ) = security( access ) {
+        responses.insert( "401".to_string(), json!({ "description": "Missing or invalid token" }) );
+        operation["security"] = security;
+    }
+
+    if access == AccessPermission::ADMIN {
+        let act_as = vec![
+            json!({ "name": ACT_AS_HEADER, "in": "header", "required": false, "description": "Act as this user, needs the impersonation permission", "schema": { "type": "string" } }),
+            json!({ "name": ACT_AS_TENANT_HEADER, "in": "header", "required": false, "description": "Act as the user within this tenant", "schema": { "type": "string" } }),
+            json!({ "name": ACT_AS_TENANT_ROLE_HEADER, "in": "header", "required": false, "description": "The user's role within the tenant", "schema": { "type": "string" } })
+        ];
+        match operation["parameters"].as_array_mut() {
+            Some( parameters ) => parameters.extend( act_as ),
+            None => operation["parameters"] = Value::Array( act_as )
+        }
+    }
+
+    operation["tags"] = json!([ resource.endpoint_name ]);
+    operation["responses"] = Value::Object( responses );
+    operation
+}
+
+fn docs_page( ui: DocsUi, title: &str, spec_path: &str ) -> String {
+    match ui {
+        DocsUi::SWAGGER => format!( r##"<!DOCTYPE html>
+<html>
+<head>
+    <title>{title}</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>window.ui = SwaggerUIBundle({{ url: "{spec_path}", dom_id: "#swagger-ui" }});</script>
+</body>
+</html>"## ),
+        DocsUi::REDOC => format!( r##"<!DOCTYPE html>
+<html>
+<head>
+    <title>{title}</title>
+</head>
+<body>
+    <redoc spec-url="{spec_path}"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+</body>
+</html>"## )
+    }
+}
@@ -0,0 +1,157 @@
+use serde::Serialize;
+use crate::{ CrudConfig, KeyValue, EndpointVerb, AccessPermission, ObjectPermission, FieldType, PrimaryKey, ActionTarget, resource_actions };
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeInfo {
+    pub name: &'static str,
+    #[serde(skip)]
+    pub path: &'static str,
+    pub fields: Vec<(&'static str, FieldType)>
+}
+
+impl TypeInfo {
+    pub fn of<K: KeyValue>() -> TypeInfo {
+        let full_name = std::any::type_name::<K>();
+        let name = full_name.split( '<' ).next().unwrap_or( full_name ).rsplit( "::" ).next().unwrap_or( full_name );
+
+        TypeInfo { name, path: full_name, fields: K::field_types() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerbInfo {
+    pub verb: EndpointVerb,
+    pub path: String,
+    pub access: AccessPermission,
+    pub object: ObjectPermission,
+    pub custom: bool
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionInfo {
+    pub name: &'static str,
+    pub path: String,
+    pub target: ActionTarget,
+    pub access: AccessPermission,
+    pub object: ObjectPermission
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceInfo {
+    pub endpoint_name: &'static str,
+    pub table_name: &'static str,
+    pub owner_column: &'static str,
+    pub key: PrimaryKey,
+    pub prefix: String,
+    pub verbs: Vec<VerbInfo>,
+    pub actions: Vec<ActionInfo>,
+    pub model: TypeInfo,
+    pub input: TypeInfo,
+    pub query: TypeInfo
+}
+
+impl ResourceInfo {
+    pub fn of<T: CrudConfig + KeyValue, UP: KeyValue, QP: KeyValue>( prefix: &str ) -> ResourceInfo {
+        let mut verbs = vec![];
+
+        for verb in [ EndpointVerb::GET, EndpointVerb::POST, EndpointVerb::PUT, EndpointVerb::DELETE ] {
+            if !T::include_endpoint( &verb ) {
+                continue;
+            }
+
+            let path = match verb {
+                EndpointVerb::GET | EndpointVerb::POST => format!("{}/{}", prefix, T::endpoint_name()),
+                EndpointVerb::PUT | EndpointVerb::DELETE => format!("{}/{}/{}", prefix, T::endpoint_name(), T::primary_key().path())
+            };
+
+            verbs.push( VerbInfo {
+                path,
+                access: T::get_access_permissions( &verb ),
+                object: T::get_object_permissions( &verb ),
+                custom: T::is_custom( &verb ),
+                verb
+            } );
+        }
+
+        let actions = resource_actions::<T>().into_iter().map( |action| ActionInfo {
+            name: action.name,
+            path: match action.target {
+                ActionTarget::COLLECTION => format!("{}/{}/{}", prefix, T::endpoint_name(), action.name),
+                ActionTarget::ITEM => format!("{}/{}/{}/{}", prefix, T::endpoint_name(), T::primary_key().path(), action.name)
+            },
+            target: action.target,
+            access: action.access,
+            object: action.object
+        } ).collect();
+
+        ResourceInfo {
+            endpoint_name: T::endpoint_name(),
+            table_name: T::table_name(),
+            owner_column: T::owner_column(),
+            key: T::primary_key(),
+            prefix: prefix.to_string(),
+            verbs,
+            actions,
+            model: TypeInfo::of::<T>(),
+            input: TypeInfo::of::<UP>(),
+            query: TypeInfo::of::<QP>()
+        }
+    }
+
+    pub fn verb( &self, verb: EndpointVerb ) -> Option<&VerbInfo> {
+        self.verbs.iter().find( |info| info.verb == verb )
+    }
+
+    pub fn types( &self ) -> [&TypeInfo; 3] {
+        [ &self.model, &self.input, &self.query ]
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResourceRegistry {
+    resources: Vec<ResourceInfo>
+}
+
+impl ResourceRegistry {
+    pub fn new() -> ResourceRegistry {
+        ResourceRegistry { resources: vec![] }
+    }
+
+    pub fn register( &mut self, info: ResourceInfo ) -> bool {
+        if self.resources.iter().any( |existing| existing.endpoint_name == info.endpoint_name && existing.prefix == info.prefix ) {
+            return false;
+        }
+
+        self.resources.push( info );
+        true
+    }
+
+    // schemas are keyed by the short type name, so two different types sharing one would overwrite each other
+    pub fn type_conflict( &self, info: &ResourceInfo ) -> Option<( &'static str, &'static str )> {
+        let known = self.resources.iter().flat_map( |existing| existing.types() );
+
+        for candidate in info.types() {
+            if let Some( other ) = known.clone().chain( info.types() ).find( |other| other.name == candidate.name && other.path != candidate.path ) {
+                return Some( ( candidate.path, other.path ) );
+            }
+        }
+
+        None
+    }
+
+    pub fn resources( &self ) -> &[ResourceInfo] {
+        &self.resources
+    }
+
+    pub fn get( &self, endpoint_name: &str ) -> Option<&ResourceInfo> {
+        self.resources.iter().find( |info| info.endpoint_name == endpoint_name )
+    }
+
+    pub fn len( &self ) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.resources.is_empty()
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{ HashMap, HashSet };
+use std::sync::{ Arc, RwLock };
+use std::time::{ Duration, Instant };
+use axum::{ async_trait, Extension, Json, Router };
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use crate::{ FieldValue, KeyValue, SchemaTrait };
+use crate::keys::UUID_KEY;
+use crate::extractors::AdminUser;
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, FilterOp };
+
+pub const REVOCATIONS_TABLE: &str = "janus_revocations";
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs( 30 );
+// other instances' rows can commit a little after the date_created they were stamped with
+const RELOAD_OVERLAP: Duration = Duration::from_secs( 60 );
+
+pub struct RevocationSchema {}
+
+impl SchemaTrait for RevocationSchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            jti TEXT,
+            subject TEXT,
+            revoked_before TIMESTAMPTZ,
+            date_created TIMESTAMPTZ NOT NULL
+        );", REVOCATIONS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Revocation {
+    pub id: uuid::Uuid,
+    pub jti: Option<String>,
+    pub subject: Option<String>,
+    pub revoked_before: Option<chrono::DateTime<chrono::Utc>>,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for Revocation {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ];
+
+        if let Some( jti ) = &self.jti {
+            pairs.push( ("jti", FieldValue::STRING( jti )) );
+        }
+
+        if let Some( subject ) = &self.subject {
+            pairs.push( ("subject", FieldValue::STRING( subject )) );
+        }
+
+        if let Some( revoked_before ) = &self.revoked_before {
+            pairs.push( ("revoked_before", FieldValue::DATE( revoked_before )) );
+        }
+
+        pairs
+    }
+}
+
+#[async_trait]
+pub trait RevocationStore: Send + Sync {
+    async fn insert_revocation( &self, revocation: &Revocation ) -> Result<(), StatusCode>;
+    async fn list_revocations( &self ) -> Result<Vec<Revocation>, StatusCode>;
+    async fn list_revocations_since( &self, since: chrono::DateTime<chrono::Utc> ) -> Result<Vec<Revocation>, StatusCode>;
+}
+
+#[async_trait]
+impl<S> RevocationStore for S where S: Storage, Revocation: FromStorageRow<S> {
+    async fn insert_revocation( &self, revocation: &Revocation ) -> Result<(), StatusCode> {
+        self.insert( REVOCATIONS_TABLE, UUID_KEY, &revocation.key_value_pairs() ).await?;
+        Ok(())
+    }
+
+    async fn list_revocations( &self ) -> Result<Vec<Revocation>, StatusCode> {
+        let query = ReadQuery {
+            table: REVOCATIONS_TABLE,
+            filters: vec![],
+            owner: None,
+            order_by: "date_created",
+            descending: true,
+            limit: None,
+            offset: None
+        };
+
+        self.select( &query ).await?
+            .into_iter()
+            .map( Revocation::from_storage_row )
+            .collect()
+    }
+
+    async fn list_revocations_since( &self, since: chrono::DateTime<chrono::Utc> ) -> Result<Vec<Revocation>, StatusCode> {
+        let query = ReadQuery {
+            table: REVOCATIONS_TABLE,
+            filters: vec![ Filter { column: "date_created", op: FilterOp::GE, value: FieldValue::DATE( &since ) } ],
+            owner: None,
+            order_by: "date_created",
+            descending: true,
+            limit: None,
+            offset: None
+        };
+
+        self.select( &query ).await?
+            .into_iter()
+            .map( Revocation::from_storage_row )
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct RevocationCache {
+    jtis: HashSet<String>,
+    subjects: HashMap<String, i64>,
+    newest: Option<chrono::DateTime<chrono::Utc>>,
+    loaded_at: Option<Instant>
+}
+
+impl RevocationCache {
+    fn add( &mut self, revocation: &Revocation ) {
+        self.newest = self.newest.max( Some( revocation.date_created ) );
+
+        if let Some( jti ) = &revocation.jti {
+            self.jtis.insert( jti.clone() );
+        }
+
+        if let ( Some( subject ), Some( revoked_before ) ) = ( &revocation.subject, &revocation.revoked_before ) {
+            let timestamp = self.subjects.entry( subject.clone() ).or_insert( revoked_before.timestamp() );
+            *timestamp = ( *timestamp ).max( revoked_before.timestamp() );
+        }
+    }
+
+    // iat has second precision, so a token from the same second as the revocation counts as revoked
+    fn is_revoked( &self, jti: Option<&str>, subject: &str, issued_at: Option<u64> ) -> bool {
+        if jti.map( |jti| self.jtis.contains( jti ) ).unwrap_or( false ) {
+            return true;
+        }
+
+        match ( self.subjects.get( subject ), issued_at ) {
+            ( Some( revoked_before ), Some( issued_at ) ) => issued_at as i64 <= *revoked_before,
+            ( Some(_), None ) => true,
+            ( None, _ ) => false
+        }
+    }
+}
+
+// other instances' revocations show up once the cache goes stale and the rows added since are read from the table
+#[derive(Clone)]
+pub struct Revocations {
+    store: Arc<dyn RevocationStore>,
+    cache: Arc<RwLock<RevocationCache>>,
+    ttl: Duration
+}
+
+impl Revocations {
+    pub fn new<K: RevocationStore + 'static>( store: K ) -> Revocations {
+        Revocations { store: Arc::new( store ), cache: Arc::new( RwLock::new( RevocationCache::default() ) ), ttl: DEFAULT_CACHE_TTL }
+    }
+
+    pub fn ttl( mut self, ttl: Duration ) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    async fn reload_if_stale( &self ) -> Result<(), StatusCode> {
+        let stale = match self.cache.read().unwrap().loaded_at {
+            Some( loaded_at ) => loaded_at.elapsed() >= self.ttl,
+            None => true
+        };
+        if !stale {
+            return Ok(());
+        }
+
+        // adding is idempotent, so the overlap only costs re-reading a few rows
+        let newest = self.cache.read().unwrap().newest;
+        let revocations = match newest {
+            Some( newest ) => self.store.list_revocations_since( newest - chrono::Duration::from_std( RELOAD_OVERLAP ).unwrap() ).await?,
+            None => self.store.list_revocations().await?
+        };
+
+        let mut cache = self.cache.write().unwrap();
+        for revocation in &revocations {
+            cache.add( revocation );
+        }
+        cache.loaded_at = Some( Instant::now() );
+        Ok(())
+    }
+
+    async fn record( &self, revocation: Revocation ) -> Result<(), StatusCode> {
+        self.store.insert_revocation( &revocation ).await?;
+        self.cache.write().unwrap().add( &revocation );
+        Ok(())
+    }
+
+    pub async fn revoke_token( &self, jti: &str ) -> Result<(), StatusCode> {
+        self.record( Revocation {
+            id: uuid::Uuid::new_v4(),
+            jti: Some( jti.to_string() ),
+            subject: None,
+            revoked_before: None,
+            date_created: chrono::Utc::now()
+        } ).await
+    }
+
+    pub async fn revoke_user( &self, user_id: &str ) -> Result<(), StatusCode> {
+        let now = chrono::Utc::now();
+        self.record( Revocation {
+            id: uuid::Uuid::new_v4(),
+            jti: None,
+            subject: Some( user_id.to_string() ),
+            revoked_before: Some( now ),
+            date_created: now
+        } ).await
+    }
+
+    pub async fn is_revoked( &self, jti: Option<&str>, subject: &str, issued_at: Option<u64> ) -> Result<bool, StatusCode> {
+        self.reload_if_stale().await?;
+        Ok( self.cache.read().unwrap().is_revoked( jti, subject, issued_at ) )
+    }
+
+    pub async fn list( &self ) -> Result<Vec<Revocation>, StatusCode> {
+        self.store.list_revocations().await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokeRequest {
+    pub jti: Option<String>,
+    pub user_id: Option<String>
+}
+
+async fn http_revoke( _admin: AdminUser, Extension( revocations ): Extension<Revocations>, Json( request ): Json<RevokeRequest> ) -> StatusCode {
+    let result = match ( request.jti.as_deref(), request.user_id.as_deref() ) {
+        ( Some( jti ), None ) if !jti.is_empty() => revocations.revoke_token( jti ).await,
+        ( None, Some( user_id ) ) if !user_id.is_empty() => revocations.revoke_user( user_id ).await,
+        _ => Err( StatusCode::BAD_REQUEST )
+    };
+
+    match result {
+        Ok(()) => StatusCode::OK,
+        Err( status ) => status
+    }
+}
+
+async fn http_list( _admin: AdminUser, Extension( revocations ): Extension<Revocations> ) -> Result<Json<Vec<Revocation>>, StatusCode> {
+    Ok( Json( revocations.list().await? ) )
+}
+
+pub fn revocations_router( path: &str ) -> Router {
+    Router::new().route( path, axum::routing::post( http_revoke ).get( http_list ) )
+}
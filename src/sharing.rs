@@ -0,0 +1,331 @@
+use std::sync::Arc;
+use axum::{ async_trait, Extension, Json, Router };
+use axum::extract::{ Path, Query };
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use crate::{ EndpointVerb, FieldValue, KeyType, KeyValue, ObjectPermission, RecordId, SchemaTrait };
+use crate::keys::UUID_KEY;
+use crate::extractors::AuthUser;
+use crate::registry::{ ResourceRegistry, ResourceInfo };
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, FilterOp, Owner, Shared };
+
+pub const GRANTS_TABLE: &str = "janus_grants";
+
+pub struct GrantSchema {}
+
+impl SchemaTrait for GrantSchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            resource TEXT NOT NULL,
+            record_id UUID NOT NULL,
+            grantee TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            granted_by TEXT NOT NULL,
+            date_created TIMESTAMPTZ NOT NULL
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS {}_grantee ON {} ( resource, record_id, grantee );", GRANTS_TABLE, GRANTS_TABLE, GRANTS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GrantLevel {
+    READ,
+    WRITE,
+    MANAGE
+}
+
+impl GrantLevel {
+    pub fn rank( &self ) -> i32 {
+        match self {
+            GrantLevel::READ => 1,
+            GrantLevel::WRITE => 2,
+            GrantLevel::MANAGE => 3
+        }
+    }
+
+    // deleting a shared record takes the same level as changing who it is shared with
+    pub fn for_verb( verb: EndpointVerb ) -> GrantLevel {
+        match verb {
+            EndpointVerb::GET | EndpointVerb::POST => GrantLevel::READ,
+            EndpointVerb::PUT => GrantLevel::WRITE,
+            EndpointVerb::DELETE => GrantLevel::MANAGE
+        }
+    }
+}
+
+pub fn user_grantee( user_id: &str ) -> String {
+    format!( "user:{}", user_id )
+}
+
+pub fn group_grantee( group: &str ) -> String {
+    format!( "group:{}", group )
+}
+
+pub fn grantees( user_id: &str, groups: &[String] ) -> Vec<String> {
+    let mut grantees = vec![ user_grantee( user_id ) ];
+    grantees.extend( groups.iter().map( |group| group_grantee( group ) ) );
+    grantees
+}
+
+pub fn shared_owner( resource: &str, owner_column: &'static str, key_column: &'static str, user_id: &str, groups: &[String], level: GrantLevel ) -> Owner {
+    Owner {
+        column: owner_column,
+        user_id: user_id.to_string(),
+        shared: Some( Shared { resource: resource.to_string(), key: key_column, grantees: grantees( user_id, groups ), level: level.rank() } )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Grant {
+    pub id: uuid::Uuid,
+    pub resource: String,
+    pub record_id: uuid::Uuid,
+    pub grantee: String,
+    pub level: i32,
+    pub granted_by: String,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for Grant {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("resource", FieldValue::STRING( &self.resource )),
+            ("record_id", FieldValue::UUID( &self.record_id )),
+            ("grantee", FieldValue::STRING( &self.grantee )),
+            ("level", FieldValue::INTEGER( &self.level )),
+            ("granted_by", FieldValue::STRING( &self.granted_by )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ]
+    }
+}
+
+#[async_trait]
+pub trait GrantStore: Send + Sync {
+    async fn insert_grant( &self, grant: &Grant ) -> Result<(), StatusCode>;
+    async fn replace_grant( &self, grant: &Grant ) -> Result<uuid::Uuid, StatusCode>;
+    async fn find_grant( &self, id: uuid::Uuid ) -> Result<Option<Grant>, StatusCode>;
+    async fn list_grants( &self, resource: &str, record_id: uuid::Uuid ) -> Result<Vec<Grant>, StatusCode>;
+    async fn delete_grants( &self, ids: &[uuid::Uuid] ) -> Result<u64, StatusCode>;
+    async fn record_visible( &self, table: &'static str, record_id: uuid::Uuid, owner: Owner ) -> Result<bool, StatusCode>;
+}
+
+#[async_trait]
+impl<S> GrantStore for S where S: Storage, Grant: FromStorageRow<S> {
+    async fn insert_grant( &self, grant: &Grant ) -> Result<(), StatusCode> {
+        self.insert( GRANTS_TABLE, UUID_KEY, &grant.key_value_pairs() ).await?;
+        Ok(())
+    }
+
+    // a grantee holds at most one grant per record, sharing with them again changes its level in place
+    async fn replace_grant( &self, grant: &Grant ) -> Result<uuid::Uuid, StatusCode> {
+        let query = ReadQuery {
+            table: GRANTS_TABLE,
+            filters: vec![
+                Filter { column: "resource", op: FilterOp::EQ, value: FieldValue::STRING( &grant.resource ) },
+                Filter { column: "record_id", op: FilterOp::EQ, value: FieldValue::UUID( &grant.record_id ) },
+                Filter { column: "grantee", op: FilterOp::EQ, value: FieldValue::STRING( &grant.grantee ) }
+            ],
+            owner: None,
+            order_by: "id",
+            descending: true,
+            limit: Some( 1 ),
+            offset: None
+        };
+
+        let transaction = self.transaction().await?;
+        let existing = match transaction.select( &query ).await?.into_iter().next() {
+            Some( row ) => Some( <Grant as FromStorageRow<S>>::from_storage_row( row )? ),
+            None => None
+        };
+
+        let id = match existing {
+            Some( existing ) => {
+                let values = [ ("level", FieldValue::INTEGER( &grant.level )), ("granted_by", FieldValue::STRING( &grant.granted_by )) ];
+                transaction.update( GRANTS_TABLE, UUID_KEY, &RecordId::from( existing.id ), &values, None ).await?;
+                existing.id
+            },
+            None => {
+                // a concurrent first share can win the insert, the UNIQUE grantee index then turns this one away
+                if let Err( status ) = transaction.insert( GRANTS_TABLE, UUID_KEY, &grant.key_value_pairs() ).await {
+                    transaction.rollback().await?;
+                    return match self.select( &query ).await?.is_empty() {
+                        true => Err( status ),
+                        false => Err( StatusCode::CONFLICT )
+                    };
+                }
+                grant.id
+            }
+        };
+        transaction.commit().await?;
+        Ok( id )
+    }
+
+    async fn find_grant( &self, id: uuid::Uuid ) -> Result<Option<Grant>, StatusCode> {
+        let query = ReadQuery {
+            table: GRANTS_TABLE,
+            filters: vec![ Filter { column: "id", op: FilterOp::EQ, value: FieldValue::UUID( &id ) } ],
+            owner: None,
+            order_by: "id",
+            descending: true,
+            limit: Some( 1 ),
+            offset: None
+        };
+
+        match self.select( &query ).await?.into_iter().next() {
+            Some( row ) => Ok( Some( Grant::from_storage_row( row )? ) ),
+            None => Ok( None )
+        }
+    }
+
+    async fn list_grants( &self, resource: &str, record_id: uuid::Uuid ) -> Result<Vec<Grant>, StatusCode> {
+        let resource = resource.to_string();
+        let query = ReadQuery {
+            table: GRANTS_TABLE,
+            filters: vec![
+                Filter { column: "resource", op: FilterOp::EQ, value: FieldValue::STRING( &resource ) },
+                Filter { column: "record_id", op: FilterOp::EQ, value: FieldValue::UUID( &record_id ) }
+            ],
+            owner: None,
+            order_by: "date_created",
+            descending: false,
+            limit: None,
+            offset: None
+        };
+
+        self.select( &query ).await?
+            .into_iter()
+            .map( Grant::from_storage_row )
+            .collect()
+    }
+
+    async fn delete_grants( &self, ids: &[uuid::Uuid] ) -> Result<u64, StatusCode> {
+        let ids = ids.iter().copied().map( RecordId::from ).collect::<Vec<_>>();
+        self.delete( GRANTS_TABLE, UUID_KEY, &ids, None ).await
+    }
+
+    async fn record_visible( &self, table: &'static str, record_id: uuid::Uuid, owner: Owner ) -> Result<bool, StatusCode> {
+        let key = owner.shared.as_ref().map( |shared| shared.key ).unwrap_or( "id" );
+        let query = ReadQuery {
+            table,
+            filters: vec![ Filter { column: key, op: FilterOp::EQ, value: FieldValue::UUID( &record_id ) } ],
+            owner: Some( owner ),
+            order_by: key,
+            descending: true,
+            limit: Some( 1 ),
+            offset: None
+        };
+
+        Ok( !self.select( &query ).await?.is_empty() )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareRequest {
+    pub resource: String,
+    pub record_id: uuid::Uuid,
+    pub user_id: Option<String>,
+    pub group: Option<String>,
+    pub level: GrantLevel
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListGrantsQuery {
+    pub resource: String,
+    pub record_id: uuid::Uuid
+}
+
+#[derive(Clone)]
+pub struct Sharing {
+    store: Arc<dyn GrantStore>
+}
+
+impl Sharing {
+    pub fn new<K: GrantStore + 'static>( store: K ) -> Sharing {
+        Sharing { store: Arc::new( store ) }
+    }
+
+    // only the record's owner, or someone it was shared with at MANAGE, may see or change its grants
+    async fn check_manage( &self, info: &ResourceInfo, record_id: uuid::Uuid, user: &AuthUser ) -> Result<(), StatusCode> {
+        if !user.allows( info.endpoint_name, EndpointVerb::PUT ) {
+            return Err( StatusCode::FORBIDDEN );
+        }
+
+        let owner = shared_owner( info.endpoint_name, info.owner_column, info.key.first_column(), &user.user_id, &user.groups, GrantLevel::MANAGE );
+        match self.store.record_visible( info.table_name, record_id, owner ).await? {
+            true => Ok(()),
+            false => Err( StatusCode::FORBIDDEN )
+        }
+    }
+
+    pub async fn share( &self, info: &ResourceInfo, user: &AuthUser, request: &ShareRequest ) -> Result<uuid::Uuid, StatusCode> {
+        let grantee = match ( request.user_id.as_deref(), request.group.as_deref() ) {
+            ( Some( user_id ), None ) if !user_id.is_empty() => user_grantee( user_id ),
+            ( None, Some( group ) ) if !group.is_empty() => group_grantee( group ),
+            _ => return Err( StatusCode::BAD_REQUEST )
+        };
+        self.check_manage( info, request.record_id, user ).await?;
+
+        let grant = Grant {
+            id: uuid::Uuid::new_v4(),
+            resource: info.endpoint_name.to_string(),
+            record_id: request.record_id,
+            grantee,
+            level: request.level.rank(),
+            granted_by: user.user_id.clone(),
+            date_created: chrono::Utc::now()
+        };
+        // losing the insert to a concurrent first share with the same grantee comes back as a conflict,
+        // going again updates the grant it made instead
+        match self.store.replace_grant( &grant ).await {
+            Err( StatusCode::CONFLICT ) => self.store.replace_grant( &grant ).await,
+            result => result
+        }
+    }
+
+    pub async fn list( &self, info: &ResourceInfo, user: &AuthUser, record_id: uuid::Uuid ) -> Result<Vec<Grant>, StatusCode> {
+        self.check_manage( info, record_id, user ).await?;
+        self.store.list_grants( info.endpoint_name, record_id ).await
+    }
+
+    pub async fn unshare( &self, registry: &ResourceRegistry, user: &AuthUser, id: uuid::Uuid ) -> Result<(), StatusCode> {
+        let grant = self.store.find_grant( id ).await?.ok_or( StatusCode::BAD_REQUEST )?;
+        let info = shared_resource( registry, &grant.resource )?;
+        self.check_manage( info, grant.record_id, user ).await?;
+
+        self.store.delete_grants( &[ id ] ).await?;
+        Ok(())
+    }
+}
+
+fn shared_resource<'a>( registry: &'a ResourceRegistry, resource: &str ) -> Result<&'a ResourceInfo, StatusCode> {
+    match registry.get( resource ) {
+        // grants point at records by uuid, so only resources keyed that way can be shared
+        Some( info ) if info.key.single_type() == Some( KeyType::UUID ) && info.verbs.iter().any( |verb| verb.object == ObjectPermission::SHARED ) => Ok( info ),
+        _ => Err( StatusCode::BAD_REQUEST )
+    }
+}
+
+async fn http_share( user: AuthUser, Extension( sharing ): Extension<Sharing>, Extension( registry ): Extension<Arc<ResourceRegistry>>, Json( request ): Json<ShareRequest> ) -> Result<Json<uuid::Uuid>, StatusCode> {
+    let info = shared_resource( &registry, &request.resource )?;
+    Ok( Json( sharing.share( info, &user, &request ).await? ) )
+}
+
+async fn http_list_grants( user: AuthUser, Extension( sharing ): Extension<Sharing>, Extension( registry ): Extension<Arc<ResourceRegistry>>, Query( query ): Query<ListGrantsQuery> ) -> Result<Json<Vec<Grant>>, StatusCode> {
+    let info = shared_resource( &registry, &query.resource )?;
+    Ok( Json( sharing.list( info, &user, query.record_id ).await? ) )
+}
+
+async fn http_unshare( user: AuthUser, Extension( sharing ): Extension<Sharing>, Extension( registry ): Extension<Arc<ResourceRegistry>>, Path( id ): Path<uuid::Uuid> ) -> StatusCode {
+    match sharing.unshare( &registry, &user, id ).await {
+        Ok(()) => StatusCode::OK,
+        Err( status ) => status
+    }
+}
+
+pub fn sharing_router( path: &str ) -> Router {
+    Router::new()
+        .route( path, axum::routing::post( http_share ).get( http_list_grants ) )
+        .route( &format!( "{}/:id", path ), axum::routing::delete( http_unshare ) )
+}
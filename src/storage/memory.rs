@@ -0,0 +1,420 @@
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, RwLock };
+use axum::async_trait;
+use axum::http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::{ Map, Value };
+use crate::{ FieldValue, KeyType, PrimaryKey, RecordId };
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, Owner, FilterOp };
+use crate::sharing::GRANTS_TABLE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryValue {
+    UUID( uuid::Uuid ),
+    STRING( String ),
+    INTEGER( i64 ),
+    DATE( chrono::DateTime<chrono::Utc> ),
+    BOOLEAN( bool ),
+    FLOAT( f64 )
+}
+
+impl From<FieldValue<'_>> for MemoryValue {
+    fn from( value: FieldValue<'_> ) -> MemoryValue {
+        match value {
+            FieldValue::UUID( value ) => MemoryValue::UUID( *value ),
+            FieldValue::STRING( value ) => MemoryValue::STRING( value.clone() ),
+            FieldValue::INTEGER( value ) => MemoryValue::INTEGER( i64::from( *value ) ),
+            FieldValue::BIGINT( value ) => MemoryValue::INTEGER( *value ),
+            FieldValue::DATE( value ) => MemoryValue::DATE( *value ),
+            FieldValue::BOOLEAN( value ) => MemoryValue::BOOLEAN( *value ),
+            FieldValue::FLOAT( value ) => MemoryValue::FLOAT( *value )
+        }
+    }
+}
+
+impl MemoryValue {
+    pub fn compare( &self, other: &MemoryValue ) -> Option<Ordering> {
+        match ( self, other ) {
+            ( MemoryValue::UUID( a ), MemoryValue::UUID( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::STRING( a ), MemoryValue::STRING( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::INTEGER( a ), MemoryValue::INTEGER( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::DATE( a ), MemoryValue::DATE( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::BOOLEAN( a ), MemoryValue::BOOLEAN( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::FLOAT( a ), MemoryValue::FLOAT( b ) ) => a.partial_cmp( b ),
+            ( MemoryValue::INTEGER( a ), MemoryValue::FLOAT( b ) ) => ( *a as f64 ).partial_cmp( b ),
+            ( MemoryValue::FLOAT( a ), MemoryValue::INTEGER( b ) ) => a.partial_cmp( &( *b as f64 ) ),
+            _ => None
+        }
+    }
+
+    pub fn to_json( &self ) -> Value {
+        match self {
+            MemoryValue::UUID( value ) => Value::String( value.to_string() ),
+            MemoryValue::STRING( value ) => Value::String( value.clone() ),
+            MemoryValue::INTEGER( value ) => Value::from( *value ),
+            MemoryValue::DATE( value ) => Value::String( value.to_rfc3339() ),
+            MemoryValue::BOOLEAN( value ) => Value::Bool( *value ),
+            MemoryValue::FLOAT( value ) => Value::from( *value )
+        }
+    }
+}
+
+pub type MemoryRow = HashMap<String, MemoryValue>;
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    tables: Arc<RwLock<HashMap<String, Vec<MemoryRow>>>>
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    pub fn rows( &self, table: &str ) -> Vec<MemoryRow> {
+        let tables = self.tables.read().unwrap();
+        tables.get( table ).cloned().unwrap_or_default()
+    }
+
+    pub fn clear( &self ) {
+        self.tables.write().unwrap().clear();
+    }
+}
+
+// answers the sql backends' grants subquery from the grants table kept alongside the rows
+fn granted_ids( tables: &HashMap<String, Vec<MemoryRow>>, owner: Option<&Owner> ) -> Vec<uuid::Uuid> {
+    let ( shared, grants ) = match ( owner.and_then( |owner| owner.shared.as_ref() ), tables.get( GRANTS_TABLE ) ) {
+        ( Some( shared ), Some( grants ) ) => ( shared, grants ),
+        _ => return vec![]
+    };
+
+    grants.iter()
+        .filter( |grant| grant.get( "resource" ) == Some( &MemoryValue::STRING( shared.resource.clone() ) ) )
+        .filter( |grant| matches!( grant.get( "level" ), Some( MemoryValue::INTEGER( level ) ) if *level >= i64::from( shared.level ) ) )
+        .filter( |grant| matches!( grant.get( "grantee" ), Some( MemoryValue::STRING( grantee ) ) if shared.grantees.contains( grantee ) ) )
+        .filter_map( |grant| match grant.get( "record_id" ) {
+            Some( MemoryValue::UUID( id ) ) => Some( *id ),
+            _ => None
+        } )
+        .collect()
+}
+
+fn matches_owner( row: &MemoryRow, owner: Option<&Owner>, granted: &[uuid::Uuid] ) -> bool {
+    let owner = match owner {
+        Some( owner ) => owner,
+        None => return true
+    };
+
+    let shared = match ( &owner.shared, owner.shared.as_ref().and_then( |shared| row.get( shared.key ) ) ) {
+        ( Some(_), Some( MemoryValue::UUID( id ) ) ) => granted.contains( id ),
+        _ => false
+    };
+    shared || row.get( owner.column ) == Some( &MemoryValue::STRING( owner.user_id.clone() ) )
+}
+
+fn matches_filter( row: &MemoryRow, column: &str, op: FilterOp, value: &MemoryValue ) -> bool {
+    let ordering = match row.get( column ).and_then( |stored| stored.compare( value ) ) {
+        Some( ordering ) => ordering,
+        None => return false
+    };
+
+    match op {
+        FilterOp::EQ => ordering == Ordering::Equal,
+        FilterOp::GT => ordering == Ordering::Greater,
+        FilterOp::LT => ordering == Ordering::Less,
+        FilterOp::GE => ordering != Ordering::Less,
+        FilterOp::LE => ordering != Ordering::Greater
+    }
+}
+
+// rows missing the sort column order first, like NULLs in an ascending Postgres sort
+fn compare_rows( a: &MemoryRow, b: &MemoryRow, column: &str ) -> Ordering {
+    match ( a.get( column ), b.get( column ) ) {
+        ( Some( a ), Some( b ) ) => a.compare( b ).unwrap_or( Ordering::Equal ),
+        ( None, Some(_) ) => Ordering::Less,
+        ( Some(_), None ) => Ordering::Greater,
+        ( None, None ) => Ordering::Equal
+    }
+}
+
+fn row_key( row: &MemoryRow, key: PrimaryKey ) -> Option<RecordId> {
+    let mut parts = key.columns.iter()
+        .map( |( column, _ )| match row.get( *column ) {
+            Some( MemoryValue::UUID( id ) ) => Some( RecordId::UUID( *id ) ),
+            Some( MemoryValue::INTEGER( id ) ) => Some( RecordId::INTEGER( *id ) ),
+            Some( MemoryValue::STRING( id ) ) => Some( RecordId::TEXT( id.clone() ) ),
+            _ => None
+        } )
+        .collect::<Option<Vec<_>>>()?;
+
+    match key.is_composite() {
+        true => Some( RecordId::COMPOSITE( parts ) ),
+        false => parts.pop()
+    }
+}
+
+// stands in for the database default of a single uuid or serial key left out of the insert
+fn generated_key( rows: &[MemoryRow], key: PrimaryKey ) -> Option<( &'static str, MemoryValue )> {
+    match key.columns {
+        [ ( column, KeyType::UUID ) ] => Some( ( *column, MemoryValue::UUID( uuid::Uuid::new_v4() ) ) ),
+        [ ( column, KeyType::SERIAL ) ] => {
+            let last = rows.iter()
+                .filter_map( |row| match row.get( *column ) {
+                    Some( MemoryValue::INTEGER( id ) ) => Some( *id ),
+                    _ => None
+                } )
+                .max()
+                .unwrap_or( 0 );
+            Some( ( *column, MemoryValue::INTEGER( last + 1 ) ) )
+        },
+        _ => None
+    }
+}
+
+impl MemoryStorage {
+    fn select_rows( &self, query: &ReadQuery<'_> ) -> Vec<MemoryRow> {
+        let filters = query.filters.iter()
+            .map( |filter| ( filter.column, filter.op, MemoryValue::from( filter.value ) ) )
+            .collect::<Vec<_>>();
+
+        let granted = granted_ids( &self.tables.read().unwrap(), query.owner.as_ref() );
+        let mut rows = self.rows( query.table ).into_iter()
+            .filter( |row| matches_owner( row, query.owner.as_ref(), &granted ) )
+            .filter( |row| filters.iter().all( |( column, op, value )| matches_filter( row, column, *op, value ) ) )
+            .collect::<Vec<_>>();
+
+        rows.sort_by( |a, b| compare_rows( a, b, query.order_by ) );
+        if query.descending {
+            rows.reverse();
+        }
+
+        let offset = query.offset.unwrap_or( 0 ) as usize;
+        let limit = query.limit.map( |limit| limit as usize ).unwrap_or( usize::MAX );
+        rows.into_iter().skip( offset ).take( limit ).collect()
+    }
+
+    fn insert_row( &self, table: &'static str, key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Result<RecordId, StatusCode> {
+        let mut row = values.iter()
+            .map( |( column, value )| ( column.to_string(), MemoryValue::from( *value ) ) )
+            .collect::<MemoryRow>();
+
+        let mut tables = self.tables.write().unwrap();
+        let rows = tables.entry( table.to_string() ).or_default();
+
+        let id = match row_key( &row, key ) {
+            Some( id ) => id,
+            None => {
+                let ( column, value ) = generated_key( rows, key ).ok_or( StatusCode::INTERNAL_SERVER_ERROR )?;
+                row.insert( column.to_string(), value );
+                row_key( &row, key ).ok_or( StatusCode::INTERNAL_SERVER_ERROR )?
+            }
+        };
+
+        if rows.iter().any( |existing| row_key( existing, key ).as_ref() == Some( &id ) ) {
+            return Err( StatusCode::INTERNAL_SERVER_ERROR );
+        }
+        rows.push( row );
+
+        Ok( id )
+    }
+
+    // hands back the rows as they were before the update
+    fn update_rows( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], filters: &[Filter<'_>], owner: Option<&Owner> ) -> Vec<MemoryRow> {
+        let filters = filters.iter()
+            .map( |filter| ( filter.column, filter.op, MemoryValue::from( filter.value ) ) )
+            .collect::<Vec<_>>();
+        let mut tables = self.tables.write().unwrap();
+        let granted = granted_ids( &tables, owner );
+        let mut previous = vec![];
+
+        for row in tables.entry( table.to_string() ).or_default().iter_mut() {
+            let matches = filters.iter().all( |( column, op, value )| matches_filter( row, column, *op, value ) );
+            if row_key( row, key ).as_ref() == Some( id ) && matches && matches_owner( row, owner, &granted ) {
+                previous.push( row.clone() );
+                for ( column, value ) in values {
+                    row.insert( column.to_string(), MemoryValue::from( *value ) );
+                }
+            }
+        }
+
+        previous
+    }
+
+    fn delete_rows( &self, table: &'static str, key: PrimaryKey, ids: &[RecordId], owner: Option<&Owner> ) -> Vec<MemoryRow> {
+        let mut tables = self.tables.write().unwrap();
+        let granted = granted_ids( &tables, owner );
+        let rows = tables.entry( table.to_string() ).or_default();
+
+        let ( removed, kept ) = std::mem::take( rows ).into_iter()
+            .partition( |row| row_key( row, key ).map( |id| ids.contains( &id ) ).unwrap_or( false ) && matches_owner( row, owner, &granted ) );
+        *rows = kept;
+        removed
+    }
+
+    fn undo( &self, change: MemoryChange ) {
+        let mut tables = self.tables.write().unwrap();
+
+        match change {
+            MemoryChange::Inserted( table, key, id ) => {
+                tables.entry( table ).or_default().retain( |row| row_key( row, key ).as_ref() != Some( &id ) );
+            },
+            MemoryChange::Updated( table, key, previous ) => {
+                let rows = tables.entry( table ).or_default();
+                for before in previous {
+                    let id = row_key( &before, key );
+                    if let Some( row ) = rows.iter_mut().find( |row| row_key( row, key ) == id ) {
+                        *row = before;
+                    }
+                }
+            },
+            MemoryChange::Deleted( table, removed ) => {
+                tables.entry( table ).or_default().extend( removed );
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    type Row = MemoryRow;
+    type Transaction = MemoryTransaction;
+
+    async fn select( &self, query: &ReadQuery<'_> ) -> Result<Vec<MemoryRow>, StatusCode> {
+        Ok( self.select_rows( query ) )
+    }
+
+    async fn insert( &self, table: &'static str, key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Result<RecordId, StatusCode> {
+        self.insert_row( table, key, values )
+    }
+
+    async fn update_where( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], filters: &[Filter<'_>], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        Ok( self.update_rows( table, key, id, values, filters, owner ).len() as u64 )
+    }
+
+    async fn delete( &self, table: &'static str, key: PrimaryKey, ids: &[RecordId], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        Ok( self.delete_rows( table, key, ids, owner ).len() as u64 )
+    }
+
+    async fn transaction( &self ) -> Result<MemoryTransaction, StatusCode> {
+        let log = MemoryLog { storage: self.clone(), changes: vec![], committed: false };
+        Ok( MemoryTransaction { storage: self.clone(), log: Arc::new( Mutex::new( log ) ), owner: true } )
+    }
+
+    fn as_any( &self ) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+enum MemoryChange {
+    Inserted( String, PrimaryKey, RecordId ),
+    Updated( String, PrimaryKey, Vec<MemoryRow> ),
+    Deleted( String, Vec<MemoryRow> )
+}
+
+// writes go straight to the tables and are undone in reverse if the transaction is dropped uncommitted,
+// there is no isolation from other requests in the meantime
+struct MemoryLog {
+    storage: MemoryStorage,
+    changes: Vec<MemoryChange>,
+    committed: bool
+}
+
+impl MemoryLog {
+    fn rollback( &mut self ) {
+        while let Some( change ) = self.changes.pop() {
+            self.storage.undo( change );
+        }
+    }
+}
+
+impl Drop for MemoryLog {
+    fn drop( &mut self ) {
+        if !self.committed {
+            self.rollback();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MemoryTransaction {
+    storage: MemoryStorage,
+    log: Arc<Mutex<MemoryLog>>,
+    owner: bool
+}
+
+impl MemoryTransaction {
+    fn record( &self, change: MemoryChange ) {
+        self.log.lock().unwrap().changes.push( change );
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryTransaction {
+    type Row = MemoryRow;
+    type Transaction = MemoryTransaction;
+
+    async fn select( &self, query: &ReadQuery<'_> ) -> Result<Vec<MemoryRow>, StatusCode> {
+        Ok( self.storage.select_rows( query ) )
+    }
+
+    async fn insert( &self, table: &'static str, key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Result<RecordId, StatusCode> {
+        let id = self.storage.insert_row( table, key, values )?;
+        self.record( MemoryChange::Inserted( table.to_string(), key, id.clone() ) );
+        Ok( id )
+    }
+
+    async fn update_where( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], filters: &[Filter<'_>], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        let previous = self.storage.update_rows( table, key, id, values, filters, owner );
+        let affected = previous.len() as u64;
+        self.record( MemoryChange::Updated( table.to_string(), key, previous ) );
+        Ok( affected )
+    }
+
+    async fn delete( &self, table: &'static str, key: PrimaryKey, ids: &[RecordId], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        let removed = self.storage.delete_rows( table, key, ids, owner );
+        let affected = removed.len() as u64;
+        self.record( MemoryChange::Deleted( table.to_string(), removed ) );
+        Ok( affected )
+    }
+
+    // a nested transaction joins the outer one, which stays the only one that commits
+    async fn transaction( &self ) -> Result<MemoryTransaction, StatusCode> {
+        Ok( MemoryTransaction { owner: false, ..self.clone() } )
+    }
+
+    async fn commit( &self ) -> Result<(), StatusCode> {
+        if self.owner {
+            self.log.lock().unwrap().committed = true;
+        }
+        Ok(())
+    }
+
+    async fn rollback( &self ) -> Result<(), StatusCode> {
+        if self.owner {
+            self.log.lock().unwrap().rollback();
+        }
+        Ok(())
+    }
+
+    fn as_any( &self ) -> &dyn Any {
+        self
+    }
+}
+
+impl<T: DeserializeOwned> FromStorageRow<MemoryStorage> for T {
+    fn from_storage_row( row: MemoryRow ) -> Result<T, StatusCode> {
+        let object = row.iter()
+            .map( |( key, value )| ( key.clone(), value.to_json() ) )
+            .collect::<Map<String, Value>>();
+        serde_json::from_value( Value::Object( object ) ).map_err( crate::storage::database_error )
+    }
+}
+
+impl<T: DeserializeOwned> FromStorageRow<MemoryTransaction> for T {
+    fn from_storage_row( row: MemoryRow ) -> Result<T, StatusCode> {
+        <T as FromStorageRow<MemoryStorage>>::from_storage_row( row )
+    }
+}
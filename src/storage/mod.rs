@@ -0,0 +1,232 @@
+use std::any::Any;
+use std::sync::Arc;
+use axum::async_trait;
+use axum::http::StatusCode;
+use crate::{ FieldValue, KeyValue, PrimaryKey, RecordId };
+
+#[macro_use]
+pub mod sql;
+mod postgres;
+mod sqlite;
+mod mysql;
+pub mod memory;
+pub mod rls;
+
+pub use sql::Dialect;
+pub use memory::MemoryStorage;
+pub use rls::RlsStorage;
+pub use sql::{ SqlTransaction, SqlConnection };
+pub use memory::MemoryTransaction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    EQ,
+    GT,
+    LT,
+    GE,
+    LE
+}
+
+impl FilterOp {
+    pub fn sql( &self ) -> &'static str {
+        match self {
+            FilterOp::EQ => "=",
+            FilterOp::GT => ">",
+            FilterOp::LT => "<",
+            FilterOp::GE => ">=",
+            FilterOp::LE => "<="
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Filter<'a> {
+    pub column: &'static str,
+    pub op: FilterOp,
+    pub value: FieldValue<'a>
+}
+
+// rows granted to any of the grantees at `level` or above match alongside the owner's own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shared {
+    pub resource: String,
+    pub key: &'static str,
+    pub grantees: Vec<String>,
+    pub level: i32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub column: &'static str,
+    pub user_id: String,
+    pub shared: Option<Shared>
+}
+
+// who a request runs for, for storages that enforce row isolation in the database itself
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub user_id: String,
+    pub tenant_id: Option<String>,
+    pub tenant_admin: bool,
+    pub grantees: Vec<String>
+}
+
+pub struct ReadQuery<'a> {
+    pub table: &'static str,
+    pub filters: Vec<Filter<'a>>,
+    pub owner: Option<Owner>,
+    pub order_by: &'a str,
+    pub descending: bool,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>
+}
+
+pub fn is_identifier( name: &str ) -> bool {
+    !name.is_empty() && name.chars().all( |c| c.is_ascii_alphanumeric() || c == '_' )
+}
+
+impl<'a> ReadQuery<'a> {
+    pub fn parse<QP: KeyValue>( table: &'static str, filters: &'a QP, owner: Option<Owner> ) -> Result<ReadQuery<'a>, StatusCode> {
+        ReadQuery::parse_ordered( table, filters, owner, "id" )
+    }
+
+    pub fn parse_ordered<QP: KeyValue>( table: &'static str, filters: &'a QP, owner: Option<Owner>, order_by: &'a str ) -> Result<ReadQuery<'a>, StatusCode> {
+        let mut query = ReadQuery { table, filters: vec![], owner, order_by, descending: true, limit: None, offset: None };
+
+        for ( key, value ) in filters.key_value_pairs() {
+            match ( key, value ) {
+                ( "order_by", FieldValue::STRING( column ) ) => {
+                    if !is_identifier( column ) {
+                        return Err( StatusCode::BAD_REQUEST );
+                    }
+                    query.order_by = column;
+                    continue;
+                },
+                ( "order_dir", FieldValue::STRING( direction ) ) => {
+                    query.descending = direction.as_str() != "asc";
+                    continue;
+                },
+                ( "limit", FieldValue::INTEGER( limit ) ) => {
+                    query.limit = Some( (*limit).max( 0 ) );
+                    continue;
+                },
+                ( "offset", FieldValue::INTEGER( offset ) ) => {
+                    query.offset = Some( (*offset).max( 0 ) );
+                    continue;
+                },
+                _ => {}
+            }
+
+            let ( column, op ) = match key.len() > 3 {
+                true => match key.split_at( key.len() - 3 ) {
+                    ( column, "_gt" ) => ( column, FilterOp::GT ),
+                    ( column, "_lt" ) => ( column, FilterOp::LT ),
+                    ( column, "_ge" ) => ( column, FilterOp::GE ),
+                    ( column, "_le" ) => ( column, FilterOp::LE ),
+                    _ => ( key, FilterOp::EQ )
+                },
+                false => ( key, FilterOp::EQ )
+            };
+
+            query.filters.push( Filter { column, op, value } );
+        }
+
+        Ok( query )
+    }
+}
+
+pub fn database_error<E: std::fmt::Debug>( error: E ) -> StatusCode {
+    println!("{:?}", error);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[async_trait]
+pub trait Storage: Clone + Send + Sync + 'static {
+    type Row: Send + 'static;
+    // writes through a transaction land together on commit and are rolled back if it is dropped first
+    type Transaction: Storage<Row = Self::Row, Transaction = Self::Transaction>;
+
+    async fn select( &self, query: &ReadQuery<'_> ) -> Result<Vec<Self::Row>, StatusCode>;
+    async fn insert( &self, table: &'static str, key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Result<RecordId, StatusCode>;
+    // only touches the row while it still matches `filters`, so a write decided on an earlier read loses to a concurrent one
+    async fn update_where( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], filters: &[Filter<'_>], owner: Option<&Owner> ) -> Result<u64, StatusCode>;
+    async fn delete( &self, table: &'static str, key: PrimaryKey, ids: &[RecordId], owner: Option<&Owner> ) -> Result<u64, StatusCode>;
+
+    async fn update( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        self.update_where( table, key, id, values, &[], owner ).await
+    }
+
+    async fn transaction( &self ) -> Result<Self::Transaction, StatusCode>;
+
+    async fn commit( &self ) -> Result<(), StatusCode> {
+        Ok(())
+    }
+
+    async fn rollback( &self ) -> Result<(), StatusCode> {
+        Ok(())
+    }
+
+    fn with_session( &self, _session: Session ) -> Self {
+        self.clone()
+    }
+
+    fn as_any( &self ) -> &dyn Any;
+}
+
+// a request's transaction, begun the first time something asks for it so requests that never write hold no connection
+pub(crate) struct LazyTransaction<S: Storage> {
+    storage: S,
+    transaction: tokio::sync::Mutex<Option<S::Transaction>>
+}
+
+impl<S: Storage> LazyTransaction<S> {
+    // handles given out join the transaction, only the request commits it
+    pub(crate) async fn get( &self ) -> Result<S::Transaction, StatusCode> {
+        let mut begun = self.transaction.lock().await;
+        let transaction = match &*begun {
+            Some( transaction ) => transaction.clone(),
+            None => begun.insert( self.storage.transaction().await? ).clone()
+        };
+        drop( begun );
+        transaction.transaction().await
+    }
+
+    pub(crate) async fn commit( &self ) -> Result<(), StatusCode> {
+        let begun = self.transaction.lock().await.clone();
+        match begun {
+            Some( transaction ) => transaction.commit().await,
+            None => Ok(())
+        }
+    }
+
+    pub(crate) async fn rollback( &self ) -> Result<(), StatusCode> {
+        let begun = self.transaction.lock().await.clone();
+        match begun {
+            Some( transaction ) => transaction.rollback().await,
+            None => Ok(())
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestTransaction( Arc<dyn Any + Send + Sync> );
+
+impl RequestTransaction {
+    pub(crate) fn new<S: Storage>( storage: S ) -> RequestTransaction {
+        RequestTransaction( Arc::new( LazyTransaction { storage, transaction: tokio::sync::Mutex::new( None ) } ) )
+    }
+
+    pub(crate) fn lazy<S: Storage>( &self ) -> Option<&LazyTransaction<S>> {
+        self.0.downcast_ref::<LazyTransaction<S>>()
+    }
+}
+
+impl std::fmt::Debug for RequestTransaction {
+    fn fmt( &self, f: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result {
+        f.write_str( "RequestTransaction" )
+    }
+}
+
+pub trait FromStorageRow<S: Storage>: Sized {
+    fn from_storage_row( row: S::Row ) -> Result<Self, StatusCode>;
+}
@@ -0,0 +1,4 @@
+use sqlx::mysql::{ MySqlPool, MySqlRow };
+use crate::storage::Dialect;
+
+impl_sql_storage!( MySqlPool, MySqlRow, sqlx::MySql, Dialect::MYSQL );
@@ -0,0 +1,4 @@
+use sqlx::postgres::{ PgPool, PgRow };
+use crate::storage::Dialect;
+
+impl_sql_storage!( PgPool, PgRow, sqlx::Postgres, Dialect::POSTGRES );
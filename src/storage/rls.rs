@@ -0,0 +1,170 @@
+use std::any::Any;
+use std::sync::Arc;
+use axum::async_trait;
+use axum::http::StatusCode;
+use sqlx::Postgres;
+use sqlx::postgres::{ PgPool, PgRow };
+use crate::{ AccessPermission, CrudConfig, EndpointVerb, FieldValue, ObjectPermission, PrimaryKey, RecordId, resource_actions };
+use crate::impersonation::IMPERSONATIONS_TABLE;
+use crate::sharing::{ GrantLevel, GRANTS_TABLE };
+use crate::storage::{ Storage, FromStorageRow, ReadQuery, Filter, Owner, Session, SqlTransaction, is_identifier, database_error };
+
+const USER_SETTING: &str = "current_setting( 'app.user_id', true )";
+const TENANT_SETTING: &str = "current_setting( 'app.tenant_id', true )";
+
+// postgres storage that also has the database enforce isolation, so a custom hook that forgets the owner filter still only sees the caller's rows
+#[derive(Debug, Clone)]
+pub struct RlsStorage {
+    pool: PgPool,
+    role: String,
+    session: Option<Arc<Session>>
+}
+
+impl RlsStorage {
+    pub fn new( pool: PgPool, role: &str ) -> anyhow::Result<RlsStorage> {
+        if !is_identifier( role ) {
+            anyhow::bail!( "Invalid role name: {}", role );
+        }
+
+        Ok( RlsStorage { pool, role: role.to_string(), session: None } )
+    }
+
+    // the pool runs as the connecting role and bypasses every policy, queries bound by the session go through `transaction()`
+    pub fn pool( &self ) -> &PgPool {
+        &self.pool
+    }
+
+    pub async fn install_policies<T: CrudConfig>( &self ) -> anyhow::Result<()> {
+        sqlx::Executor::execute( &self.pool, rls_policies::<T>( &self.role ).as_str() ).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for RlsStorage {
+    type Row = PgRow;
+    type Transaction = SqlTransaction<Postgres>;
+
+    async fn select( &self, query: &ReadQuery<'_> ) -> Result<Vec<PgRow>, StatusCode> {
+        let transaction = self.transaction().await?;
+        let rows = transaction.select( query ).await?;
+        transaction.commit().await?;
+        Ok( rows )
+    }
+
+    async fn insert( &self, table: &'static str, key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Result<RecordId, StatusCode> {
+        let transaction = self.transaction().await?;
+        let id = transaction.insert( table, key, values ).await?;
+        transaction.commit().await?;
+        Ok( id )
+    }
+
+    async fn update_where( &self, table: &'static str, key: PrimaryKey, id: &RecordId, values: &[(&'static str, FieldValue<'_>)], filters: &[Filter<'_>], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        let transaction = self.transaction().await?;
+        let affected = transaction.update_where( table, key, id, values, filters, owner ).await?;
+        transaction.commit().await?;
+        Ok( affected )
+    }
+
+    async fn delete( &self, table: &'static str, key: PrimaryKey, ids: &[RecordId], owner: Option<&Owner> ) -> Result<u64, StatusCode> {
+        let transaction = self.transaction().await?;
+        let affected = transaction.delete( table, key, ids, owner ).await?;
+        transaction.commit().await?;
+        Ok( affected )
+    }
+
+    // only admin requests that impersonate no one come without a session, they keep the pool's own role and see every row
+    async fn transaction( &self ) -> Result<SqlTransaction<Postgres>, StatusCode> {
+        let mut transaction = self.pool.begin().await.map_err( database_error )?;
+
+        if let Some( session ) = &self.session {
+            sqlx::query( &format!( "SET LOCAL ROLE {}", self.role ) )
+                .execute( &mut *transaction ).await
+                .map_err( database_error )?;
+
+            let grantees = serde_json::to_string( &session.grantees ).map_err( database_error )?;
+            sqlx::query( "SELECT set_config( 'app.user_id', $1, true ), set_config( 'app.tenant_id', $2, true ), set_config( 'app.tenant_admin', $3, true ), set_config( 'app.grantees', $4, true )" )
+                .bind( &session.user_id )
+                .bind( session.tenant_id.as_deref().unwrap_or_default() )
+                .bind( session.tenant_admin.to_string() )
+                .bind( grantees )
+                .execute( &mut *transaction ).await
+                .map_err( database_error )?;
+        }
+
+        Ok( SqlTransaction::new( transaction ) )
+    }
+
+    fn with_session( &self, session: Session ) -> Self {
+        RlsStorage { session: Some( Arc::new( session ) ), ..self.clone() }
+    }
+
+    fn as_any( &self ) -> &dyn Any {
+        self
+    }
+}
+
+impl<T> FromStorageRow<RlsStorage> for T where T: for<'r> sqlx::FromRow<'r, PgRow> {
+    fn from_storage_row( row: PgRow ) -> Result<T, StatusCode> {
+        T::from_row( &row ).map_err( database_error )
+    }
+}
+
+// mirrors the filters the generated queries add for each object permission
+fn policy_condition<T: CrudConfig>( verb: EndpointVerb ) -> String {
+    let owned = format!( "{} = {}", T::owner_column(), USER_SETTING );
+
+    match T::get_object_permissions( &verb ) {
+        ObjectPermission::ALL if matches!( T::get_access_permissions( &verb ), AccessPermission::ANY | AccessPermission::OPTIONAL ) => "true".to_string(),
+        ObjectPermission::ALL => format!( "{} <> ''", USER_SETTING ),
+        ObjectPermission::OWNER => owned,
+        ObjectPermission::TENANT => format!( "{} = {}", T::tenant_column(), TENANT_SETTING ),
+        ObjectPermission::TENANTADMIN => format!( "{} = {} AND current_setting( 'app.tenant_admin', true ) = 'true'", T::tenant_column(), TENANT_SETTING ),
+        ObjectPermission::SHARED if verb == EndpointVerb::POST => owned,
+        ObjectPermission::SHARED => format!(
+            "( {} OR {} IN ( SELECT record_id FROM {} WHERE resource = '{}' AND level >= {} AND grantee IN ( SELECT jsonb_array_elements_text( current_setting( 'app.grantees', true )::jsonb ) ) ) )",
+            owned, T::primary_key().first_column(), GRANTS_TABLE, T::endpoint_name().replace( '\'', "''" ), GrantLevel::for_verb( verb ).rank()
+        )
+    }
+}
+
+pub fn rls_policies<T: CrudConfig>( role: &str ) -> String {
+    let table = T::table_name();
+    let mut statements = vec![
+        format!( "ALTER TABLE {} ENABLE ROW LEVEL SECURITY;", table ),
+        format!( "GRANT SELECT, INSERT, UPDATE, DELETE ON {} TO {};", table, role )
+    ];
+
+    // shared policies look grants up while running as the role
+    let verbs = [ EndpointVerb::GET, EndpointVerb::POST, EndpointVerb::PUT, EndpointVerb::DELETE ];
+    if verbs.iter().any( |verb| T::include_endpoint( verb ) && T::get_object_permissions( verb ) == ObjectPermission::SHARED ) {
+        statements.push( format!( "GRANT SELECT ON {} TO {};", GRANTS_TABLE, role ) );
+    }
+
+    // impersonated requests run as the role and write their audit row in the same transaction
+    let admin_access = verbs.iter().any( |verb| T::include_endpoint( verb ) && T::get_access_permissions( verb ) == AccessPermission::ADMIN ) ||
+        resource_actions::<T>().iter().any( |action| action.access == AccessPermission::ADMIN );
+    if admin_access {
+        statements.push( format!( "DO $$ BEGIN IF to_regclass( '{}' ) IS NOT NULL THEN GRANT INSERT ON {} TO {}; END IF; END $$;", IMPERSONATIONS_TABLE, IMPERSONATIONS_TABLE, role ) );
+    }
+
+    for verb in verbs {
+        let policy = format!( "janus_{}", verb.name().to_lowercase() );
+        statements.push( format!( "DROP POLICY IF EXISTS {} ON {};", policy, table ) );
+
+        // verbs without an endpoint get no policy, which leaves them denied for the role
+        if !T::include_endpoint( &verb ) {
+            continue;
+        }
+
+        let condition = policy_condition::<T>( verb );
+        statements.push( match verb {
+            EndpointVerb::GET => format!( "CREATE POLICY {} ON {} FOR SELECT TO {} USING ( {} );", policy, table, role, condition ),
+            EndpointVerb::POST => format!( "CREATE POLICY {} ON {} FOR INSERT TO {} WITH CHECK ( {} );", policy, table, role, condition ),
+            EndpointVerb::PUT => format!( "CREATE POLICY {} ON {} FOR UPDATE TO {} USING ( {} ) WITH CHECK ( {} );", policy, table, role, condition, condition ),
+            EndpointVerb::DELETE => format!( "CREATE POLICY {} ON {} FOR DELETE TO {} USING ( {} );", policy, table, role, condition )
+        } );
+    }
+
+    statements.join( "\n" )
+}
@@ -0,0 +1,454 @@
+use std::ops::{ Deref, DerefMut };
+use std::sync::Arc;
+use axum::http::StatusCode;
+use tokio::sync::{ Mutex, MutexGuard };
+use crate::{ FieldValue, KeyType, PrimaryKey, RecordId };
+use crate::storage::{ ReadQuery, Filter, Owner, database_error };
+use crate::sharing::GRANTS_TABLE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    POSTGRES,
+    SQLITE,
+    MYSQL
+}
+
+impl Dialect {
+    pub fn placeholder( &self, index: usize ) -> String {
+        match self {
+            Dialect::POSTGRES => format!( "${}", index ),
+            Dialect::SQLITE | Dialect::MYSQL => "?".to_string()
+        }
+    }
+
+    pub fn supports_returning( &self ) -> bool {
+        !matches!( self, Dialect::MYSQL )
+    }
+
+    // sqlite and mysql only accept OFFSET after a LIMIT
+    fn unbounded_limit( &self ) -> Option<&'static str> {
+        match self {
+            Dialect::POSTGRES => None,
+            Dialect::SQLITE => Some( "-1" ),
+            Dialect::MYSQL => Some( "18446744073709551615" )
+        }
+    }
+}
+
+pub struct Statement<'a> {
+    pub sql: String,
+    pub bindings: Vec<FieldValue<'a>>
+}
+
+impl<'a> Statement<'a> {
+    fn new( sql: String ) -> Statement<'a> {
+        Statement { sql, bindings: vec![] }
+    }
+
+    fn bind( &mut self, dialect: Dialect, value: FieldValue<'a> ) -> String {
+        self.bindings.push( value );
+        dialect.placeholder( self.bindings.len() )
+    }
+
+    fn owner_condition( &mut self, dialect: Dialect, owner: &'a Owner ) -> String {
+        let placeholder = self.bind( dialect, FieldValue::STRING( &owner.user_id ) );
+        let condition = format!( "{} = {}", owner.column, placeholder );
+
+        match &owner.shared {
+            Some( shared ) if !shared.grantees.is_empty() => {
+                let resource = self.bind( dialect, FieldValue::STRING( &shared.resource ) );
+                let level = self.bind( dialect, FieldValue::INTEGER( &shared.level ) );
+                let grantees = shared.grantees.iter().map( |grantee| self.bind( dialect, FieldValue::STRING( grantee ) ) ).collect::<Vec<_>>();
+                format!( "( {} OR {} IN ( SELECT record_id FROM {} WHERE resource = {} AND level >= {} AND grantee IN ( {} ) ) )", condition, shared.key, GRANTS_TABLE, resource, level, grantees.join( ", " ) )
+            },
+            _ => condition
+        }
+    }
+
+    fn key_condition( &mut self, dialect: Dialect, key: PrimaryKey, id: &'a RecordId ) -> Result<String, StatusCode> {
+        let conditions = id.key_values( key ).ok_or( StatusCode::BAD_REQUEST )?
+            .into_iter()
+            .map( |( column, value )| format!( "{} = {}", column, self.bind( dialect, value ) ) )
+            .collect::<Vec<_>>();
+
+        Ok( conditions.join( " AND " ) )
+    }
+
+    fn bind_owner( &mut self, dialect: Dialect, owner: Option<&'a Owner> ) {
+        if let Some( owner ) = owner {
+            let condition = self.owner_condition( dialect, owner );
+            self.sql = format!( "{} AND {}", self.sql, condition );
+        }
+    }
+}
+
+pub fn select<'a>( dialect: Dialect, query: &'a ReadQuery<'_> ) -> Statement<'a> {
+    let mut statement = Statement::new( format!( "SELECT * FROM {}", query.table ) );
+    let mut conditions = vec![];
+
+    if let Some( owner ) = &query.owner {
+        conditions.push( statement.owner_condition( dialect, owner ) );
+    }
+
+    for filter in &query.filters {
+        let placeholder = statement.bind( dialect, filter.value );
+        conditions.push( format!( "{} {} {}", filter.column, filter.op.sql(), placeholder ) );
+    }
+
+    if !conditions.is_empty() {
+        statement.sql = format!( "{} WHERE {}", statement.sql, conditions.join( " AND " ) );
+    }
+
+    statement.sql = format!( "{} ORDER BY {} {}", statement.sql, query.order_by, match query.descending {
+        true => "DESC",
+        false => "ASC"
+    } );
+
+    match ( query.limit, query.offset, dialect.unbounded_limit() ) {
+        ( Some( limit ), _, _ ) => { statement.sql = format!( "{} LIMIT {}", statement.sql, limit ); },
+        ( None, Some(_), Some( unbounded ) ) => { statement.sql = format!( "{} LIMIT {}", statement.sql, unbounded ); },
+        _ => {}
+    }
+    if let Some( offset ) = query.offset {
+        statement.sql = format!( "{} OFFSET {}", statement.sql, offset );
+    }
+
+    statement
+}
+
+pub fn insert<'a>( dialect: Dialect, table: &str, key: PrimaryKey, values: &[(&'static str, FieldValue<'a>)] ) -> Statement<'a> {
+    let mut statement = Statement::new( String::new() );
+    let columns = values.iter().map( |( key, _ )| *key ).collect::<Vec<_>>();
+    let placeholders = values.iter().map( |( _, value )| statement.bind( dialect, *value ) ).collect::<Vec<_>>();
+
+    statement.sql = format!( "INSERT INTO {} ( {} ) VALUES ( {} )", table, columns.join( ", " ), placeholders.join( ", " ) );
+    if dialect.supports_returning() {
+        let returning = key.columns.iter().map( |( column, _ )| *column ).collect::<Vec<_>>();
+        statement.sql = format!( "{} RETURNING {}", statement.sql, returning.join( ", " ) );
+    }
+
+    statement
+}
+
+// filters narrow the update past the key, so it can be made conditional on what a row still holds
+pub fn update<'a>( dialect: Dialect, table: &str, key: PrimaryKey, id: &'a RecordId, values: &[(&'static str, FieldValue<'a>)], filters: &[Filter<'a>], owner: Option<&'a Owner> ) -> Result<Statement<'a>, StatusCode> {
+    let mut statement = Statement::new( String::new() );
+    let assignments = values.iter()
+        .map( |( key, value )| format!( "{} = {}", key, statement.bind( dialect, *value ) ) )
+        .collect::<Vec<_>>();
+    let mut conditions = vec![ statement.key_condition( dialect, key, id )? ];
+    for filter in filters {
+        let placeholder = statement.bind( dialect, filter.value );
+        conditions.push( format!( "{} {} {}", filter.column, filter.op.sql(), placeholder ) );
+    }
+
+    statement.sql = format!( "UPDATE {} SET {} WHERE {}", table, assignments.join( ", " ), conditions.join( " AND " ) );
+    statement.bind_owner( dialect, owner );
+    Ok( statement )
+}
+
+// single keys keep the IN list, composite keys match each id's columns together
+pub fn delete<'a>( dialect: Dialect, table: &str, key: PrimaryKey, ids: &'a [RecordId], owner: Option<&'a Owner> ) -> Result<Statement<'a>, StatusCode> {
+    let mut statement = Statement::new( String::new() );
+
+    statement.sql = match key.is_composite() {
+        false => {
+            let placeholders = ids.iter()
+                .map( |id| id.key_values( key ).and_then( |mut values| values.pop() ).map( |( _, value )| statement.bind( dialect, value ) ) )
+                .collect::<Option<Vec<_>>>()
+                .ok_or( StatusCode::BAD_REQUEST )?;
+            format!( "DELETE FROM {} WHERE {} IN ( {} )", table, key.first_column(), placeholders.join( ", " ) )
+        },
+        true => {
+            let conditions = ids.iter()
+                .map( |id| statement.key_condition( dialect, key, id ).map( |condition| format!( "( {} )", condition ) ) )
+                .collect::<Result<Vec<_>, StatusCode>>()?;
+            format!( "DELETE FROM {} WHERE ( {} )", table, conditions.join( " OR " ) )
+        }
+    };
+
+    statement.bind_owner( dialect, owner );
+    Ok( statement )
+}
+
+macro_rules! bind_values {
+    ( $query:expr, $bindings:expr ) => {{
+        let mut query = $query;
+        for binding in $bindings {
+            query = match binding {
+                $crate::FieldValue::UUID( value ) => query.bind( value ),
+                $crate::FieldValue::STRING( value ) => query.bind( value ),
+                $crate::FieldValue::INTEGER( value ) => query.bind( value ),
+                $crate::FieldValue::BIGINT( value ) => query.bind( value ),
+                $crate::FieldValue::DATE( value ) => query.bind( value ),
+                $crate::FieldValue::BOOLEAN( value ) => query.bind( value ),
+                $crate::FieldValue::FLOAT( value ) => query.bind( value )
+            };
+        }
+        query
+    }};
+}
+
+// one database transaction shared by every handle a request passes around, only the handle that began it commits
+pub struct SqlTransaction<DB: sqlx::Database> {
+    pub(crate) transaction: Arc<Mutex<Option<sqlx::Transaction<'static, DB>>>>,
+    owner: bool
+}
+
+impl<DB: sqlx::Database> Clone for SqlTransaction<DB> {
+    fn clone( &self ) -> Self {
+        SqlTransaction { transaction: self.transaction.clone(), owner: self.owner }
+    }
+}
+
+impl<DB: sqlx::Database> SqlTransaction<DB> {
+    pub fn new( transaction: sqlx::Transaction<'static, DB> ) -> SqlTransaction<DB> {
+        SqlTransaction { transaction: Arc::new( Mutex::new( Some( transaction ) ) ), owner: true }
+    }
+
+    // raw sqlx runs on the same connection through `&mut *transaction.connection().await?`
+    pub async fn connection( &self ) -> Result<SqlConnection<'_, DB>, StatusCode> {
+        let transaction = self.transaction.lock().await;
+        match transaction.is_some() {
+            true => Ok( SqlConnection( transaction ) ),
+            false => Err( StatusCode::INTERNAL_SERVER_ERROR )
+        }
+    }
+
+    pub(crate) fn joined( &self ) -> SqlTransaction<DB> {
+        SqlTransaction { transaction: self.transaction.clone(), owner: false }
+    }
+
+    pub(crate) async fn finish( &self ) -> Result<(), StatusCode> {
+        if !self.owner {
+            return Ok(());
+        }
+
+        match self.transaction.lock().await.take() {
+            Some( transaction ) => transaction.commit().await.map_err( database_error ),
+            None => Err( StatusCode::INTERNAL_SERVER_ERROR )
+        }
+    }
+
+    pub(crate) async fn abort( &self ) -> Result<(), StatusCode> {
+        if !self.owner {
+            return Ok(());
+        }
+
+        match self.transaction.lock().await.take() {
+            Some( transaction ) => transaction.rollback().await.map_err( database_error ),
+            None => Ok(())
+        }
+    }
+}
+
+// the transaction's connection, locked for as long as it is held
+pub struct SqlConnection<'a, DB: sqlx::Database>( MutexGuard<'a, Option<sqlx::Transaction<'static, DB>>> );
+
+impl<DB: sqlx::Database> Deref for SqlConnection<'_, DB> {
+    type Target = DB::Connection;
+
+    fn deref( &self ) -> &DB::Connection {
+        self.0.as_deref().expect( "an open transaction is checked before handing out its connection" )
+    }
+}
+
+impl<DB: sqlx::Database> DerefMut for SqlConnection<'_, DB> {
+    fn deref_mut( &mut self ) -> &mut DB::Connection {
+        self.0.as_deref_mut().expect( "an open transaction is checked before handing out its connection" )
+    }
+}
+
+macro_rules! impl_sql_storage {
+    ( $pool:ty, $row:ty, $db:ty, $dialect:expr ) => {
+        #[axum::async_trait]
+        impl $crate::storage::Storage for $pool {
+            type Row = $row;
+            type Transaction = $crate::storage::sql::SqlTransaction<$db>;
+
+            async fn select( &self, query: &$crate::storage::ReadQuery<'_> ) -> Result<Vec<$row>, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::select( $dialect, query );
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .fetch_all( self ).await
+                    .map_err( $crate::storage::database_error )
+            }
+
+            async fn insert( &self, table: &'static str, key: $crate::PrimaryKey, values: &[(&'static str, $crate::FieldValue<'_>)] ) -> Result<$crate::RecordId, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::insert( $dialect, table, key, values );
+                let query = bind_values!( sqlx::query( &statement.sql ), statement.bindings );
+
+                if !$dialect.supports_returning() {
+                    let mut connection = self.acquire().await.map_err( $crate::storage::database_error )?;
+                    query.execute( &mut *connection ).await.map_err( $crate::storage::database_error )?;
+                    if let Some( id ) = $crate::storage::sql::inserted_id( key, values ) {
+                        return Ok( id );
+                    }
+
+                    // a serial key the database generated is only known to the connection that inserted it
+                    let row = sqlx::query( "SELECT CAST( LAST_INSERT_ID() AS SIGNED )" )
+                        .fetch_one( &mut *connection ).await
+                        .map_err( $crate::storage::database_error )?;
+                    return $crate::storage::sql::returned_id( &row, key );
+                }
+
+                let row = query.fetch_one( self ).await.map_err( $crate::storage::database_error )?;
+                $crate::storage::sql::returned_id( &row, key )
+            }
+
+            async fn update_where( &self, table: &'static str, key: $crate::PrimaryKey, id: &$crate::RecordId, values: &[(&'static str, $crate::FieldValue<'_>)], filters: &[$crate::storage::Filter<'_>], owner: Option<&$crate::storage::Owner> )
+                -> Result<u64, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::update( $dialect, table, key, id, values, filters, owner )?;
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .execute( self ).await
+                    .map( |result| result.rows_affected() )
+                    .map_err( $crate::storage::database_error )
+            }
+
+            async fn delete( &self, table: &'static str, key: $crate::PrimaryKey, ids: &[$crate::RecordId], owner: Option<&$crate::storage::Owner> ) -> Result<u64, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::delete( $dialect, table, key, ids, owner )?;
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .execute( self ).await
+                    .map( |result| result.rows_affected() )
+                    .map_err( $crate::storage::database_error )
+            }
+
+            async fn transaction( &self ) -> Result<Self::Transaction, axum::http::StatusCode> {
+                let transaction = sqlx::Pool::begin( self ).await.map_err( $crate::storage::database_error )?;
+                Ok( $crate::storage::sql::SqlTransaction::new( transaction ) )
+            }
+
+            fn as_any( &self ) -> &dyn std::any::Any {
+                self
+            }
+        }
+
+        impl<T> $crate::storage::FromStorageRow<$pool> for T where T: for<'r> sqlx::FromRow<'r, $row> {
+            fn from_storage_row( row: $row ) -> Result<T, axum::http::StatusCode> {
+                T::from_row( &row ).map_err( $crate::storage::database_error )
+            }
+        }
+
+        #[axum::async_trait]
+        impl $crate::storage::Storage for $crate::storage::sql::SqlTransaction<$db> {
+            type Row = $row;
+            type Transaction = Self;
+
+            async fn select( &self, query: &$crate::storage::ReadQuery<'_> ) -> Result<Vec<$row>, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::select( $dialect, query );
+                let mut transaction = self.transaction.lock().await;
+                let connection: &mut <$db as sqlx::Database>::Connection = transaction.as_mut().ok_or( axum::http::StatusCode::INTERNAL_SERVER_ERROR )?;
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .fetch_all( &mut *connection ).await
+                    .map_err( $crate::storage::database_error )
+            }
+
+            async fn insert( &self, table: &'static str, key: $crate::PrimaryKey, values: &[(&'static str, $crate::FieldValue<'_>)] ) -> Result<$crate::RecordId, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::insert( $dialect, table, key, values );
+                let query = bind_values!( sqlx::query( &statement.sql ), statement.bindings );
+                let mut transaction = self.transaction.lock().await;
+                let connection: &mut <$db as sqlx::Database>::Connection = transaction.as_mut().ok_or( axum::http::StatusCode::INTERNAL_SERVER_ERROR )?;
+
+                if !$dialect.supports_returning() {
+                    query.execute( &mut *connection ).await.map_err( $crate::storage::database_error )?;
+                    if let Some( id ) = $crate::storage::sql::inserted_id( key, values ) {
+                        return Ok( id );
+                    }
+
+                    let row = sqlx::query( "SELECT CAST( LAST_INSERT_ID() AS SIGNED )" )
+                        .fetch_one( &mut *connection ).await
+                        .map_err( $crate::storage::database_error )?;
+                    return $crate::storage::sql::returned_id( &row, key );
+                }
+
+                let row = query.fetch_one( &mut *connection ).await.map_err( $crate::storage::database_error )?;
+                $crate::storage::sql::returned_id( &row, key )
+            }
+
+            async fn update_where( &self, table: &'static str, key: $crate::PrimaryKey, id: &$crate::RecordId, values: &[(&'static str, $crate::FieldValue<'_>)], filters: &[$crate::storage::Filter<'_>], owner: Option<&$crate::storage::Owner> )
+                -> Result<u64, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::update( $dialect, table, key, id, values, filters, owner )?;
+                let mut transaction = self.transaction.lock().await;
+                let connection: &mut <$db as sqlx::Database>::Connection = transaction.as_mut().ok_or( axum::http::StatusCode::INTERNAL_SERVER_ERROR )?;
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .execute( &mut *connection ).await
+                    .map( |result| result.rows_affected() )
+                    .map_err( $crate::storage::database_error )
+            }
+
+            async fn delete( &self, table: &'static str, key: $crate::PrimaryKey, ids: &[$crate::RecordId], owner: Option<&$crate::storage::Owner> ) -> Result<u64, axum::http::StatusCode> {
+                let statement = $crate::storage::sql::delete( $dialect, table, key, ids, owner )?;
+                let mut transaction = self.transaction.lock().await;
+                let connection: &mut <$db as sqlx::Database>::Connection = transaction.as_mut().ok_or( axum::http::StatusCode::INTERNAL_SERVER_ERROR )?;
+                bind_values!( sqlx::query( &statement.sql ), statement.bindings )
+                    .execute( &mut *connection ).await
+                    .map( |result| result.rows_affected() )
+                    .map_err( $crate::storage::database_error )
+            }
+
+            // a nested transaction joins the outer one, which stays the only one that commits
+            async fn transaction( &self ) -> Result<Self, axum::http::StatusCode> {
+                Ok( self.joined() )
+            }
+
+            async fn commit( &self ) -> Result<(), axum::http::StatusCode> {
+                self.finish().await
+            }
+
+            async fn rollback( &self ) -> Result<(), axum::http::StatusCode> {
+                self.abort().await
+            }
+
+            fn as_any( &self ) -> &dyn std::any::Any {
+                self
+            }
+        }
+
+        impl<T> $crate::storage::FromStorageRow<$crate::storage::sql::SqlTransaction<$db>> for T where T: for<'r> sqlx::FromRow<'r, $row> {
+            fn from_storage_row( row: $row ) -> Result<T, axum::http::StatusCode> {
+                T::from_row( &row ).map_err( $crate::storage::database_error )
+            }
+        }
+    };
+}
+
+// backends without RETURNING hand back the key the serializer generated
+pub fn inserted_id( key: PrimaryKey, values: &[(&'static str, FieldValue<'_>)] ) -> Option<RecordId> {
+    let mut parts = key.columns.iter()
+        .map( |( column, _ )| values.iter().find( |( name, _ )| name == column ).and_then( |( _, value )| match value {
+            FieldValue::UUID( id ) => Some( RecordId::UUID( **id ) ),
+            FieldValue::INTEGER( id ) => Some( RecordId::INTEGER( **id as i64 ) ),
+            FieldValue::BIGINT( id ) => Some( RecordId::INTEGER( **id ) ),
+            FieldValue::STRING( id ) => Some( RecordId::TEXT( (*id).clone() ) ),
+            _ => None
+        } ) )
+        .collect::<Option<Vec<_>>>()?;
+
+    match key.is_composite() {
+        true => Some( RecordId::COMPOSITE( parts ) ),
+        false => parts.pop()
+    }
+}
+
+// serial columns may be 32 or 64 bit depending on how the table was declared
+pub fn returned_id<R>( row: &R, key: PrimaryKey ) -> Result<RecordId, StatusCode> where
+        R: sqlx::Row,
+        usize: sqlx::ColumnIndex<R>,
+        for<'r> uuid::Uuid: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+        for<'r> i64: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+        for<'r> i32: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+        for<'r> String: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database> {
+    let mut parts = key.columns.iter()
+        .enumerate()
+        .map( |( index, ( _, key_type ) )| match key_type {
+            KeyType::UUID => row.try_get::<uuid::Uuid, _>( index ).map( RecordId::UUID ),
+            KeyType::SERIAL => row.try_get::<i64, _>( index ).or_else( |_| row.try_get::<i32, _>( index ).map( i64::from ) ).map( RecordId::INTEGER ),
+            KeyType::TEXT => row.try_get::<String, _>( index ).map( RecordId::TEXT )
+        } )
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err( super::database_error )?;
+
+    match key.is_composite() {
+        true => Ok( RecordId::COMPOSITE( parts ) ),
+        false => parts.pop().ok_or( StatusCode::INTERNAL_SERVER_ERROR )
+    }
+}
@@ -0,0 +1,4 @@
+use sqlx::sqlite::{ SqlitePool, SqliteRow };
+use crate::storage::Dialect;
+
+impl_sql_storage!( SqlitePool, SqliteRow, sqlx::Sqlite, Dialect::SQLITE );
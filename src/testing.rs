@@ -0,0 +1,158 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+use rsa::RsaPrivateKey;
+use sqlx::postgres::{ PgConnectOptions, PgPool, PgPoolOptions };
+use tokio::task::JoinHandle;
+use crate::{ CrudConfig, SchemaTrait };
+use crate::dev::DevIssuer;
+use crate::extractors::AuthConfig;
+
+static TEST_KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+
+// key generation is slow in debug builds, so every harness in a test binary shares one key
+fn test_key() -> &'static RsaPrivateKey {
+    TEST_KEY.get_or_init( || crate::dev::generate_key().expect( "Failed to generate test key" ) )
+}
+
+pub struct TestAuth {
+    issuer: DevIssuer,
+    server: JoinHandle<()>
+}
+
+impl TestAuth {
+    pub async fn start() -> anyhow::Result<TestAuth> {
+        let ( issuer, server ) = DevIssuer::bind_with_key( "127.0.0.1:0", test_key() ).await?;
+        Ok( TestAuth { issuer, server } )
+    }
+
+    pub fn config( &self ) -> AuthConfig {
+        self.issuer.auth_config()
+    }
+
+    pub fn issuer( &self ) -> &DevIssuer {
+        &self.issuer
+    }
+
+    pub fn user_token( &self, user_id: &str ) -> String {
+        self.issuer.user_token( user_id )
+    }
+
+    pub fn tenant_token( &self, user_id: &str, org_id: &str, org_role: &str ) -> String {
+        self.issuer.tenant_token( user_id, org_id, org_role )
+    }
+
+    pub fn group_token( &self, user_id: &str, groups: &[&str] ) -> String {
+        self.issuer.group_token( user_id, &groups.iter().map( |group| group.to_string() ).collect::<Vec<_>>() )
+    }
+
+    pub fn permission_token( &self, user_id: &str, permissions: &[&str] ) -> String {
+        self.issuer.permission_token( user_id, &permissions.iter().map( |permission| permission.to_string() ).collect::<Vec<_>>() )
+    }
+
+    pub fn admin_token( &self, user_id: &str ) -> String {
+        self.issuer.admin_token( user_id, &[ self.issuer.auth_config().admin_permission ] )
+    }
+
+    pub fn admin_token_with( &self, user_id: &str, permissions: &[&str] ) -> String {
+        self.issuer.admin_token( user_id, &permissions.iter().map( |permission| permission.to_string() ).collect::<Vec<_>>() )
+    }
+
+    pub fn subscription_token( &self, user_id: &str, subscription: &str ) -> String {
+        self.issuer.subscription_token( user_id, subscription )
+    }
+
+    pub fn bearer( token: &str ) -> String {
+        format!( "Bearer {}", token )
+    }
+}
+
+impl Drop for TestAuth {
+    fn drop( &mut self ) {
+        self.server.abort();
+    }
+}
+
+pub struct TestDatabase {
+    pub pool: PgPool,
+    pub schema: String,
+    options: PgConnectOptions,
+    dropped: bool
+}
+
+async fn drop_schema( options: &PgConnectOptions, schema: &str ) -> anyhow::Result<()> {
+    let admin = PgPool::connect_with( options.clone() ).await?;
+    sqlx::query( &format!( "DROP SCHEMA IF EXISTS {} CASCADE", schema ) ).execute( &admin ).await?;
+    admin.close().await;
+    Ok(())
+}
+
+impl TestDatabase {
+    pub async fn connect( options: PgConnectOptions ) -> anyhow::Result<TestDatabase> {
+        let schema = format!( "janus_test_{}", uuid::Uuid::new_v4().simple() );
+
+        let admin = PgPool::connect_with( options.clone() ).await?;
+        sqlx::query( &format!( "CREATE SCHEMA {}", schema ) ).execute( &admin ).await?;
+        admin.close().await;
+
+        let pool = PgPoolOptions::new()
+            .max_connections( 5 )
+            .connect_with( options.clone().options( [ ( "search_path", schema.as_str() ) ] ) )
+            .await?;
+
+        Ok( TestDatabase { pool, schema, options, dropped: false } )
+    }
+
+    pub async fn from_url( url: &str ) -> anyhow::Result<TestDatabase> {
+        TestDatabase::connect( PgConnectOptions::from_str( url )? ).await
+    }
+
+    pub async fn from_env() -> anyhow::Result<TestDatabase> {
+        dotenv::dotenv().ok();
+
+        let options = PgConnectOptions::new()
+            .host( &std::env::var( "DB_HOST" )? )
+            .port( std::env::var( "DB_PORT" ).ok().and_then( |port| port.parse().ok() ).unwrap_or( 5432 ) )
+            .username( &std::env::var( "DB_USERNAME" )? )
+            .password( &std::env::var( "DB_PASSWORD" )? )
+            .database( &std::env::var( "DB_NAME" )? );
+
+        TestDatabase::connect( options ).await
+    }
+
+    pub async fn apply<T: CrudConfig>( &self ) -> anyhow::Result<()> {
+        sqlx::Executor::execute( &self.pool, T::schema() ).await?;
+        Ok(())
+    }
+
+    pub async fn apply_schema<S: SchemaTrait>( &self ) -> anyhow::Result<()> {
+        sqlx::Executor::execute( &self.pool, S::schema().as_str() ).await?;
+        Ok(())
+    }
+
+    pub async fn teardown( mut self ) -> anyhow::Result<()> {
+        self.pool.close().await;
+        drop_schema( &self.options, &self.schema ).await?;
+        self.dropped = true;
+        Ok(())
+    }
+}
+
+// a test that panics before teardown still gets its schema dropped, on a side runtime since Drop can't await
+impl Drop for TestDatabase {
+    fn drop( &mut self ) {
+        if self.dropped {
+            return;
+        }
+
+        let options = self.options.clone();
+        let schema = self.schema.clone();
+        let cleanup = std::thread::spawn( move || {
+            if let Ok( runtime ) = tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                if let Err( error ) = runtime.block_on( drop_schema( &options, &schema ) ) {
+                    println!( "Failed to drop test schema {}: {:?}", schema, error );
+                }
+            }
+        } );
+        cleanup.join().ok();
+    }
+}
@@ -0,0 +1,275 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, SchemaTrait, RecordId};
+use crate::keys::UUID_KEY;
+use crate::accounts::{AccountsConfig, AccountsSchema, AccountStore, AccountTokens, Credentials, RefreshRequest, USERS_TABLE};
+use crate::api_keys::hash_key;
+use crate::storage::{MemoryStorage, Storage};
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), user_id: user_id.unwrap_or_default() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "AccountObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "accountObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+}
+
+fn credentials( username: &str, password: &str ) -> Credentials {
+    Credentials { username: username.to_string(), password: password.to_string() }
+}
+
+#[tokio::test]
+async fn test_accounts_hs256() {
+    let auth = TestAuth::start().await.unwrap();
+    let storage = MemoryStorage::new();
+    let app = Janus::with_storage( storage.clone() )
+        .auth( auth.config() )
+        .accounts( AccountsConfig::new( "/auth" ) )
+        .revocations( "/revocations" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    let response = client.post("/auth/signup").json( &credentials( "john", "short" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post("/auth/signup").json( &credentials( "john", "correct horse" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let signup: AccountTokens = response.json().await;
+    assert!( signup.subscription_token.is_none() );
+    assert!( !format!( "{:?}", storage.rows( USERS_TABLE ) ).contains( "correct horse" ) );
+
+    let response = client.post("/auth/signup").json( &credentials( "john", "another password" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+
+    let response = client.post("/accountObjects").json( &TestObjectInputParams { name: "Mine".to_string() } )
+        .header( "Authorization", TestAuth::bearer( &signup.access_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/accountObjects").header( "Authorization", TestAuth::bearer( &signup.access_token ) ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects[0].user_id, signup.user_id );
+
+    let response = client.post("/auth/login").json( &credentials( "john", "wrong password" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/auth/login").json( &credentials( "jane", "correct horse" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let id = uuid::Uuid::parse_str( &signup.user_id ).unwrap();
+    let subscription = "pro".to_string();
+    storage.update( USERS_TABLE, UUID_KEY, &RecordId::from( id ), &[ ("subscription", FieldValue::STRING( &subscription )) ], None ).await.unwrap();
+
+    let response = client.post("/auth/login").json( &credentials( "john", "correct horse" ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let login: AccountTokens = response.json().await;
+    let subscription_token = login.subscription_token.clone().unwrap();
+    let response = client.get("/accountObjects")
+        .header( "Authorization", TestAuth::bearer( &login.access_token ) )
+        .header( "Subscription", TestAuth::bearer( &subscription_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    // subscription tokens share the secret and issuer but are not access tokens
+    let response = client.get("/accountObjects").header( "Authorization", TestAuth::bearer( &subscription_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/accountObjects").header( "Authorization", TestAuth::bearer( &auth.subscription_token( &signup.user_id, "pro" ) ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    // without accounts mounted no HS256 token is an access token
+    let without_accounts = Janus::with_storage( storage.clone() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let response = TestClient::new( without_accounts ).get("/accountObjects").header( "Authorization", TestAuth::bearer( &login.access_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/auth/refresh").json( &RefreshRequest { refresh_token: login.refresh_token.clone() } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let refreshed: AccountTokens = response.json().await;
+    assert_eq!( refreshed.user_id, signup.user_id );
+
+    let response = client.post("/auth/refresh").json( &RefreshRequest { refresh_token: login.refresh_token } ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/auth/logout").json( &RefreshRequest { refresh_token: refreshed.refresh_token.clone() } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/auth/refresh").json( &RefreshRequest { refresh_token: refreshed.refresh_token } ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/auth/login").json( &credentials( "john", "correct horse" ) ).send().await;
+    let login: AccountTokens = response.json().await;
+    let admin = TestAuth::bearer( &auth.admin_token( "user_admin" ) );
+    let response = client.post("/revocations").json( &serde_json::json!({ "user_id": signup.user_id }) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/auth/refresh").json( &RefreshRequest { refresh_token: login.refresh_token } ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/auth/.well-known/jwks.json").send().await;
+    assert_eq!( response.status(), StatusCode::NOT_FOUND );
+
+    let mut forged = auth.config();
+    forged.jwt_secret = "not the secret".to_string();
+    let other = Janus::with_storage( MemoryStorage::new() ).auth( forged ).accounts( AccountsConfig::new( "/auth" ) ).build();
+    let response = TestClient::new( other ).post("/auth/signup").json( &credentials( "mallory", "correct horse" ) ).send().await;
+    let forged: AccountTokens = response.json().await;
+    let response = client.get("/accountObjects").header( "Authorization", TestAuth::bearer( &forged.access_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+}
+
+#[tokio::test]
+async fn test_accounts_rs256() {
+    let listener = std::net::TcpListener::bind( "127.0.0.1:0" ).unwrap();
+    let mut config = TestAuth::start().await.unwrap().config();
+    config.clerk_issuer = format!( "http://{}/auth", listener.local_addr().unwrap() );
+
+    let pool = SqlitePoolOptions::new().max_connections( 1 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, AccountsSchema::schema().as_str() ).await.unwrap();
+    sqlx::Executor::execute( &pool, "CREATE TABLE AccountObjects ( id UUID PRIMARY KEY, name TEXT NOT NULL, user_id TEXT NOT NULL )" ).await.unwrap();
+
+    let app = Janus::with_storage( pool )
+        .auth( config.clone() )
+        .accounts( AccountsConfig::new( "/auth" ).rs256( crate::dev::generate_key().unwrap() ) )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let server = tokio::spawn( axum::Server::from_tcp( listener ).unwrap().serve( app.into_make_service() ) );
+    let http = reqwest::Client::new();
+
+    let tokens: AccountTokens = http.post( format!( "{}/signup", config.clerk_issuer ) ).json( &credentials( "jane", "correct horse" ) )
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!( jsonwebtoken::decode_header( &tokens.access_token ).unwrap().alg, jsonwebtoken::Algorithm::RS256 );
+
+    let response = http.post( format!( "{}/login", config.clerk_issuer ) ).json( &credentials( "jane", "correct horse" ) ).send().await.unwrap();
+    assert_eq!( response.status(), 200 );
+
+    // both lookups miss while the passwords hash, so the loser of the race hits the UNIQUE username
+    let signup = || http.post( format!( "{}/signup", config.clerk_issuer ) ).json( &credentials( "john", "correct horse" ) ).send();
+    let ( first, second ) = tokio::join!( signup(), signup() );
+    let mut statuses = vec![ first.unwrap().status(), second.unwrap().status() ];
+    statuses.sort();
+    assert_eq!( statuses, vec![ StatusCode::OK, StatusCode::CONFLICT ] );
+
+    let base_url = config.clerk_issuer.trim_end_matches( "/auth" );
+    let response = http.post( format!( "{}/accountObjects", base_url ) ).json( &TestObjectInputParams { name: "Hers".to_string() } )
+        .bearer_auth( &tokens.access_token ).send().await.unwrap();
+    assert_eq!( response.status(), 200 );
+
+    let objects: Vec<TestObject> = http.get( format!( "{}/accountObjects", base_url ) ).bearer_auth( &tokens.access_token )
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!( objects[0].user_id, tokens.user_id );
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_refresh_replay() {
+    let auth = TestAuth::start().await.unwrap();
+    let pool = SqlitePoolOptions::new().max_connections( 2 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, AccountsSchema::schema().as_str() ).await.unwrap();
+
+    let app = Janus::with_storage( pool.clone() )
+        .auth( auth.config() )
+        .accounts( AccountsConfig::new( "/auth" ) )
+        .build();
+    let client = TestClient::new( app );
+
+    let response = client.post("/auth/signup").json( &credentials( "john", "correct horse" ) ).send().await;
+    let signup: AccountTokens = response.json().await;
+
+    // both replays can find the token active, only one may revoke it and mint a pair
+    let refresh = || client.post("/auth/refresh").json( &RefreshRequest { refresh_token: signup.refresh_token.clone() } ).send();
+    let ( first, second ) = tokio::join!( refresh(), refresh() );
+    let mut statuses = vec![ first.status(), second.status() ];
+    statuses.sort();
+    assert_eq!( statuses, vec![ StatusCode::OK, StatusCode::UNAUTHORIZED ] );
+
+    let response = client.post("/auth/login").json( &credentials( "john", "correct horse" ) ).send().await;
+    let login: AccountTokens = response.json().await;
+    let stored = pool.find_refresh_token( &hash_key( &login.refresh_token ) ).await.unwrap().unwrap();
+    assert_eq!( pool.revoke_refresh_token( stored.id ).await.unwrap(), 1 );
+    assert_eq!( pool.revoke_refresh_token( stored.id ).await.unwrap(), 0 );
+
+    let response = client.post("/auth/refresh").json( &RefreshRequest { refresh_token: login.refresh_token } ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+}
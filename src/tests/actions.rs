@@ -0,0 +1,169 @@
+use axum::http::StatusCode;
+use axum::Json;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, Action, InputSerializer, CrudConfig, CrudError, KeyValue, FieldValue, Janus, RecordId, RequestContext, Storage};
+use crate::openapi::{OpenApiConfig, openapi_document};
+use crate::storage::{MemoryStorage, ReadQuery};
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    item: String,
+    status: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    item: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            item: self.item.clone(),
+            status: "open".to_string(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("item", FieldValue::STRING( &self.item )),
+            ("status", FieldValue::STRING( &self.status )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("item", FieldValue::STRING( &self.item ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "ActionOrders"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "orders"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS ActionOrders (
+            id UUID PRIMARY KEY,
+            item TEXT NOT NULL,
+            status TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn actions() -> Vec<Action> {
+        vec![
+            Action::item( "cancel", AccessPermission::AUTHENTICATED, ObjectPermission::OWNER ),
+            Action::collection( "summary", AccessPermission::ANY, ObjectPermission::ALL )
+        ]
+    }
+
+    async fn run_action<S: Storage>( storage: &S, context: &RequestContext, action: &str, id: Option<RecordId>, input: Value ) -> Result<Json<Value>, CrudError> {
+        match ( action, id ) {
+            ( "cancel", Some( id ) ) => {
+                let reason = input["reason"].as_str().ok_or( CrudError::bad_request( "a reason is required" ) )?.to_string();
+                let status = "cancelled".to_string();
+                storage.update( "ActionOrders", Self::primary_key(), &id, &[ ("status", FieldValue::STRING( &status )) ], None ).await?;
+                Ok( Json( json!({ "status": status, "reason": reason, "by": context.user_id }) ) )
+            },
+            ( "summary", None ) => {
+                let query = ReadQuery { table: "ActionOrders", filters: vec![], owner: None, order_by: "id", descending: false, limit: None, offset: None };
+                Ok( Json( json!({ "orders": storage.select( &query ).await?.len() }) ) )
+            },
+            _ => Err( StatusCode::NOT_FOUND.into() )
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_resource_actions() {
+    let auth = TestAuth::start().await.unwrap();
+    let builder = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .openapi( OpenApiConfig::new( "Test API", "1.0.0" ) )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>();
+
+    let document = openapi_document( builder.registry(), "Test API", "1.0.0" );
+    let cancel = &document["paths"]["/orders/{id}/cancel"]["post"];
+    assert_eq!( cancel["operationId"], "cancel_orders" );
+    assert_eq!( cancel["parameters"][0]["name"], "id" );
+    assert!( cancel["security"].is_array() );
+    assert!( document["paths"]["/orders/summary"]["post"]["security"].is_null() );
+
+    let client = TestClient::new( builder.build() );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+
+    let response = client.post("/orders").json( &TestObjectInputParams { item: "Book".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.post( &format!( "/orders/{}/cancel", id ) ).json( &json!({ "reason": "late" }) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post( &format!( "/orders/{}/cancel", id ) ).json( &json!({ "reason": "late" }) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post( &format!( "/orders/{}/cancel", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+    assert_eq!( response.text().await, "a reason is required" );
+
+    let response = client.post( &format!( "/orders/{}/cancel", id ) ).json( &json!({ "reason": "late" }) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert_eq!( response.json::<Value>().await, json!({ "status": "cancelled", "reason": "late", "by": "user_john" }) );
+
+    let response = client.get("/orders").header( "Authorization", &john ).send().await;
+    let orders: Vec<TestObject> = response.json().await;
+    assert_eq!( orders[0].status, "cancelled" );
+
+    let response = client.post("/orders/summary").send().await;
+    assert_eq!( response.json::<Value>().await, json!({ "orders": 1 }) );
+}
@@ -0,0 +1,204 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, SchemaTrait};
+use crate::api_keys::{ApiKeys, ApiKeySchema, ApiKeyInfo, CreateApiKey, CreatedApiKey, scope_allows, API_KEYS_TABLE};
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), user_id: user_id.unwrap_or_default() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "KeyObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "keyObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+}
+
+#[test]
+fn test_scope_allows() {
+    let scopes = vec![ "keyObjects:GET".to_string(), "*:POST".to_string() ];
+    assert!( scope_allows( &[], "keyObjects", EndpointVerb::DELETE ) );
+    assert!( scope_allows( &scopes, "keyObjects", EndpointVerb::GET ) );
+    assert!( scope_allows( &scopes, "other", EndpointVerb::POST ) );
+    assert!( !scope_allows( &scopes, "keyObjects", EndpointVerb::PUT ) );
+    assert!( !scope_allows( &scopes, "other", EndpointVerb::GET ) );
+    assert!( scope_allows( &[ "keyObjects:*".to_string() ], "keyObjects", EndpointVerb::PUT ) );
+}
+
+#[tokio::test]
+async fn test_api_key_routes() {
+    let auth = TestAuth::start().await.unwrap();
+    let storage = MemoryStorage::new();
+    let app = Janus::with_storage( storage.clone() )
+        .auth( auth.config() )
+        .api_keys( "/apiKeys" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let admin = TestAuth::bearer( &auth.admin_token( "admin_1" ) );
+
+    let request = CreateApiKey { user_id: "user_1".to_string(), name: "cron".to_string(), scopes: vec![], expires_at: None };
+    let response = client.post("/apiKeys").json( &request ).header( "Authorization", TestAuth::bearer( &auth.user_token( "user_1" ) ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/apiKeys").json( &request ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let full: CreatedApiKey = response.json().await;
+    assert!( full.key.starts_with( &full.info.prefix ) );
+    assert!( storage.rows( API_KEYS_TABLE ).iter().all( |row| !format!( "{:?}", row ).contains( &full.key ) ) );
+
+    let scoped_request = CreateApiKey { scopes: vec![ "keyObjects:GET".to_string() ], name: "reader".to_string(), ..request.clone() };
+    let response = client.post("/apiKeys").json( &scoped_request ).header( "Authorization", &admin ).send().await;
+    let scoped: CreatedApiKey = response.json().await;
+
+    let response = client.post("/apiKeys").json( &CreateApiKey { scopes: vec![ "keyObjects:FETCH".to_string() ], ..request.clone() } ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post("/keyObjects").json( &TestObjectInputParams { name: "Job".to_string() } ).header( "X-Api-Key", &full.key ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/keyObjects").header( "X-Api-Key", &scoped.key ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects[0].user_id, "user_1" );
+
+    let response = client.post("/keyObjects").json( &TestObjectInputParams { name: "Job".to_string() } ).header( "X-Api-Key", &scoped.key ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.get("/keyObjects").header( "X-Api-Key", "jk_not_a_real_key" ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.delete( &format!( "/keyObjects/{}", id ) ).header( "X-Api-Key", &full.key ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/apiKeys?user_id=user_1").header( "Authorization", &admin ).send().await;
+    let keys: Vec<ApiKeyInfo> = response.json().await;
+    assert_eq!( keys.len(), 2 );
+    assert_eq!( serde_json::to_value( &keys[0] ).unwrap().get( "key_hash" ), None );
+
+    let response = client.delete( &format!( "/apiKeys/{}", full.info.id ) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/keyObjects").header( "X-Api-Key", &full.key ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.delete( &format!( "/apiKeys/{}", uuid::Uuid::new_v4() ) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let expired = CreateApiKey { expires_at: Some( chrono::Utc::now() - chrono::Duration::minutes( 1 ) ), ..request.clone() };
+    let response = client.post("/apiKeys").json( &expired ).header( "Authorization", &admin ).send().await;
+    let expired: CreatedApiKey = response.json().await;
+    let response = client.get("/keyObjects").header( "X-Api-Key", &expired.key ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/apiKeys").header( "Authorization", &admin ).send().await;
+    let keys: Vec<serde_json::Value> = response.json().await;
+    assert_eq!( keys.iter().filter( |key| key["revoked"] == json!( true ) ).count(), 1 );
+}
+
+#[tokio::test]
+async fn test_api_keys_disabled() {
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    let response = client.get("/keyObjects").header( "X-Api-Key", "jk_anything" ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+}
+
+#[tokio::test]
+async fn test_sqlite_api_key_store() {
+    let pool = SqlitePoolOptions::new().max_connections( 1 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, ApiKeySchema::schema().as_str() ).await.unwrap();
+    let api_keys = ApiKeys::new( pool );
+
+    let request = CreateApiKey { user_id: "user_1".to_string(), name: "partner".to_string(), scopes: vec![ "*:GET".to_string() ], expires_at: None };
+    let created = api_keys.create( &request ).await.unwrap();
+
+    let api_key = api_keys.authenticate( &created.key ).await.unwrap();
+    assert_eq!( api_key.user_id, "user_1" );
+    assert_eq!( api_key.scope_list(), vec![ "*:GET".to_string() ] );
+
+    assert_eq!( api_keys.revoke( created.info.id ).await, Ok( 1 ) );
+    assert_eq!( api_keys.authenticate( &created.key ).await.unwrap_err(), StatusCode::UNAUTHORIZED );
+    assert!( api_keys.list( Some( "user_1" ) ).await.unwrap()[0].revoked );
+}
@@ -0,0 +1,204 @@
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use tower_http::cors::{Any, CorsLayer};
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::postgres::PgPoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, ErrorFormat};
+use axum::Router;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: match user_id { Some(id) => id, None => "nobody".to_string() }
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        pairs
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "BuilderObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "builderObjects"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS BuilderObjects (
+                id UUID PRIMARY KEY,
+                name VARCHAR(50) NOT NULL,
+                user_id VARCHAR(50) NOT NULL
+            );
+        "
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        match verb {
+            EndpointVerb::GET => true,
+            EndpointVerb::POST => true,
+            EndpointVerb::PUT => false,
+            EndpointVerb::DELETE => true
+        }
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+}
+
+fn app_test_setup( error_format: ErrorFormat ) -> Router {
+    let pool = PgPoolOptions::new().connect_lazy( "postgres://janus@localhost/janus" ).unwrap();
+
+    Janus::builder()
+        .prefix( "/api" )
+        .pool( pool )
+        .cors( CorsLayer::new().allow_origin(Any) )
+        .error_format( error_format )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .resource_at::<TestObject, TestObjectInputParams, TestObjectQueryParams>( "v2" )
+        .build()
+}
+
+#[test]
+fn test_registry() {
+    let builder = Janus::builder()
+        .prefix( "api/" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>();
+
+    let registry = builder.registry();
+    assert_eq!( registry.len(), 1 );
+
+    let info = registry.get( "builderObjects" ).unwrap();
+    assert_eq!( info.table_name, "BuilderObjects" );
+    assert_eq!( info.prefix, "/api" );
+    assert_eq!( info.verbs.len(), 3 );
+    assert!( info.verb( EndpointVerb::PUT ).is_none() );
+
+    let delete = info.verb( EndpointVerb::DELETE ).unwrap();
+    assert_eq!( delete.path, "/api/builderObjects/:id" );
+    assert_eq!( delete.access, AccessPermission::ADMIN );
+    assert_eq!( delete.object, ObjectPermission::OWNER );
+}
+
+mod other {
+    use serde::{Serialize, Deserialize};
+    use crate::{KeyValue, FieldValue};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct TestObjectQueryParams {}
+
+    impl KeyValue for TestObjectQueryParams {
+        fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+            vec![]
+        }
+    }
+}
+
+#[test]
+#[should_panic( expected = "share a type name" )]
+fn test_duplicate_type_names() {
+    Janus::builder()
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .resource_at::<TestObject, TestObjectInputParams, other::TestObjectQueryParams>( "v2" );
+}
+
+#[tokio::test]
+async fn test_builder_routes() {
+    let client = TestClient::new( app_test_setup( ErrorFormat::PLAIN ) );
+
+    let response = client.get("/api/builderObjects").send().await;
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.text().await, "No auth token");
+
+    let response = client.get("/api/v2/builderObjects").send().await;
+    assert_eq!(response.status(), 401);
+
+    let response = client.get("/builderObjects").send().await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_json_errors() {
+    let client = TestClient::new( app_test_setup( ErrorFormat::JSON ) );
+
+    let response = client.post("/api/builderObjects").json(&TestObjectInputParams { name: "John".to_string() }).send().await;
+    assert_eq!(response.status(), 401);
+
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["status"], 401 );
+    assert_eq!( body["error"], "No auth token" );
+
+    let response = client.get("/api/missing").send().await;
+    assert_eq!(response.status(), 404);
+
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["error"], "Not Found" );
+}
@@ -0,0 +1,241 @@
+use std::sync::Mutex;
+use axum::http::StatusCode;
+use axum::async_trait;
+use axum::Json;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::postgres::PgPoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, Storage, FromStorageRow, KeyValue, FieldValue, Janus, ErrorFormat, RecordId, RequestContext};
+use crate::client::{ResourceClient, ClientError, Page};
+
+static RECORDED_FILTERS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    date_created: chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    date_created_gt: Option<chrono::DateTime<chrono::Utc>>,
+    limit: Option<i32>,
+    offset: Option<i32>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, _user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), date_created: chrono::Utc::now() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(date_created_gt) = &self.date_created_gt {
+            pairs.push( ("date_created_gt", FieldValue::DATE( date_created_gt )) );
+        }
+
+        if let Some(limit) = &self.limit {
+            pairs.push( ("limit", FieldValue::INTEGER( limit )) );
+        }
+
+        if let Some(offset) = &self.offset {
+            pairs.push( ("offset", FieldValue::INTEGER( offset )) );
+        }
+
+        pairs
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "ClientObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "clientObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::AUTHENTICATED,
+            _ => AccessPermission::ANY
+        }
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_create<S, T>( storage: &S, _context: &RequestContext, values: T ) -> Result<Json<RecordId>, StatusCode> where S: Storage, T : Send + Sync + Unpin + KeyValue {
+        Ok( Json( RecordId::UUID( uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() ) ) )
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_read<S, T, QP>( storage: &S, _context: &RequestContext, filters: QP, user_id: Option<String> )
+        -> Result<Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
+        let mut recorded = RECORDED_FILTERS.lock().unwrap();
+        for (key, value) in filters.key_value_pairs() {
+            let value = match value {
+                FieldValue::STRING( value ) => value.clone(),
+                FieldValue::INTEGER( value ) => value.to_string(),
+                FieldValue::DATE( value ) => value.to_rfc3339(),
+                _ => String::new()
+            };
+            recorded.push( format!( "{}={}", key, value ) );
+        }
+
+        Ok( Json( vec![] ) )
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_update<S, UP>( storage: &S, _context: &RequestContext, id: RecordId, values: UP, user_id: Option<String> ) -> StatusCode where S: Storage, UP: Send + Sync + Unpin + KeyValue {
+        match id == RecordId::UUID( uuid::Uuid::nil() ) {
+            true => StatusCode::BAD_REQUEST,
+            false => StatusCode::OK
+        }
+    }
+}
+
+async fn spawn_server() -> String {
+    let pool = PgPoolOptions::new().connect_lazy( "postgres://janus@localhost/janus" ).unwrap();
+    let app = Janus::builder()
+        .prefix( "/restful" )
+        .pool( pool )
+        .error_format( ErrorFormat::JSON )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+
+    let listener = std::net::TcpListener::bind( "127.0.0.1:0" ).unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn( async move {
+        axum::Server::from_tcp( listener ).unwrap().serve( app.into_make_service() ).await.unwrap();
+    } );
+
+    format!( "http://{}/restful", addr )
+}
+
+#[tokio::test]
+async fn test_client_round_trip() {
+    let base_url = spawn_server().await;
+    let client = ResourceClient::<TestObject, TestObjectInputParams, TestObjectQueryParams>::new( &base_url );
+    assert_eq!( client.collection_url(), format!( "{}/clientObjects", base_url ) );
+
+    let id = client.create( &TestObjectInputParams { name: "John".to_string() } ).await.unwrap();
+    assert_eq!( id, RecordId::UUID( uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() ) );
+
+    let error = client.create( &TestObjectInputParams { name: "".to_string() } ).await.unwrap_err();
+    assert!( matches!( error, ClientError::BADREQUEST(_) ) );
+
+    client.update( &id, &TestObjectInputParams { name: "Jane".to_string() } ).await.unwrap();
+    let error = client.update( &RecordId::UUID( uuid::Uuid::nil() ), &TestObjectInputParams { name: "Jane".to_string() } ).await.unwrap_err();
+    assert_eq!( error.status(), Some( StatusCode::BAD_REQUEST ) );
+
+    let error = client.delete( &id ).await.unwrap_err();
+    match error {
+        ClientError::UNAUTHORIZED( message ) => assert_eq!( message, "No auth token" ),
+        other => panic!( "unexpected error {:?}", other )
+    }
+
+    let date = chrono::DateTime::parse_from_rfc3339( "2023-11-15T03:09:35Z" ).unwrap().with_timezone( &chrono::Utc );
+    let query = TestObjectQueryParams { name: Some( "John Doe".to_string() ), date_created_gt: Some( date ), ..Default::default() };
+    let objects = client.list_all( &query, 10 ).await.unwrap();
+    assert!( objects.is_empty() );
+
+    let objects = client.list_page( &TestObjectQueryParams::default(), Page { limit: 5, offset: 15 } ).await.unwrap();
+    assert!( objects.is_empty() );
+
+    let recorded = RECORDED_FILTERS.lock().unwrap().clone();
+    assert_eq!( recorded, vec![
+        "name=John Doe".to_string(),
+        "date_created_gt=2023-11-15T03:09:35+00:00".to_string(),
+        "limit=10".to_string(),
+        "offset=0".to_string(),
+        "limit=5".to_string(),
+        "offset=15".to_string()
+    ] );
+}
+
+async fn spawn_unpaged_server() -> String {
+    let rows = vec![
+        TestObject { id: uuid::Uuid::new_v4(), name: "John".to_string(), date_created: chrono::Utc::now() },
+        TestObject { id: uuid::Uuid::new_v4(), name: "Jane".to_string(), date_created: chrono::Utc::now() }
+    ];
+    let app = axum::Router::new()
+        .route( "/clientObjects", axum::routing::get( move || async move { Json( rows ) } ) )
+        .route( "/clientObjects/:id", axum::routing::put( |axum::extract::Path( id ): axum::extract::Path<String>| async move {
+            match id.as_str() {
+                "reports/2023 q4" => StatusCode::OK,
+                _ => StatusCode::NOT_FOUND
+            }
+        } ) );
+
+    let listener = std::net::TcpListener::bind( "127.0.0.1:0" ).unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn( async move {
+        axum::Server::from_tcp( listener ).unwrap().serve( app.into_make_service() ).await.unwrap();
+    } );
+
+    format!( "http://{}", addr )
+}
+
+#[tokio::test]
+async fn test_client_unpaged_server() {
+    let base_url = spawn_unpaged_server().await;
+    let client = ResourceClient::<TestObject, TestObjectInputParams, TestObjectQueryParams>::new( &base_url );
+
+    // the server ignores limit/offset, so a full page repeats instead of advancing
+    let objects = client.list_all( &TestObjectQueryParams::default(), 2 ).await.unwrap();
+    assert_eq!( objects.iter().map( |object| object.name.as_str() ).collect::<Vec<_>>(), vec![ "John", "Jane" ] );
+
+    client.update( &RecordId::TEXT( "reports/2023 q4".to_string() ), &TestObjectInputParams { name: "Jane".to_string() } ).await.unwrap();
+}
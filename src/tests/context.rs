@@ -0,0 +1,194 @@
+use std::sync::Mutex;
+use axum::http::StatusCode;
+use axum::Json;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, RecordId, RequestContext, Storage};
+use crate::keys::UUID_KEY;
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+static CONTEXTS: Mutex<Vec<RequestContext>> = Mutex::new( vec![] );
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "ContextObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "contextObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS ContextObjects (
+            id UUID PRIMARY KEY,
+            name TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        matches!( verb, EndpointVerb::POST | EndpointVerb::DELETE )
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+
+    async fn custom_create<S, T>( storage: &S, context: &RequestContext, values: T ) -> Result<Json<RecordId>, StatusCode> where S: Storage, T : Send + Sync + Unpin + KeyValue {
+        CONTEXTS.lock().unwrap().push( context.clone() );
+        if context.subscription.as_deref() != Some( "pro" ) {
+            return Err( StatusCode::PAYMENT_REQUIRED );
+        }
+        Ok( Json( storage.insert( "ContextObjects", UUID_KEY, &values.key_value_pairs() ).await? ) )
+    }
+
+    async fn custom_delete<S>( storage: &S, context: &RequestContext, ids: Vec<RecordId>, _user_id: Option<String> ) -> StatusCode where S: Storage {
+        CONTEXTS.lock().unwrap().push( context.clone() );
+        let transaction = match context.transaction( storage ).await {
+            Ok( transaction ) => transaction,
+            Err( status ) => return status
+        };
+
+        // the delete is refused after it ran, failing the request rolls it back
+        let deleted = transaction.delete( "ContextObjects", UUID_KEY, &ids, None ).await;
+        if !context.admin || !context.permissions.iter().any( |permission| permission == "delete:records" ) {
+            return StatusCode::FORBIDDEN;
+        }
+        match ( deleted, transaction.commit().await ) {
+            ( Ok( 1 ), Ok(()) ) => StatusCode::OK,
+            ( Ok(_), Ok(()) ) => StatusCode::BAD_REQUEST,
+            ( Err( status ), _ ) | ( _, Err( status ) ) => status
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_request_context() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let john_pro = TestAuth::bearer( &auth.subscription_token( "user_john", "pro" ) );
+    let admin = TestAuth::bearer( &auth.admin_token( "user_admin" ) );
+    let deleting_admin = TestAuth::bearer( &auth.admin_token_with( "user_admin", &[ "read:admin", "delete:records" ] ) );
+
+    let input = TestObjectInputParams { name: "Plans".to_string() };
+    let response = client.post("/contextObjects").json( &input ).header( "Authorization", &john ).header( "X-Request-Id", "request-1" ).send().await;
+    assert_eq!( response.status(), StatusCode::PAYMENT_REQUIRED );
+
+    let response = client.post("/contextObjects").json( &input ).header( "Authorization", &john ).header( "Subscription", &john_pro ).header( "X-Client", "mobile" ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.delete( &format!( "/contextObjects/{}", id ) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/contextObjects/{}", id ) ).header( "Authorization", &deleting_admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/contextObjects/{}", id ) ).header( "Authorization", &deleting_admin ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let contexts = CONTEXTS.lock().unwrap();
+    assert_eq!( contexts.len(), 5 );
+    assert_eq!( contexts[0].request_id, "request-1" );
+    assert_eq!( contexts[0].subscription.as_deref(), Some( "none" ) );
+    assert_eq!( contexts[1].user_id.as_deref(), Some( "user_john" ) );
+    assert_eq!( contexts[1].headers.get( "X-Client" ).unwrap(), "mobile" );
+    assert!( !contexts[1].request_id.is_empty() && contexts[1].request_id != contexts[0].request_id );
+    assert!( !contexts[1].admin );
+    assert_eq!( contexts[3].user_id.as_deref(), Some( "user_admin" ) );
+    assert!( contexts[3].admin );
+}
+
+#[tokio::test]
+async fn test_unconfigured_subscription_secret() {
+    let auth = TestAuth::start().await.unwrap();
+    let mut config = auth.config();
+    config.jwt_secret = String::new();
+    config.jwt_issuer = String::new();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( config.clone() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+
+    // signed with the empty key that an unset JWT_SECRET would leave behind
+    let now = chrono::Utc::now().timestamp();
+    let claims = serde_json::json!({ "subscription": "pro", "clerk_id": "user_john", "aud": config.audience, "iss": "", "iat": now, "exp": now + 300 });
+    let forged = jsonwebtoken::encode( &jsonwebtoken::Header::new( jsonwebtoken::Algorithm::HS256 ), &claims, &jsonwebtoken::EncodingKey::from_secret( b"" ) ).unwrap();
+
+    let input = TestObjectInputParams { name: "Plans".to_string() };
+    let response = client.post("/contextObjects").json( &input ).header( "Authorization", &john ).header( "Subscription", TestAuth::bearer( &forged ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+}
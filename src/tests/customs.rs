@@ -5,10 +5,10 @@ use axum::async_trait;
 use tower_http::cors::{Any, CorsLayer};
 use serde::{Serialize, Deserialize};
 use sqlx::FromRow;
-use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, SchemaTrait, FieldValue, KeyValue};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, FieldValue, KeyValue, Storage, FromStorageRow, RecordId, RequestContext};
 use axum::Router;
+use crate::testing::TestDatabase;
 use axum::Json;
-use sqlx::postgres::{PgPool, PgRow};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 struct TestObject {
@@ -200,65 +200,32 @@ impl CrudConfig for TestObject {
     }
 
     #[allow(unused_variables)]
-    async fn custom_create<T: KeyValue>( connection_pool: &PgPool, values: T ) -> Result<Json<uuid::Uuid>, StatusCode> where T: Send + Unpin {
-        return Ok( Json( uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() ) );
+    async fn custom_create<S: Storage, T: KeyValue>( storage: &S, _context: &RequestContext, values: T ) -> Result<Json<RecordId>, StatusCode> where T: Send + Unpin {
+        return Ok( Json( RecordId::UUID( uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() ) ) );
     }
 
     #[allow(unused_variables)]
-    async fn custom_read<T, QP>( connection_pool: &PgPool, filters: QP, user_id: Option<String> ) -> Result<Json<Vec<T>>, StatusCode> where T: for<'r> FromRow<'r, PgRow> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
+    async fn custom_read<S, T, QP>( storage: &S, _context: &RequestContext, filters: QP, user_id: Option<String> ) -> Result<Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
         return Ok( Json( vec![] ) );
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct SchemaConfig {}
-impl SchemaTrait for SchemaConfig {
-    fn schema() -> String {
-        return "
-            CREATE TABLE IF NOT EXISTS TestObjects (
-                id SERIAL PRIMARY KEY,
-                name VARCHAR(50) NOT NULL,
-                age INT NOT NULL,
-                date_created VARCHAR(50) NOT NULL,
-                status VARCHAR(50) NOT NULL,
-                user_id VARCHAR(50) NOT NULL
-            );
-        ".to_string();
-    }
-}
+async fn app_test_setup() -> ( Router, TestDatabase ) {
+    let database = TestDatabase::from_env().await.unwrap();
+    database.apply::<TestObject>().await.unwrap();
 
-pub async fn app_test_setup() -> Router {
-    dotenv::dotenv().ok();
-    
-    let resp = crate::connect_to_db(
-                                                    std::env::var("DB_HOST").unwrap().as_str(), 
-                                                    5432,
-                                                    std::env::var("DB_USERNAME").unwrap().as_str(), 
-                                                    std::env::var("DB_PASSWORD").unwrap().as_str(), 
-                                                    std::env::var("DB_NAME").unwrap().as_str() 
-                                                )
-                                                .await;
-
-    let connection_pool = match resp {
-        Ok(pool) => pool,
-        Err(e) => panic!("Failed to connect to database: {:?}", e)
-    };
-    
-    Router::new()
+    let router = Router::new()
         .nest("/restful", crate::create_endpoint_router::<TestObject, TestObjectInputParams, TestObjectQueryParams>())
-        .nest("/tableCommands", crate::create_tables_router::<SchemaConfig>())
-        .layer(Extension(connection_pool))
-        .layer(CorsLayer::new().allow_origin(Any))
+        .layer(Extension(database.pool.clone()))
+        .layer(CorsLayer::new().allow_origin(Any));
+
+    ( router, database )
 }
 
 #[tokio::test]
 async fn test_customs() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John Doe".to_string(), age: 30 }).send().await;
@@ -266,5 +233,6 @@ async fn test_customs() {
     let id = response.json::<uuid::Uuid>().await;
     assert_eq!(id, uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() );
 
+    database.teardown().await.unwrap();
 }
 
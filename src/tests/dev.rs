@@ -0,0 +1,142 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::dev::{DevIssuer, TokenResponse};
+use crate::storage::MemoryStorage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), user_id: user_id.unwrap_or_default() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "DevObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "devObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_dev_issuer() {
+    let ( issuer, server ) = DevIssuer::bind( "127.0.0.1:0" ).await.unwrap();
+    let config = issuer.auth_config();
+    let http = reqwest::Client::new();
+
+    let jwks: serde_json::Value = http.get( format!( "{}/.well-known/jwks.json", config.clerk_issuer ) ).send().await.unwrap().json().await.unwrap();
+    assert_eq!( jwks["keys"][0]["kid"], "janus-dev-key" );
+
+    let response = http.post( format!( "{}/token", config.clerk_issuer ) ).json( &json!({ "sub": "" }) ).send().await.unwrap();
+    assert_eq!( response.status(), 400 );
+
+    let tokens: TokenResponse = http.post( format!( "{}/token", config.clerk_issuer ) )
+        .json( &json!({ "sub": "user_1", "permissions": [ "read:admin" ], "subscription": "pro" }) )
+        .send().await.unwrap()
+        .json().await.unwrap();
+
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( config.clone() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    let access = format!( "Bearer {}", tokens.access_token );
+    let subscription = format!( "Bearer {}", tokens.subscription_token.unwrap() );
+    let response = client.post("/devObjects").json( &TestObjectInputParams { name: "Mine".to_string() } )
+        .header( "Authorization", &access ).header( "Subscription", &subscription ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/devObjects").header( "Authorization", &access ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects[0].user_id, "user_1" );
+
+    let other_subscription = format!( "Bearer {}", issuer.subscription_token( "user_2", "pro" ) );
+    let response = client.get("/devObjects").header( "Authorization", &access ).header( "Subscription", &other_subscription ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.delete( &format!( "/devObjects/{}", id ) ).header( "Authorization", &access ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.delete( &format!( "/devObjects/{}", id ) ).header( "Authorization", format!( "Bearer {}", tokens.admin_token ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let other_issuer = DevIssuer::new( "http://127.0.0.1:1" ).unwrap();
+    let response = client.get("/devObjects").header( "Authorization", format!( "Bearer {}", other_issuer.user_token( "user_1" ) ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    server.abort();
+}
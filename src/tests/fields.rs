@@ -0,0 +1,270 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, FieldPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    title: String,
+    body: String,
+    notes: String,
+    priority: i32,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AdminObject {
+    id: uuid::Uuid,
+    title: String,
+    body: String,
+    notes: String,
+    priority: i32,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    title: String,
+    body: String,
+    notes: String,
+    priority: i32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+fn field_permission( field: &str ) -> FieldPermission {
+    match field {
+        "title" => FieldPermission::READONLY,
+        "notes" => FieldPermission::OWNERONLY,
+        "priority" => FieldPermission::ADMINONLY,
+        "user_id" => FieldPermission::HIDDEN,
+        _ => FieldPermission::PUBLIC
+    }
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.title.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            title: self.title.clone(),
+            body: self.body.clone(),
+            notes: self.notes.clone(),
+            priority: self.priority,
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl InputSerializer<AdminObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.title.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> AdminObject {
+        AdminObject {
+            id: uuid::Uuid::new_v4(),
+            title: self.title.clone(),
+            body: self.body.clone(),
+            notes: self.notes.clone(),
+            priority: self.priority,
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("title", FieldValue::STRING( &self.title )),
+            ("body", FieldValue::STRING( &self.body )),
+            ("notes", FieldValue::STRING( &self.notes )),
+            ("priority", FieldValue::INTEGER( &self.priority )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for AdminObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("title", FieldValue::STRING( &self.title )),
+            ("body", FieldValue::STRING( &self.body )),
+            ("notes", FieldValue::STRING( &self.notes )),
+            ("priority", FieldValue::INTEGER( &self.priority )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("title", FieldValue::STRING( &self.title )),
+            ("body", FieldValue::STRING( &self.body )),
+            ("notes", FieldValue::STRING( &self.notes )),
+            ("priority", FieldValue::INTEGER( &self.priority ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "FieldObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "fieldObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS FieldObjects (
+            id UUID PRIMARY KEY,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            notes TEXT NOT NULL,
+            priority INTEGER NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn field_permission( field: &str ) -> FieldPermission {
+        field_permission( field )
+    }
+}
+
+#[async_trait]
+impl CrudConfig for AdminObject {
+    fn table_name() -> &'static str {
+        "FieldObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "adminFieldObjects"
+    }
+
+    fn schema() -> &'static str {
+        TestObject::schema()
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        matches!( verb, EndpointVerb::GET | EndpointVerb::PUT )
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ADMIN
+    }
+
+    fn field_permission( field: &str ) -> FieldPermission {
+        field_permission( field )
+    }
+}
+
+fn input( title: &str, body: &str, notes: &str, priority: i32 ) -> TestObjectInputParams {
+    TestObjectInputParams { title: title.to_string(), body: body.to_string(), notes: notes.to_string(), priority }
+}
+
+fn keys( object: &Value ) -> Vec<&str> {
+    let mut keys = object.as_object().unwrap().keys().map( String::as_str ).collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn test_field_permissions() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .resource::<AdminObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+    let admin = TestAuth::bearer( &auth.admin_token( "user_admin" ) );
+
+    let response = client.post("/fieldObjects").json( &input( "Plans", "Draft", "Private", 1 ) ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/fieldObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( keys( &objects[0] ), vec![ "body", "id", "notes", "title" ] );
+
+    let response = client.get("/fieldObjects").header( "Authorization", &jane ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( keys( &objects[0] ), vec![ "body", "id", "title" ] );
+
+    let response = client.get("/adminFieldObjects").header( "Authorization", &admin ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( keys( &objects[0] ), vec![ "body", "id", "notes", "priority", "title" ] );
+
+    // jane may only change the public body, the rest of her update is dropped
+    let response = client.put( &format!( "/fieldObjects/{}", id ) ).json( &input( "Renamed", "Jane's draft", "Jane's notes", 5 ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/adminFieldObjects").header( "Authorization", &admin ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( objects[0]["title"], "Plans" );
+    assert_eq!( objects[0]["body"], "Jane's draft" );
+    assert_eq!( objects[0]["notes"], "Private" );
+    assert_eq!( objects[0]["priority"], 1 );
+
+    let response = client.put( &format!( "/fieldObjects/{}", id ) ).json( &input( "Renamed", "Final", "John's notes", 5 ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.put( &format!( "/adminFieldObjects/{}", id ) ).json( &input( "Renamed", "Final", "Reviewed", 3 ) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/adminFieldObjects").header( "Authorization", &admin ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( objects[0]["title"], "Plans" );
+    assert_eq!( objects[0]["notes"], "Reviewed" );
+    assert_eq!( objects[0]["priority"], 3 );
+
+    let response = client.get("/fieldObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( objects[0]["notes"], "Reviewed" );
+    assert!( objects[0].get( "user_id" ).is_none() );
+}
@@ -4,8 +4,9 @@ use axum::async_trait;
 use tower_http::cors::{Any, CorsLayer};
 use serde::{Serialize, Deserialize};
 use sqlx::FromRow;
-use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, SchemaTrait, KeyValue, FieldValue};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue};
 use axum::Router;
+use crate::testing::TestDatabase;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -198,55 +199,22 @@ impl CrudConfig for TestObject {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct SchemaConfig {}
-impl SchemaTrait for SchemaConfig {
-    fn schema() -> String {
-        return "
-            CREATE TABLE IF NOT EXISTS TestObjects (
-                id UUID PRIMARY KEY,
-                name VARCHAR(50) NOT NULL,
-                age INT NOT NULL,
-                date_created TIMESTAMPTZ NOT NULL,
-                status VARCHAR(50) NOT NULL,
-                user_id VARCHAR(50) NOT NULL
-            );
-        ".to_string();
-    }
-}
+async fn app_test_setup() -> ( Router, TestDatabase ) {
+    let database = TestDatabase::from_env().await.unwrap();
+    database.apply::<TestObject>().await.unwrap();
 
-pub async fn app_test_setup() -> Router {
-    dotenv::dotenv().ok();
-    
-    let resp = crate::connect_to_db(
-                                                    std::env::var("DB_HOST").unwrap().as_str(), 
-                                                    5432,
-                                                    std::env::var("DB_USERNAME").unwrap().as_str(), 
-                                                    std::env::var("DB_PASSWORD").unwrap().as_str(), 
-                                                    std::env::var("DB_NAME").unwrap().as_str() 
-                                                )
-                                                .await;
-
-    let connection_pool = match resp {
-        Ok(pool) => pool,
-        Err(e) => panic!("Failed to connect to database: {:?}", e)
-    };
-    
-    Router::new()
+    let router = Router::new()
         .nest("/restful", crate::create_endpoint_router::<TestObject, TestObjectInputParams, TestObjectQueryParams>())
-        .nest("/tableCommands", crate::create_tables_router::<SchemaConfig>())
-        .layer(Extension(connection_pool))
-        .layer(CorsLayer::new().allow_origin(Any))
+        .layer(Extension(database.pool.clone()))
+        .layer(CorsLayer::new().allow_origin(Any));
+
+    ( router, database )
 }
 
 #[tokio::test]
 async fn test_post_any() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John Doe".to_string(), age: 30 }).send().await;
     assert_eq!(response.status(), 200);
@@ -272,17 +240,13 @@ async fn test_post_any() {
     println!("{:?}", objects);
     assert_eq!( objects[0].user_id, "nobody");
 
+    database.teardown().await.unwrap();
 }
 
 #[tokio::test]
 async fn test_get_any() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.get("/restful/testObjects?age=hello&myage=world").send().await;
     assert_eq!(response.status(), 400);
@@ -301,16 +265,14 @@ async fn test_get_any() {
 
     let objects: Vec<TestObject> = response.json().await;
     assert_eq!( objects[0].name, "John");
+
+    database.teardown().await.unwrap();
 }
 
 #[tokio::test]
 async fn test_put_any() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John".to_string(), age: 30 }).send().await;
     assert_eq!(response.status(), 200);
@@ -328,16 +290,14 @@ async fn test_put_any() {
 
     let response = client.put(&format!("/restful/testObjects/{}", id.to_string() )).json(&TestObjectInputParams { name: "John".to_string(), age: 101 }).send().await;
     assert_eq!(response.status(), 400);
+
+    database.teardown().await.unwrap();
 }
 
 #[tokio::test]
 async fn test_delete_any() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John".to_string(), age: 30 }).send().await;
     assert_eq!(response.status(), 200);
@@ -357,16 +317,14 @@ async fn test_delete_any() {
 
     let objects: Vec<TestObject> = response.json().await;
     assert!( objects.is_empty() );
+
+    database.teardown().await.unwrap();
 }
 
 #[tokio::test]
 async fn test_order_by_any() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John".to_string(), age: 30 }).send().await;
     assert_eq!(response.status(), 200);
@@ -385,22 +343,22 @@ async fn test_order_by_any() {
 
     let objects: Vec<TestObject> = response.json().await;
     assert_eq!( objects[0].age, 30);
+
+    database.teardown().await.unwrap();
 }
 
 #[tokio::test]
 async fn test_datetime() {
-    let client = TestClient::new( app_test_setup().await );
-
-    let response = client.post("/tableCommands/dropTable").json(&TestObject::table_name()).send().await;
-    assert_eq!(response.status(), 200);
-    let response = client.post("/tableCommands/initTables").send().await;
-    assert_eq!(response.status(), 200);
+    let ( app, database ) = app_test_setup().await;
+    let client = TestClient::new( app );
 
     let response = client.post("/restful/testObjects").json(&TestObjectInputParams { name: "John".to_string(), age: 30 }).send().await;
     assert_eq!(response.status(), 200);
 
 
-    let response = client.get( &format!("/restful/testObjects?date_created_gt=2023-11-15T03:09:35.682706Z&date_created_lt=2023-12-18T03:09:35.682706Z")).send().await;
+    let day_before = ( chrono::Utc::now() - chrono::Duration::days(1) ).format("%Y-%m-%dT%H:%M:%SZ");
+    let day_after = ( chrono::Utc::now() + chrono::Duration::days(1) ).format("%Y-%m-%dT%H:%M:%SZ");
+    let response = client.get( &format!("/restful/testObjects?date_created_gt={}&date_created_lt={}", day_before, day_after)).send().await;
     assert_eq!(response.status(), 200);
 
     let objects: Vec<TestObject> = response.json().await;
@@ -411,4 +369,6 @@ async fn test_datetime() {
 
     let objects: Vec<TestObject> = response.json().await;
     assert!( objects.is_empty() );
+
+    database.teardown().await.unwrap();
 }
\ No newline at end of file
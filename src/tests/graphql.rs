@@ -0,0 +1,244 @@
+use std::sync::Mutex;
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use axum::Json;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use sqlx::FromRow;
+use sqlx::postgres::PgPoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, Storage, FromStorageRow, KeyValue, FieldValue, FieldType, Janus, RecordId, RequestContext};
+use axum::Router;
+
+static RECORDED_FILTERS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    age: i32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String,
+    age: i32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    age_gt: Option<i32>,
+    order_dir: Option<String>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        self.age > 0
+    }
+
+    fn add_set_values(&self, _user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), age: self.age }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("id", FieldType::UUID),
+            ("name", FieldType::STRING),
+            ("age", FieldType::INTEGER)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING),
+            ("age", FieldType::INTEGER)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(age_gt) = &self.age_gt {
+            pairs.push( ("age_gt", FieldValue::INTEGER( age_gt )) );
+        }
+
+        if let Some(order_dir) = &self.order_dir {
+            pairs.push( ("order_dir", FieldValue::STRING( order_dir )) );
+        }
+
+        pairs
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING),
+            ("age_gt", FieldType::INTEGER),
+            ("order_dir", FieldType::STRING)
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "GraphqlObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "graphqlObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        !matches!( verb, EndpointVerb::PUT )
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::AUTHENTICATED,
+            _ => AccessPermission::ANY
+        }
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_create<S, T>( storage: &S, _context: &RequestContext, values: T ) -> Result<Json<RecordId>, StatusCode> where S: Storage, T : Send + Sync + Unpin + KeyValue {
+        Ok( Json( RecordId::UUID( uuid::Uuid::parse_str( "96fcebe4-fab9-484e-a28d-cbb1a6216b72" ).unwrap() ) ) )
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_read<S, T, QP>( storage: &S, _context: &RequestContext, filters: QP, user_id: Option<String> )
+        -> Result<Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
+        let mut recorded = RECORDED_FILTERS.lock().unwrap();
+        for (key, value) in filters.key_value_pairs() {
+            let value = match value {
+                FieldValue::STRING( value ) => value.clone(),
+                FieldValue::INTEGER( value ) => value.to_string(),
+                _ => String::new()
+            };
+            recorded.push( format!( "{}={}", key, value ) );
+        }
+
+        Ok( Json( vec![] ) )
+    }
+
+    #[allow(unused_variables)]
+    async fn custom_delete<S>( storage: &S, _context: &RequestContext, ids: Vec<RecordId>, user_id: Option<String> ) -> StatusCode where S: Storage {
+        StatusCode::OK
+    }
+}
+
+fn app_test_setup() -> Router {
+    let pool = PgPoolOptions::new().connect_lazy( "postgres://janus@localhost/janus" ).unwrap();
+
+    Janus::builder()
+        .pool( pool )
+        .graphql( "/graphql" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build()
+}
+
+#[tokio::test]
+async fn test_graphql_queries() {
+    let client = TestClient::new( app_test_setup() );
+
+    let response = client.post("/graphql").json(&json!({ "query": "{ janusResources graphqlObjects(name: \"John\", age_gt: 30, order_dir: \"asc\") { id name age } }" })).send().await;
+    assert_eq!(response.status(), 200);
+
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["data"]["janusResources"], json!([ "graphqlObjects" ]) );
+    assert_eq!( body["data"]["graphqlObjects"], json!([]) );
+    assert_eq!( *RECORDED_FILTERS.lock().unwrap(), vec![ "name=John".to_string(), "age_gt=30".to_string(), "order_dir=asc".to_string() ] );
+
+    let response = client.get("/graphql").send().await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_graphql_mutations() {
+    let client = TestClient::new( app_test_setup() );
+
+    let response = client.post("/graphql").json(&json!({ "query": "mutation { createTestObject(input: { name: \"John\", age: 30 }) }" })).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["data"]["createTestObject"], "96fcebe4-fab9-484e-a28d-cbb1a6216b72" );
+
+    let response = client.post("/graphql").json(&json!({ "query": "mutation { createTestObject(input: { name: \"John\", age: 0 }) }" })).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["errors"][0]["extensions"]["status"], 400 );
+
+    let response = client.post("/graphql").json(&json!({ "query": "mutation { deleteTestObject(id: \"96fcebe4-fab9-484e-a28d-cbb1a6216b72\") }" })).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["errors"][0]["message"], "No auth token" );
+    assert_eq!( body["errors"][0]["extensions"]["status"], 401 );
+
+    let response = client.post("/graphql").json(&json!({ "query": "mutation { updateTestObject(id: \"96fcebe4-fab9-484e-a28d-cbb1a6216b72\", input: { name: \"John\", age: 30 }) }" })).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert!( body["errors"][0]["message"].as_str().unwrap().contains( "updateTestObject" ) );
+}
+
+#[cfg(feature = "accounts")]
+#[tokio::test]
+async fn test_graphql_accounts() {
+    use crate::accounts::{AccountsConfig, AccountTokens, Credentials};
+    use crate::storage::MemoryStorage;
+    use crate::testing::TestAuth;
+
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .accounts( AccountsConfig::new( "/auth" ) )
+        .graphql( "/graphql" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    let response = client.post("/auth/signup").json( &Credentials { username: "john".to_string(), password: "correct horse".to_string() } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let login: AccountTokens = response.json().await;
+
+    let delete = json!({ "query": "mutation { deleteTestObject(id: \"96fcebe4-fab9-484e-a28d-cbb1a6216b72\") }" });
+    let response = client.post("/graphql").json( &delete ).header( "Authorization", TestAuth::bearer( &login.access_token ) ).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["errors"], serde_json::Value::Null );
+    assert_eq!( body["data"]["deleteTestObject"], true );
+
+    let response = client.post("/graphql").json( &delete ).send().await;
+    let body: serde_json::Value = response.json().await;
+    assert_eq!( body["errors"][0]["extensions"]["status"], 401 );
+}
@@ -0,0 +1,173 @@
+use std::any::Any;
+use std::sync::Mutex;
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, CrudError, RequestContext, KeyValue, FieldValue, Janus, ErrorFormat, RecordId, Storage};
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+static EVENTS: Mutex<Vec<String>> = Mutex::new( vec![] );
+
+fn record( event: String ) {
+    EVENTS.lock().unwrap().push( event );
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "HookObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "hookObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS HookObjects (
+            id UUID PRIMARY KEY,
+            name TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    async fn before_create<S: Storage>( _storage: &S, context: &RequestContext, values: &mut TestObject ) -> Result<(), CrudError> {
+        if values.name == "admin" {
+            return Err( CrudError::bad_request( "name is reserved" ) );
+        }
+        values.name = values.name.trim().to_string();
+        record( format!( "before_create {:?}", context.user_id ) );
+        Ok(())
+    }
+
+    async fn after_create<S: Storage>( _storage: &S, _context: &RequestContext, id: &RecordId, values: &TestObject ) -> Result<(), CrudError> {
+        if values.name == "draft" {
+            return Err( CrudError::conflict( "drafts are not kept" ) );
+        }
+        record( format!( "after_create {} {}", id, values.name ) );
+        Ok(())
+    }
+
+    async fn before_update<S: Storage, UP: Send + Sync + KeyValue + 'static>( _storage: &S, _context: &RequestContext, _id: &RecordId, values: &mut UP ) -> Result<(), CrudError> {
+        if let Some( input ) = ( values as &mut dyn Any ).downcast_mut::<TestObjectInputParams>() {
+            input.name = input.name.to_uppercase();
+        }
+        Ok(())
+    }
+
+    async fn before_delete<S: Storage>( _storage: &S, _context: &RequestContext, _id: &RecordId ) -> Result<(), CrudError> {
+        Err( CrudError::conflict( "records are archived, not deleted" ) )
+    }
+}
+
+#[tokio::test]
+async fn test_lifecycle_hooks() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .error_format( ErrorFormat::JSON )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+
+    let response = client.post("/hookObjects").json( &TestObjectInputParams { name: "admin".to_string() } ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+    assert_eq!( response.json::<Value>().await, json!({ "status": 400, "error": "name is reserved" }) );
+
+    let response = client.post("/hookObjects").json( &TestObjectInputParams { name: "  Plans ".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+    assert_eq!( *EVENTS.lock().unwrap(), vec![ "before_create Some(\"user_john\")".to_string(), format!( "after_create {} Plans", id ) ] );
+
+    let response = client.put( &format!( "/hookObjects/{}", id ) ).json( &TestObjectInputParams { name: "final plans".to_string() } ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/hookObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects[0].name, "FINAL PLANS" );
+
+    let response = client.delete( &format!( "/hookObjects/{}", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+    assert_eq!( response.json::<Value>().await["error"], "records are archived, not deleted" );
+
+    let response = client.get("/hookObjects").header( "Authorization", &john ).send().await;
+    assert_eq!( response.json::<Vec<TestObject>>().await.len(), 1 );
+
+    // a failing after hook rolls back the insert it followed
+    let response = client.post("/hookObjects").json( &TestObjectInputParams { name: "draft".to_string() } ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+
+    let response = client.get("/hookObjects").header( "Authorization", &john ).send().await;
+    assert_eq!( response.json::<Vec<TestObject>>().await.len(), 1 );
+}
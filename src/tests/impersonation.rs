@@ -0,0 +1,272 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::extractors::{ ACT_AS_HEADER, ACT_AS_TENANT_HEADER, ACT_AS_TENANT_ROLE_HEADER };
+use crate::impersonation::{ Impersonation, IMPERSONATIONS_TABLE };
+use crate::storage::{ MemoryStorage, Storage, FromStorageRow, ReadQuery };
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "ImpersonationObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "impersonationObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ADMIN
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TenantObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String,
+    org_id: String
+}
+
+impl InputSerializer<TenantObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TenantObject {
+        TenantObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap(),
+            org_id: String::new()
+        }
+    }
+}
+
+impl KeyValue for TenantObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id )),
+            ("org_id", FieldValue::STRING( &self.org_id ))
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TenantObject {
+    fn table_name() -> &'static str {
+        "ImpersonationTenantObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "impersonationTenantObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( verb: &EndpointVerb ) -> ObjectPermission {
+        match verb {
+            EndpointVerb::DELETE => ObjectPermission::TENANTADMIN,
+            _ => ObjectPermission::TENANT
+        }
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ADMIN
+    }
+
+    fn tenant_column() -> &'static str {
+        "org_id"
+    }
+}
+
+#[tokio::test]
+async fn test_admin_impersonation() {
+    let auth = TestAuth::start().await.unwrap();
+    let storage = MemoryStorage::new();
+    let app = Janus::with_storage( storage.clone() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let admin = TestAuth::bearer( &auth.admin_token_with( "user_admin", &[ "read:admin", "impersonate:users" ] ) );
+    let plain_admin = TestAuth::bearer( &auth.admin_token( "user_plain" ) );
+
+    let response = client.post("/impersonationObjects").json( &TestObjectInputParams { name: "John's".to_string() } )
+        .header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_john" ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let john_id = response.json::<uuid::Uuid>().await;
+
+    let response = client.post("/impersonationObjects").json( &TestObjectInputParams { name: "Jane's".to_string() } )
+        .header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_jane" ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/impersonationObjects").header( "Authorization", &plain_admin ).send().await;
+    let mut owners = response.json::<Vec<TestObject>>().await.into_iter().map( |object| object.user_id ).collect::<Vec<_>>();
+    owners.sort();
+    assert_eq!( owners, vec![ "user_jane", "user_john" ] );
+
+    let response = client.get("/impersonationObjects").header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_jane" ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].user_id, "user_jane" );
+
+    let response = client.get("/impersonationObjects").header( "Authorization", &plain_admin ).header( ACT_AS_HEADER, "user_jane" ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/impersonationObjects/{}", john_id ) ).header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_jane" ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.put( &format!( "/impersonationObjects/{}", john_id ) ).json( &TestObjectInputParams { name: "Renamed".to_string() } )
+        .header( "Authorization", &plain_admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/impersonationObjects/{}", john_id ) ).header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_john" ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let query = ReadQuery {
+        table: IMPERSONATIONS_TABLE,
+        filters: vec![],
+        owner: None,
+        order_by: "date_created",
+        descending: false,
+        limit: None,
+        offset: None
+    };
+    let records = storage.select( &query ).await.unwrap().into_iter().map( <Impersonation as FromStorageRow<MemoryStorage>>::from_storage_row ).collect::<Result<Vec<_>, _>>().unwrap();
+    // jane's refused delete of john's object rolled its audit row back with it
+    assert_eq!( records.len(), 4 );
+    assert!( !records.iter().any( |record| record.verb == "DELETE" && record.user_id == "user_jane" ) );
+    assert!( records.iter().all( |record| record.admin_id == "user_admin" ) );
+    assert!( records.iter().any( |record| record.verb == "DELETE" && record.user_id == "user_john" && record.object_id == Some( john_id.to_string() ) ) );
+}
+
+#[tokio::test]
+async fn test_tenant_impersonation() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource::<TenantObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let admin = TestAuth::bearer( &auth.admin_token_with( "user_admin", &[ "read:admin", "impersonate:users" ] ) );
+    let plain_admin = TestAuth::bearer( &auth.admin_token( "user_plain" ) );
+
+    let response = client.post("/impersonationTenantObjects").json( &TestObjectInputParams { name: "Alice's".to_string() } )
+        .header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_alice" ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.post("/impersonationTenantObjects").json( &TestObjectInputParams { name: "Alice's".to_string() } )
+        .header( "Authorization", &admin ).header( ACT_AS_HEADER, "user_alice" ).header( ACT_AS_TENANT_HEADER, "org_a" ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/impersonationTenantObjects").header( "Authorization", &admin )
+        .header( ACT_AS_HEADER, "user_bob" ).header( ACT_AS_TENANT_HEADER, "org_a" ).send().await;
+    let objects: Vec<TenantObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].org_id, "org_a" );
+
+    let response = client.get("/impersonationTenantObjects").header( "Authorization", &admin )
+        .header( ACT_AS_HEADER, "user_carol" ).header( ACT_AS_TENANT_HEADER, "org_b" ).send().await;
+    assert!( response.json::<Vec<TenantObject>>().await.is_empty() );
+
+    let response = client.get("/impersonationTenantObjects").header( "Authorization", &admin ).header( ACT_AS_TENANT_HEADER, "org_a" ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/impersonationTenantObjects").header( "Authorization", &plain_admin )
+        .header( ACT_AS_HEADER, "user_bob" ).header( ACT_AS_TENANT_HEADER, "org_a" ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/impersonationTenantObjects/{}", id ) ).header( "Authorization", &admin )
+        .header( ACT_AS_HEADER, "user_bob" ).header( ACT_AS_TENANT_HEADER, "org_a" ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/impersonationTenantObjects/{}", id ) ).header( "Authorization", &admin )
+        .header( ACT_AS_HEADER, "user_bob" ).header( ACT_AS_TENANT_HEADER, "org_a" ).header( ACT_AS_TENANT_ROLE_HEADER, "org:admin" ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+}
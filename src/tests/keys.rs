@@ -0,0 +1,275 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePoolOptions;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, KeyType, PrimaryKey, RecordId};
+use crate::storage::{MemoryStorage, Owner, Dialect, sql};
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Ticket {
+    number: i64,
+    title: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TicketInputParams {
+    title: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmptyQueryParams {}
+
+impl InputSerializer<Ticket> for TicketInputParams {
+    fn verify(&self) -> bool {
+        !self.title.is_empty()
+    }
+
+    // the storage fills in the serial number
+    fn add_set_values(&self, _user_id: Option<String>) -> Ticket {
+        Ticket { number: 0, title: self.title.clone() }
+    }
+}
+
+impl KeyValue for Ticket {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("title", FieldValue::STRING( &self.title ))
+        ]
+    }
+}
+
+impl KeyValue for TicketInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("title", FieldValue::STRING( &self.title ))
+        ]
+    }
+}
+
+impl KeyValue for EmptyQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for Ticket {
+    fn table_name() -> &'static str {
+        "Tickets"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "tickets"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ANY
+    }
+
+    fn primary_key() -> PrimaryKey {
+        PrimaryKey { columns: &[ ( "number", KeyType::SERIAL ) ] }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct Page {
+    org: String,
+    slug: String,
+    title: String,
+    author: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PageInputParams {
+    org: String,
+    slug: String,
+    title: String
+}
+
+impl InputSerializer<Page> for PageInputParams {
+    fn verify(&self) -> bool {
+        !self.slug.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> Page {
+        Page { org: self.org.clone(), slug: self.slug.clone(), title: self.title.clone(), author: user_id.unwrap() }
+    }
+}
+
+impl KeyValue for Page {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("org", FieldValue::STRING( &self.org )),
+            ("slug", FieldValue::STRING( &self.slug )),
+            ("title", FieldValue::STRING( &self.title )),
+            ("author", FieldValue::STRING( &self.author ))
+        ]
+    }
+}
+
+impl KeyValue for PageInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("title", FieldValue::STRING( &self.title ))
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for Page {
+    fn table_name() -> &'static str {
+        "Pages"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "pages"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS Pages (
+                org TEXT NOT NULL,
+                slug TEXT NOT NULL,
+                title TEXT NOT NULL,
+                author TEXT NOT NULL,
+                PRIMARY KEY ( org, slug )
+            );
+        "
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn owner_column() -> &'static str {
+        "author"
+    }
+
+    fn primary_key() -> PrimaryKey {
+        PrimaryKey { columns: &[ ( "org", KeyType::TEXT ), ( "slug", KeyType::TEXT ) ] }
+    }
+}
+
+#[test]
+fn test_key_statements() {
+    let key = Page::primary_key();
+    let id = key.parse_str( "acme/about" ).unwrap();
+    assert_eq!( id, RecordId::COMPOSITE( vec![ RecordId::TEXT( "acme".to_string() ), RecordId::TEXT( "about".to_string() ) ] ) );
+    assert_eq!( id.to_string(), "acme/about" );
+    assert_eq!( key.path(), ":org/:slug" );
+
+    let owner = Owner { column: "author", user_id: "user_1".to_string(), shared: None };
+    let title = "About".to_string();
+    let values = vec![ ("title", FieldValue::STRING( &title )) ];
+    assert_eq!( sql::update( Dialect::POSTGRES, "Pages", key, &id, &values, &[], Some( &owner ) ).unwrap().sql, "UPDATE Pages SET title = $1 WHERE org = $2 AND slug = $3 AND author = $4" );
+    assert_eq!( sql::delete( Dialect::SQLITE, "Pages", key, &[ id.clone(), id ], None ).unwrap().sql, "DELETE FROM Pages WHERE ( ( org = ? AND slug = ? ) OR ( org = ? AND slug = ? ) )" );
+    assert_eq!( sql::insert( Dialect::POSTGRES, "Pages", key, &values ).sql, "INSERT INTO Pages ( title ) VALUES ( $1 ) RETURNING org, slug" );
+
+    let ticket = Ticket::primary_key();
+    assert_eq!( ticket.parse( &[ "12".to_string() ] ), Ok( RecordId::INTEGER( 12 ) ) );
+    assert_eq!( ticket.parse( &[ "twelve".to_string() ] ), Err( StatusCode::BAD_REQUEST ) );
+    assert_eq!( sql::update( Dialect::POSTGRES, "Tickets", ticket, &RecordId::TEXT( "12".to_string() ), &values, &[], None ).err(), Some( StatusCode::BAD_REQUEST ) );
+}
+
+#[tokio::test]
+async fn test_serial_key() {
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .resource::<Ticket, TicketInputParams, EmptyQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    for ( title, number ) in [ ( "First", 1 ), ( "Second", 2 ) ] {
+        let response = client.post("/tickets").json( &TicketInputParams { title: title.to_string() } ).send().await;
+        assert_eq!( response.json::<i64>().await, number );
+    }
+
+    let response = client.put("/tickets/2").json( &TicketInputParams { title: "Renamed".to_string() } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.put("/tickets/two").json( &TicketInputParams { title: "Renamed".to_string() } ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/tickets").send().await;
+    let tickets: Vec<Ticket> = response.json().await;
+    assert_eq!( tickets.iter().map( |ticket| ticket.number ).collect::<Vec<_>>(), vec![ 2, 1 ] );
+    assert_eq!( tickets[0].title, "Renamed" );
+
+    let response = client.delete("/tickets/1").send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/tickets").send().await;
+    assert_eq!( response.json::<Vec<Ticket>>().await.len(), 1 );
+}
+
+#[tokio::test]
+async fn test_composite_key() {
+    let auth = TestAuth::start().await.unwrap();
+    let pool = SqlitePoolOptions::new().max_connections( 1 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, Page::schema() ).await.unwrap();
+
+    let app = Janus::with_storage( pool )
+        .auth( auth.config() )
+        .resource::<Page, PageInputParams, EmptyQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+
+    let input = PageInputParams { org: "acme".to_string(), slug: "about".to_string(), title: "About".to_string() };
+    let response = client.post("/pages").json( &input ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert_eq!( response.json::<RecordId>().await, RecordId::COMPOSITE( vec![ RecordId::TEXT( "acme".to_string() ), RecordId::TEXT( "about".to_string() ) ] ) );
+
+    let rename = PageInputParams { title: "About us".to_string(), ..input.clone() };
+    let response = client.put("/pages/acme/about").json( &rename ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.put("/pages/acme/about").json( &rename ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/pages").header( "Authorization", &john ).send().await;
+    let pages: Vec<Page> = response.json().await;
+    assert_eq!( pages.len(), 1 );
+    assert_eq!( pages[0].title, "About us" );
+    assert_eq!( pages[0].author, "user_john" );
+
+    let response = client.get("/pages").header( "Authorization", &jane ).send().await;
+    assert!( response.json::<Vec<Page>>().await.is_empty() );
+
+    let response = client.delete("/pages/acme/contact").header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.delete("/pages/acme/about").header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+}
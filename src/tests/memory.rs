@@ -0,0 +1,219 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use axum::{Extension, Router};
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Storage, RecordId};
+use crate::keys::UUID_KEY;
+use crate::storage::{MemoryStorage, ReadQuery, Owner};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    age: i32,
+    date_created: chrono::DateTime<chrono::Utc>,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String,
+    age: i32
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    age_ge: Option<i32>,
+    date_created_lt: Option<chrono::DateTime<chrono::Utc>>,
+    order_by: Option<String>,
+    order_dir: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            age: self.age,
+            date_created: chrono::Utc::now(),
+            user_id: user_id.unwrap_or( "nobody".to_string() )
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age )),
+            ("date_created", FieldValue::DATE( &self.date_created )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(age_ge) = &self.age_ge {
+            pairs.push( ("age_ge", FieldValue::INTEGER( age_ge )) );
+        }
+
+        if let Some(date_created_lt) = &self.date_created_lt {
+            pairs.push( ("date_created_lt", FieldValue::DATE( date_created_lt )) );
+        }
+
+        if let Some(order_by) = &self.order_by {
+            pairs.push( ("order_by", FieldValue::STRING( order_by )) );
+        }
+
+        if let Some(order_dir) = &self.order_dir {
+            pairs.push( ("order_dir", FieldValue::STRING( order_dir )) );
+        }
+
+        if let Some(limit) = &self.limit {
+            pairs.push( ("limit", FieldValue::INTEGER( limit )) );
+        }
+
+        if let Some(offset) = &self.offset {
+            pairs.push( ("offset", FieldValue::INTEGER( offset )) );
+        }
+
+        pairs
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "MemoryObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "memoryObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ANY
+    }
+}
+
+fn app_test_setup( storage: MemoryStorage ) -> Router {
+    crate::create_storage_router::<MemoryStorage, TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .layer( Extension( storage ) )
+}
+
+#[tokio::test]
+async fn test_memory_router() {
+    let storage = MemoryStorage::new();
+    let client = TestClient::new( app_test_setup( storage.clone() ) );
+
+    let mut ids = vec![];
+    for ( name, age ) in [ ( "John", 31 ), ( "Jane", 25 ), ( "Jack", 42 ), ( "Jill", 31 ) ] {
+        let response = client.post("/memoryObjects").json( &TestObjectInputParams { name: name.to_string(), age } ).send().await;
+        assert_eq!( response.status(), StatusCode::OK );
+        ids.push( response.json::<uuid::Uuid>().await );
+    }
+    assert_eq!( storage.rows( "MemoryObjects" ).len(), 4 );
+
+    let response = client.post("/memoryObjects").json( &TestObjectInputParams { name: "".to_string(), age: 1 } ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/memoryObjects?age_ge=31&order_by=age&order_dir=asc").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.iter().map( |object| object.age ).collect::<Vec<_>>(), vec![ 31, 31, 42 ] );
+
+    let response = client.get("/memoryObjects?order_by=name&order_dir=asc&limit=2&offset=1").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.iter().map( |object| object.name.as_str() ).collect::<Vec<_>>(), vec![ "Jane", "Jill" ] );
+
+    let response = client.get("/memoryObjects?order_by=name").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.iter().map( |object| object.name.as_str() ).collect::<Vec<_>>(), vec![ "John", "Jill", "Jane", "Jack" ] );
+
+    let response = client.get("/memoryObjects?date_created_lt=2000-01-01T00:00:00Z").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert!( objects.is_empty() );
+
+    let response = client.put( &format!( "/memoryObjects/{}", ids[1] ) ).json( &TestObjectInputParams { name: "Janet".to_string(), age: 26 } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.put( &format!( "/memoryObjects/{}", uuid::Uuid::new_v4() ) ).json( &TestObjectInputParams { name: "Nobody".to_string(), age: 1 } ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/memoryObjects?name=Janet").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].id, ids[1] );
+    assert_eq!( objects[0].age, 26 );
+
+    let response = client.delete( &format!( "/memoryObjects/{}", ids[2] ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/memoryObjects/{}", ids[2] ) ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+    assert_eq!( storage.rows( "MemoryObjects" ).len(), 3 );
+}
+
+#[tokio::test]
+async fn test_memory_owner_scope() {
+    let storage = MemoryStorage::new();
+    let mine = TestObjectInputParams { name: "Mine".to_string(), age: 1 }.add_set_values( Some( "user_1".to_string() ) );
+    let theirs = TestObjectInputParams { name: "Theirs".to_string(), age: 2 }.add_set_values( Some( "user_2".to_string() ) );
+    storage.insert( "MemoryObjects", UUID_KEY, &mine.key_value_pairs() ).await.unwrap();
+    storage.insert( "MemoryObjects", UUID_KEY, &theirs.key_value_pairs() ).await.unwrap();
+
+    let owner = Owner { column: "user_id", user_id: "user_1".to_string(), shared: None };
+    let filters = TestObjectQueryParams::default();
+    let query = ReadQuery::parse( "MemoryObjects", &filters, Some( owner.clone() ) ).unwrap();
+    let rows = storage.select( &query ).await.unwrap();
+    assert_eq!( rows.len(), 1 );
+
+    let update = TestObjectInputParams { name: "Stolen".to_string(), age: 3 };
+    assert_eq!( storage.update( "MemoryObjects", UUID_KEY, &RecordId::from( theirs.id ), &update.key_value_pairs(), Some( &owner ) ).await, Ok( 0 ) );
+    assert_eq!( storage.update( "MemoryObjects", UUID_KEY, &RecordId::from( mine.id ), &update.key_value_pairs(), Some( &owner ) ).await, Ok( 1 ) );
+
+    assert_eq!( storage.delete( "MemoryObjects", UUID_KEY, &[ RecordId::from( mine.id ), RecordId::from( theirs.id ) ], Some( &owner ) ).await, Ok( 1 ) );
+    assert_eq!( storage.rows( "MemoryObjects" ).len(), 1 );
+
+    assert_eq!( storage.insert( "MemoryObjects", UUID_KEY, &theirs.key_value_pairs() ).await, Err( StatusCode::INTERNAL_SERVER_ERROR ) );
+}
@@ -6,3 +6,78 @@ mod general;
 
 #[cfg(test)]
 mod permissions;
+
+#[cfg(test)]
+mod builder;
+
+#[cfg(test)]
+mod openapi;
+
+#[cfg(test)]
+mod typescript;
+
+#[cfg(test)]
+mod client;
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql;
+
+#[cfg(test)]
+mod storage;
+
+#[cfg(test)]
+mod memory;
+
+#[cfg(test)]
+mod testing;
+
+#[cfg(test)]
+mod dev;
+
+#[cfg(test)]
+mod api_keys;
+
+#[cfg(all(test, feature = "accounts"))]
+mod accounts;
+
+#[cfg(test)]
+mod revocations;
+
+#[cfg(test)]
+mod optional;
+
+#[cfg(test)]
+mod impersonation;
+
+#[cfg(test)]
+mod tenants;
+
+#[cfg(test)]
+mod sharing;
+
+#[cfg(test)]
+mod rls;
+
+#[cfg(test)]
+mod keys;
+
+#[cfg(test)]
+mod fields;
+
+#[cfg(test)]
+mod outputs;
+
+#[cfg(test)]
+mod hooks;
+
+#[cfg(test)]
+mod context;
+
+#[cfg(test)]
+mod actions;
+
+#[cfg(test)]
+mod transitions;
+
+#[cfg(test)]
+mod updates;
@@ -0,0 +1,198 @@
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, FieldType, Janus};
+use crate::openapi::{OpenApiConfig, DocsUi};
+use axum::Router;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    age: i32,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String,
+    age: i32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    age_gt: Option<i32>,
+    order_dir: Option<String>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            age: self.age,
+            user_id: match user_id { Some(id) => id, None => "nobody".to_string() }
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("id", FieldType::UUID),
+            ("name", FieldType::STRING),
+            ("age", FieldType::INTEGER),
+            ("user_id", FieldType::STRING)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING),
+            ("age", FieldType::INTEGER)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(age_gt) = &self.age_gt {
+            pairs.push( ("age_gt", FieldValue::INTEGER( age_gt )) );
+        }
+
+        if let Some(order_dir) = &self.order_dir {
+            pairs.push( ("order_dir", FieldValue::STRING( order_dir )) );
+        }
+
+        pairs
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING),
+            ("age_gt", FieldType::INTEGER),
+            ("order_dir", FieldType::STRING)
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "OpenApiObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "openApiObjects"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS OpenApiObjects (
+                id UUID PRIMARY KEY,
+                name VARCHAR(50) NOT NULL,
+                age INT NOT NULL,
+                user_id VARCHAR(50) NOT NULL
+            );
+        "
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::GET => AccessPermission::ANY,
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+}
+
+fn app_test_setup() -> Router {
+    Janus::builder()
+        .prefix( "/restful" )
+        .openapi( OpenApiConfig::new( "Test API", "1.0.0" ).docs( DocsUi::SWAGGER, "/docs" ) )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build()
+}
+
+#[tokio::test]
+async fn test_openapi_document() {
+    let client = TestClient::new( app_test_setup() );
+
+    let response = client.get("/openapi.json").send().await;
+    assert_eq!(response.status(), 200);
+
+    let document: serde_json::Value = response.json().await;
+    assert_eq!( document["info"]["title"], "Test API" );
+
+    let collection = &document["paths"]["/restful/openApiObjects"];
+    let parameters = collection["get"]["parameters"].as_array().unwrap();
+    assert_eq!( parameters.len(), 3 );
+    assert_eq!( parameters[1]["name"], "age_gt" );
+    assert_eq!( parameters[1]["schema"]["type"], "integer" );
+    assert_eq!( parameters[2]["schema"]["enum"][1], "desc" );
+    assert!( collection["get"].get("security").is_none() );
+    assert_eq!( collection["get"]["responses"]["200"]["content"]["application/json"]["schema"]["items"]["$ref"], "#/components/schemas/TestObject" );
+
+    assert_eq!( collection["post"]["security"][0]["userAuth"], serde_json::json!([]) );
+    assert_eq!( collection["post"]["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/TestObjectInputParams" );
+
+    let item = &document["paths"]["/restful/openApiObjects/{id}"];
+    assert_eq!( item["put"]["parameters"][0]["name"], "id" );
+    assert_eq!( item["delete"]["security"][0]["adminAuth"], serde_json::json!([]) );
+    assert_eq!( item["delete"]["parameters"][1]["name"], "X-Act-As-User" );
+
+    let model = &document["components"]["schemas"]["TestObject"];
+    assert_eq!( model["properties"]["id"]["format"], "uuid" );
+    assert_eq!( document["components"]["schemas"]["TestObjectInputParams"]["required"], serde_json::json!([ "name", "age" ]) );
+}
+
+#[tokio::test]
+async fn test_docs_page() {
+    let client = TestClient::new( app_test_setup() );
+
+    let response = client.get("/docs").send().await;
+    assert_eq!(response.status(), 200);
+    assert!( response.text().await.contains( "/openapi.json" ) );
+}
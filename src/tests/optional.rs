@@ -0,0 +1,143 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::openapi::OpenApiConfig;
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap_or( "anonymous".to_string() )
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "OptionalObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "optionalObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::OPTIONAL
+    }
+}
+
+#[tokio::test]
+async fn test_optional_access() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .openapi( OpenApiConfig::new( "Optional", "1.0.0" ) )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+
+    let response = client.post("/optionalObjects").json( &TestObjectInputParams { name: "Public".to_string() } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/optionalObjects").json( &TestObjectInputParams { name: "John's".to_string() } ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/optionalObjects").send().await;
+    let mut owners = response.json::<Vec<TestObject>>().await.into_iter().map( |object| object.user_id ).collect::<Vec<_>>();
+    owners.sort();
+    assert_eq!( owners, vec![ "anonymous", "user_john" ] );
+
+    let response = client.get("/optionalObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].id, id );
+
+    let response = client.get("/optionalObjects").header( "Authorization", "Bearer not.a.token" ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    // reads may be anonymous, writes to owned rows may not
+    let response = client.put( &format!( "/optionalObjects/{}", id ) ).json( &TestObjectInputParams { name: "Anyone's".to_string() } ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.delete( &format!( "/optionalObjects/{}", id ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+    let response = client.delete( &format!( "/optionalObjects/{}", id ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.delete( &format!( "/optionalObjects/{}", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/openapi.json").send().await;
+    let document: serde_json::Value = response.json().await;
+    assert_eq!( document["paths"]["/optionalObjects"]["get"]["security"][0], serde_json::json!({}) );
+    assert_eq!( document["paths"]["/optionalObjects"]["get"]["security"][1]["userAuth"], serde_json::json!([]) );
+}
@@ -0,0 +1,181 @@
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, FieldPermission, InputSerializer, OutputSerializer, RequestContext, CrudConfig, KeyValue, FieldValue, FieldType, Janus};
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    first_name: String,
+    last_name: String,
+    notes: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectOutput {
+    id: uuid::Uuid,
+    name: String,
+    notes: String,
+    mine: bool
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    first_name: String,
+    last_name: String,
+    notes: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.first_name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            first_name: self.first_name.clone(),
+            last_name: self.last_name.clone(),
+            notes: self.notes.clone(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl OutputSerializer<TestObject> for TestObjectOutput {
+    fn from_row( row: TestObject, context: &RequestContext ) -> TestObjectOutput {
+        TestObjectOutput {
+            id: row.id,
+            name: format!( "{} {}", row.first_name, row.last_name ),
+            notes: row.notes,
+            mine: context.user_id.as_deref() == Some( row.user_id.as_str() )
+        }
+    }
+
+    fn output_fields() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("id", FieldType::UUID),
+            ("name", FieldType::STRING),
+            ("notes", FieldType::STRING),
+            ("mine", FieldType::BOOLEAN)
+        ]
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("first_name", FieldValue::STRING( &self.first_name )),
+            ("last_name", FieldValue::STRING( &self.last_name )),
+            ("notes", FieldValue::STRING( &self.notes )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("id", FieldType::UUID),
+            ("first_name", FieldType::STRING),
+            ("last_name", FieldType::STRING),
+            ("notes", FieldType::STRING),
+            ("user_id", FieldType::STRING)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("first_name", FieldValue::STRING( &self.first_name )),
+            ("last_name", FieldValue::STRING( &self.last_name )),
+            ("notes", FieldValue::STRING( &self.notes ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "OutputObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "outputObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS OutputObjects (
+            id UUID PRIMARY KEY,
+            first_name TEXT NOT NULL,
+            last_name TEXT NOT NULL,
+            notes TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn field_permission( field: &str ) -> FieldPermission {
+        match field {
+            "notes" => FieldPermission::OWNERONLY,
+            _ => FieldPermission::PUBLIC
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_output_serializer() {
+    let auth = TestAuth::start().await.unwrap();
+    let builder = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource_with_output::<TestObject, TestObjectInputParams, TestObjectQueryParams, TestObjectOutput>();
+
+    let info = builder.registry().get( "outputObjects" ).unwrap();
+    assert_eq!( info.model.name, "TestObject" );
+    assert_eq!( info.model.fields.iter().map( |( name, _ )| *name ).collect::<Vec<_>>(), vec![ "id", "name", "notes", "mine" ] );
+
+    let client = TestClient::new( builder.build() );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+
+    let input = TestObjectInputParams { first_name: "John".to_string(), last_name: "Doe".to_string(), notes: "Private".to_string() };
+    let response = client.post("/outputObjects").json( &input ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/outputObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( objects, vec![ json!({ "id": id, "name": "John Doe", "notes": "Private", "mine": true }) ] );
+
+    // field rules still apply to the output keys, decided by who owns the stored row
+    let response = client.get("/outputObjects").header( "Authorization", &jane ).send().await;
+    let objects: Vec<Value> = response.json().await;
+    assert_eq!( objects, vec![ json!({ "id": id, "name": "John Doe", "mine": false }) ] );
+}
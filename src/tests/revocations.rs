@@ -0,0 +1,232 @@
+use std::time::Duration;
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::revocations::{Revocations, Revocation, RevocationSchema, REVOCATIONS_TABLE};
+use crate::keys::UUID_KEY;
+use crate::api_keys::{CreateApiKey, CreatedApiKey};
+use crate::storage::{MemoryStorage, Storage};
+use crate::testing::{TestAuth, TestDatabase};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), user_id: user_id.unwrap_or_default() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "RevocationObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "revocationObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( verb: &EndpointVerb ) -> AccessPermission {
+        match verb {
+            EndpointVerb::DELETE => AccessPermission::ADMIN,
+            _ => AccessPermission::AUTHENTICATED
+        }
+    }
+}
+
+fn token_id( token: &str ) -> String {
+    let payload = URL_SAFE_NO_PAD.decode( token.split( '.' ).nth( 1 ).unwrap() ).unwrap();
+    let claims: serde_json::Value = serde_json::from_slice( &payload ).unwrap();
+    claims["jti"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_revocation_cache() {
+    let storage = MemoryStorage::new();
+    let revocations = Revocations::new( storage.clone() );
+    let other_instance = Revocations::new( storage.clone() ).ttl( Duration::ZERO );
+    let now = chrono::Utc::now().timestamp() as u64;
+
+    assert_eq!( other_instance.is_revoked( Some( "token_1" ), "user_1", Some( now ) ).await, Ok( false ) );
+
+    revocations.revoke_token( "token_1" ).await.unwrap();
+    assert_eq!( revocations.is_revoked( Some( "token_1" ), "user_1", Some( now ) ).await, Ok( true ) );
+    assert_eq!( revocations.is_revoked( Some( "token_2" ), "user_1", Some( now ) ).await, Ok( false ) );
+    assert_eq!( other_instance.is_revoked( Some( "token_1" ), "user_1", Some( now ) ).await, Ok( true ) );
+
+    revocations.revoke_user( "user_2" ).await.unwrap();
+    assert_eq!( revocations.is_revoked( None, "user_2", Some( now - 60 ) ).await, Ok( true ) );
+    assert_eq!( revocations.is_revoked( None, "user_2", None ).await, Ok( true ) );
+    assert_eq!( revocations.is_revoked( None, "user_2", Some( now + 60 ) ).await, Ok( false ) );
+    assert_eq!( revocations.is_revoked( None, "user_1", Some( now - 60 ) ).await, Ok( false ) );
+
+    let stored: Vec<Revocation> = revocations.list().await.unwrap();
+    assert_eq!( stored.len(), 2 );
+
+    // once loaded, reloads only read rows from around the newest one already seen
+    let backdated = chrono::Utc::now() - chrono::Duration::hours( 1 );
+    let old = Revocation { id: uuid::Uuid::new_v4(), jti: Some( "token_3".to_string() ), subject: None, revoked_before: None, date_created: backdated };
+    storage.insert( REVOCATIONS_TABLE, UUID_KEY, &old.key_value_pairs() ).await.unwrap();
+    revocations.revoke_token( "token_4" ).await.unwrap();
+    assert_eq!( other_instance.is_revoked( Some( "token_4" ), "user_1", Some( now ) ).await, Ok( true ) );
+    assert_eq!( other_instance.is_revoked( Some( "token_3" ), "user_1", Some( now ) ).await, Ok( false ) );
+    assert_eq!( other_instance.is_revoked( None, "user_2", Some( now - 60 ) ).await, Ok( true ) );
+}
+
+#[tokio::test]
+async fn test_revocation_routes() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .revocations( "/revocations" )
+        .api_keys( "/apiKeys" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let admin = TestAuth::bearer( &auth.admin_token( "admin_1" ) );
+    let key_request = CreateApiKey { user_id: "user_john".to_string(), name: "partner".to_string(), scopes: vec![ "*:GET".to_string() ], expires_at: None };
+
+    let john = auth.user_token( "user_john" );
+    let john_again = auth.user_token( "user_john" );
+    let jane = auth.user_token( "user_jane" );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &john ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/revocations").json( &json!({ "jti": token_id( &john ) }) ).header( "Authorization", TestAuth::bearer( &jane ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.post("/revocations").json( &json!({}) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post("/revocations").json( &json!({ "jti": token_id( &john ) }) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &john ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &john_again ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let subscription = auth.subscription_token( "user_jane", "pro" );
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &jane ) ).header( "Subscription", TestAuth::bearer( &subscription ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/revocations").json( &json!({ "jti": token_id( &subscription ) }) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &jane ) ).header( "Subscription", TestAuth::bearer( &subscription ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &jane ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/apiKeys").json( &key_request ).header( "Authorization", &admin ).send().await;
+    let john_key: CreatedApiKey = response.json().await;
+    let response = client.get("/revocationObjects").header( "X-Api-Key", &john_key.key ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let john_admin = TestAuth::bearer( &auth.admin_token( "user_john" ) );
+    let response = client.post("/revocations").json( &json!({ "user_id": "user_john" }) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/revocationObjects").header( "Authorization", TestAuth::bearer( &john_again ) ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    // keys held before the user was revoked stop working, keys handed out afterwards are fine
+    let response = client.get("/revocationObjects").header( "X-Api-Key", &john_key.key ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    tokio::time::sleep( Duration::from_millis( 1100 ) ).await;
+    let response = client.post("/apiKeys").json( &key_request ).header( "Authorization", &admin ).send().await;
+    let new_key: CreatedApiKey = response.json().await;
+    let response = client.get("/revocationObjects").header( "X-Api-Key", &new_key.key ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/revocationObjects/{}", uuid::Uuid::new_v4() ) ).header( "Authorization", &john_admin ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get("/revocations").header( "Authorization", &admin ).send().await;
+    let stored: Vec<Revocation> = response.json().await;
+    assert_eq!( stored.len(), 3 );
+}
+
+#[tokio::test]
+async fn test_postgres_revocations() {
+    let database = TestDatabase::from_env().await.unwrap();
+    database.apply_schema::<RevocationSchema>().await.unwrap();
+    let revocations = Revocations::new( database.pool.clone() );
+
+    revocations.revoke_token( "token_1" ).await.unwrap();
+    revocations.revoke_user( "user_1" ).await.unwrap();
+
+    let reloaded = Revocations::new( database.pool.clone() );
+    assert_eq!( reloaded.is_revoked( Some( "token_1" ), "user_2", None ).await, Ok( true ) );
+    assert_eq!( reloaded.is_revoked( None, "user_1", Some( 0 ) ).await, Ok( true ) );
+    assert_eq!( reloaded.is_revoked( None, "user_2", Some( 0 ) ).await, Ok( false ) );
+    assert_eq!( reloaded.list().await.unwrap().len(), 2 );
+
+    database.teardown().await.unwrap();
+}
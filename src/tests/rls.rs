@@ -0,0 +1,317 @@
+use axum::Json;
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use sqlx::FromRow;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, CrudError, KeyValue, FieldValue, Janus, RecordId, RequestContext, Action};
+use crate::storage::{Storage, FromStorageRow, ReadQuery, RlsStorage, rls, database_error};
+use crate::sharing::{GrantLevel, GrantSchema, ShareRequest};
+use crate::testing::{TestAuth, TestDatabase};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap_or( "nobody".to_string() )
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "RlsObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "rlsObjects"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS RlsObjects (
+                id UUID PRIMARY KEY,
+                name TEXT NOT NULL,
+                user_id TEXT NOT NULL
+            );
+        "
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        *verb != EndpointVerb::DELETE
+    }
+
+    fn is_custom( verb: &EndpointVerb ) -> bool {
+        *verb == EndpointVerb::GET
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    // deliberately forgets the owner filter
+    async fn custom_read<S, T, QP>( storage: &S, _context: &RequestContext, _filters: QP, _user_id: Option<String> ) -> Result<Json<Vec<T>>, StatusCode> where S: Storage, T: FromStorageRow<S> + Send + Unpin, QP: Send + Sync + Unpin + KeyValue {
+        let query = ReadQuery { table: "RlsObjects", filters: vec![], owner: None, order_by: "name", descending: false, limit: None, offset: None };
+        let rows = storage.select( &query ).await?;
+        Ok( Json( rows.into_iter().map( T::from_storage_row ).collect::<Result<Vec<T>, StatusCode>>()? ) )
+    }
+
+    fn actions() -> Vec<Action> {
+        vec![
+            Action::collection( "count", AccessPermission::AUTHENTICATED, ObjectPermission::OWNER ),
+            Action::collection( "publicCount", AccessPermission::ANY, ObjectPermission::OWNER )
+        ]
+    }
+
+    // raw sql on the request's session transaction, no owner filter either
+    async fn run_action<S: Storage>( storage: &S, context: &RequestContext, _action: &str, _id: Option<RecordId>, _input: Value ) -> Result<Json<Value>, CrudError> {
+        let storage = storage.as_any().downcast_ref::<RlsStorage>().ok_or( StatusCode::INTERNAL_SERVER_ERROR )?;
+        let transaction = context.transaction( storage ).await?;
+        let count: i64 = sqlx::query_scalar( "SELECT COUNT(*) FROM RlsObjects" )
+            .fetch_one( &mut *transaction.connection().await? ).await
+            .map_err( database_error )?;
+        transaction.commit().await?;
+        Ok( Json( json!({ "count": count }) ) )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct SharedObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+impl InputSerializer<SharedObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> SharedObject {
+        SharedObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap_or( "nobody".to_string() )
+        }
+    }
+}
+
+impl KeyValue for SharedObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for SharedObject {
+    fn table_name() -> &'static str {
+        "RlsSharedObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "rlsSharedObjects"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS RlsSharedObjects (
+                id UUID PRIMARY KEY,
+                name TEXT NOT NULL,
+                user_id TEXT NOT NULL
+            );
+        "
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( verb: &EndpointVerb ) -> ObjectPermission {
+        match verb {
+            EndpointVerb::POST => ObjectPermission::OWNER,
+            _ => ObjectPermission::SHARED
+        }
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+}
+
+async fn rls_role( database: &TestDatabase ) {
+    sqlx::query( "DO $$ BEGIN IF NOT EXISTS ( SELECT FROM pg_roles WHERE rolname = 'janus_rls_test' ) THEN CREATE ROLE janus_rls_test NOLOGIN; END IF; END $$;" )
+        .execute( &database.pool ).await.unwrap();
+    sqlx::query( &format!( "GRANT USAGE ON SCHEMA {} TO janus_rls_test", database.schema ) )
+        .execute( &database.pool ).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rls_policies() {
+    let policies = rls::rls_policies::<TestObject>( "janus_app" );
+
+    assert!( policies.contains( "ALTER TABLE RlsObjects ENABLE ROW LEVEL SECURITY;" ) );
+    assert!( policies.contains( "CREATE POLICY janus_get ON RlsObjects FOR SELECT TO janus_app USING ( user_id = current_setting( 'app.user_id', true ) );" ) );
+    assert!( policies.contains( "DROP POLICY IF EXISTS janus_delete ON RlsObjects;" ) );
+    assert!( !policies.contains( "CREATE POLICY janus_delete" ) );
+    assert!( !policies.contains( "janus_grants" ) );
+
+    let policies = rls::rls_policies::<SharedObject>( "janus_app" );
+    assert!( policies.contains( "GRANT SELECT ON janus_grants TO janus_app;" ) );
+    assert!( RlsStorage::new( sqlx::PgPool::connect_lazy( "postgres://localhost/janus" ).unwrap(), "janus; DROP TABLE RlsObjects" ).is_err() );
+}
+
+#[tokio::test]
+async fn test_rls_isolation() {
+    let auth = TestAuth::start().await.unwrap();
+    let database = TestDatabase::from_env().await.unwrap();
+    database.apply::<TestObject>().await.unwrap();
+
+    rls_role( &database ).await;
+
+    let storage = RlsStorage::new( database.pool.clone(), "janus_rls_test" ).unwrap();
+    storage.install_policies::<TestObject>().await.unwrap();
+
+    let app = Janus::with_storage( storage )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+
+    for ( name, token ) in [ ( "John's", &john ), ( "Jane's", &jane ) ] {
+        let response = client.post("/rlsObjects").json( &TestObjectInputParams { name: name.to_string() } ).header( "Authorization", token ).send().await;
+        assert_eq!( response.status(), StatusCode::OK );
+    }
+
+    let response = client.get("/rlsObjects").header( "Authorization", &john ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].user_id, "user_john" );
+
+    let response = client.get("/rlsObjects").header( "Authorization", &jane ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].name, "Jane's" );
+
+    let response = client.post("/rlsObjects/count").header( "Authorization", &john ).send().await;
+    assert_eq!( response.json::<Value>().await, json!({ "count": 1 }) );
+
+    // anonymous requests run as the role too, with no user to own anything
+    let response = client.post("/rlsObjects/publicCount").send().await;
+    assert_eq!( response.json::<Value>().await, json!({ "count": 0 }) );
+
+    let count: i64 = sqlx::query_scalar( "SELECT COUNT(*) FROM RlsObjects" ).fetch_one( &database.pool ).await.unwrap();
+    assert_eq!( count, 2 );
+
+    database.teardown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rls_sharing() {
+    let auth = TestAuth::start().await.unwrap();
+    let database = TestDatabase::from_env().await.unwrap();
+    database.apply::<SharedObject>().await.unwrap();
+    database.apply_schema::<GrantSchema>().await.unwrap();
+    rls_role( &database ).await;
+
+    let storage = RlsStorage::new( database.pool.clone(), "janus_rls_test" ).unwrap();
+    storage.install_policies::<SharedObject>().await.unwrap();
+
+    let app = Janus::with_storage( storage )
+        .auth( auth.config() )
+        .sharing( "/shares" )
+        .resource::<SharedObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+    let share = |record_id: uuid::Uuid, level: GrantLevel| ShareRequest { resource: "rlsSharedObjects".to_string(), record_id, user_id: Some( "user_jane".to_string() ), group: None, level };
+
+    let response = client.post("/rlsSharedObjects").json( &TestObjectInputParams { name: "Plans".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/rlsSharedObjects").header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert!( response.json::<Vec<SharedObject>>().await.is_empty() );
+
+    let response = client.post("/shares").json( &share( id, GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/rlsSharedObjects").header( "Authorization", &jane ).send().await;
+    assert_eq!( response.json::<Vec<SharedObject>>().await.len(), 1 );
+
+    let response = client.put( &format!( "/rlsSharedObjects/{}", id ) ).json( &TestObjectInputParams { name: "Jane's plans".to_string() } ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    client.post("/shares").json( &share( id, GrantLevel::MANAGE ) ).header( "Authorization", &john ).send().await;
+
+    let response = client.put( &format!( "/rlsSharedObjects/{}", id ) ).json( &TestObjectInputParams { name: "Jane's plans".to_string() } ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/rlsSharedObjects/{}", id ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let count: i64 = sqlx::query_scalar( "SELECT COUNT(*) FROM RlsSharedObjects" ).fetch_one( &database.pool ).await.unwrap();
+    assert_eq!( count, 0 );
+
+    database.teardown().await.unwrap();
+}
@@ -0,0 +1,237 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, SchemaTrait};
+use crate::sharing::{ Grant, GrantLevel, GrantSchema, ShareRequest };
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "SharedObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "sharedObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS SharedObjects (
+            id UUID PRIMARY KEY,
+            name TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( verb: &EndpointVerb ) -> ObjectPermission {
+        match verb {
+            EndpointVerb::POST => ObjectPermission::OWNER,
+            _ => ObjectPermission::SHARED
+        }
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+}
+
+async fn sqlite_storage() -> SqlitePool {
+    let pool = SqlitePoolOptions::new().max_connections( 1 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, TestObject::schema() ).await.unwrap();
+    sqlx::Executor::execute( &pool, GrantSchema::schema().as_str() ).await.unwrap();
+    pool
+}
+
+fn share( record_id: uuid::Uuid, user_id: Option<&str>, group: Option<&str>, level: GrantLevel ) -> ShareRequest {
+    ShareRequest { resource: "sharedObjects".to_string(), record_id, user_id: user_id.map( str::to_string ), group: group.map( str::to_string ), level }
+}
+
+#[tokio::test]
+async fn test_record_sharing() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( sqlite_storage().await )
+        .auth( auth.config() )
+        .sharing( "/shares" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+    let emma = TestAuth::bearer( &auth.group_token( "user_emma", &[ "editors" ] ) );
+
+    let response = client.post("/sharedObjects").json( &TestObjectInputParams { name: "Plans".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/sharedObjects").header( "Authorization", &jane ).send().await;
+    assert!( response.json::<Vec<TestObject>>().await.is_empty() );
+
+    let response = client.post("/shares").json( &share( id, Some( "user_jane" ), None, GrantLevel::MANAGE ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.post("/shares").json( &share( id, Some( "user_jane" ), Some( "editors" ), GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post("/shares").json( &share( id, Some( "user_jane" ), None, GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let jane_grant = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/sharedObjects").header( "Authorization", &jane ).send().await;
+    assert_eq!( response.json::<Vec<TestObject>>().await.len(), 1 );
+
+    let response = client.put( &format!( "/sharedObjects/{}", id ) ).json( &TestObjectInputParams { name: "Jane's plans".to_string() } ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.post("/shares").json( &share( id, None, Some( "editors" ), GrantLevel::WRITE ) ).header( "Authorization", &john ).send().await;
+    let group_grant = response.json::<uuid::Uuid>().await;
+
+    let response = client.put( &format!( "/sharedObjects/{}", id ) ).json( &TestObjectInputParams { name: "Edited plans".to_string() } ).header( "Authorization", &emma ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/sharedObjects/{}", id ) ).header( "Authorization", &emma ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    // sharing again changes the existing grant rather than adding another
+    let response = client.post("/shares").json( &share( id, Some( "user_jane" ), None, GrantLevel::MANAGE ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert_eq!( response.json::<uuid::Uuid>().await, jane_grant );
+
+    let level = |level: GrantLevel| client.post("/shares").json( &share( id, Some( "user_ann" ), None, level ) ).header( "Authorization", &john ).send();
+    let ( first, second ) = tokio::join!( level( GrantLevel::READ ), level( GrantLevel::WRITE ) );
+    assert_eq!( ( first.status(), second.status() ), ( StatusCode::OK, StatusCode::OK ) );
+
+    let response = client.get( &format!( "/shares?resource=sharedObjects&record_id={}", id ) ).header( "Authorization", &jane ).send().await;
+    let grants: Vec<Grant> = response.json().await;
+    let mut grantees = grants.iter().map( |grant| grant.grantee.as_str() ).collect::<Vec<_>>();
+    grantees.sort();
+    assert_eq!( grantees, vec![ "group:editors", "user:user_ann", "user:user_jane" ] );
+    assert_eq!( grants.iter().find( |grant| grant.grantee == "user:user_jane" ).unwrap().level, GrantLevel::MANAGE.rank() );
+
+    let response = client.delete( &format!( "/shares/{}", group_grant ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/sharedObjects").header( "Authorization", &emma ).send().await;
+    assert!( response.json::<Vec<TestObject>>().await.is_empty() );
+
+    let response = client.delete( &format!( "/sharedObjects/{}", id ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+}
+
+#[tokio::test]
+async fn test_memory_sharing() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .sharing( "/shares" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let emma = TestAuth::bearer( &auth.group_token( "user_emma", &[ "editors" ] ) );
+
+    let response = client.post("/sharedObjects").json( &TestObjectInputParams { name: "Plans".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.post("/shares").json( &share( id, None, Some( "editors" ), GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/sharedObjects").header( "Authorization", &emma ).send().await;
+    assert_eq!( response.json::<Vec<TestObject>>().await.len(), 1 );
+
+    let response = client.put( &format!( "/sharedObjects/{}", id ) ).json( &TestObjectInputParams { name: "Edited".to_string() } ).header( "Authorization", &emma ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+}
+
+#[tokio::test]
+async fn test_revoked_sharing() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .revocations( "/revocations" )
+        .sharing( "/shares" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let admin = TestAuth::bearer( &auth.admin_token( "user_admin" ) );
+
+    let response = client.post("/sharedObjects").json( &TestObjectInputParams { name: "Plans".to_string() } ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.post("/shares").json( &share( id, Some( "user_jane" ), None, GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post("/revocations").json( &serde_json::json!({ "user_id": "user_john" }) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    // the sharing routes are merged after revocations but still see its extension
+    let response = client.post("/shares").json( &share( id, Some( "user_emma" ), None, GrantLevel::READ ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+
+    let response = client.get( &format!( "/shares?resource=sharedObjects&record_id={}", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::UNAUTHORIZED );
+}
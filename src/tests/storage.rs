@@ -0,0 +1,235 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus, RecordId};
+use crate::keys::UUID_KEY;
+use crate::storage::{ReadQuery, Filter, FilterOp, Owner, Shared, Dialect, sql};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    age: i32,
+    date_created: chrono::DateTime<chrono::Utc>,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String,
+    age: i32
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    age_gt: Option<i32>,
+    order_by: Option<String>,
+    order_dir: Option<String>,
+    limit: Option<i32>,
+    offset: Option<i32>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        self.age > 0
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            age: self.age,
+            date_created: chrono::Utc::now(),
+            user_id: user_id.unwrap_or( "nobody".to_string() )
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age )),
+            ("date_created", FieldValue::DATE( &self.date_created )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name )),
+            ("age", FieldValue::INTEGER( &self.age ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(age_gt) = &self.age_gt {
+            pairs.push( ("age_gt", FieldValue::INTEGER( age_gt )) );
+        }
+
+        if let Some(order_by) = &self.order_by {
+            pairs.push( ("order_by", FieldValue::STRING( order_by )) );
+        }
+
+        if let Some(order_dir) = &self.order_dir {
+            pairs.push( ("order_dir", FieldValue::STRING( order_dir )) );
+        }
+
+        if let Some(limit) = &self.limit {
+            pairs.push( ("limit", FieldValue::INTEGER( limit )) );
+        }
+
+        if let Some(offset) = &self.offset {
+            pairs.push( ("offset", FieldValue::INTEGER( offset )) );
+        }
+
+        pairs
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "StorageObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "storageObjects"
+    }
+
+    fn schema() -> &'static str {
+        "
+            CREATE TABLE IF NOT EXISTS StorageObjects (
+                id BLOB PRIMARY KEY,
+                name TEXT NOT NULL,
+                age INTEGER NOT NULL,
+                date_created TEXT NOT NULL,
+                user_id TEXT NOT NULL
+            );
+        "
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::ANY
+    }
+}
+
+#[test]
+fn test_dialect_statements() {
+    let query_params = TestObjectQueryParams {
+        name: Some( "John".to_string() ),
+        age_gt: Some( 30 ),
+        order_dir: Some( "asc".to_string() ),
+        offset: Some( 10 ),
+        ..Default::default()
+    };
+    let owner = Owner { column: "user_id", user_id: "user_1".to_string(), shared: None };
+    let query = ReadQuery::parse( "StorageObjects", &query_params, Some( owner.clone() ) ).unwrap();
+
+    let statement = sql::select( Dialect::POSTGRES, &query );
+    assert_eq!( statement.sql, "SELECT * FROM StorageObjects WHERE user_id = $1 AND name = $2 AND age > $3 ORDER BY id ASC OFFSET 10" );
+    assert_eq!( statement.bindings.len(), 3 );
+
+    let statement = sql::select( Dialect::SQLITE, &query );
+    assert_eq!( statement.sql, "SELECT * FROM StorageObjects WHERE user_id = ? AND name = ? AND age > ? ORDER BY id ASC LIMIT -1 OFFSET 10" );
+
+    let statement = sql::select( Dialect::MYSQL, &query );
+    assert_eq!( statement.sql, "SELECT * FROM StorageObjects WHERE user_id = ? AND name = ? AND age > ? ORDER BY id ASC LIMIT 18446744073709551615 OFFSET 10" );
+
+    let input = TestObjectInputParams { name: "John".to_string(), age: 31 };
+    let values = input.key_value_pairs();
+    assert_eq!( sql::insert( Dialect::POSTGRES, "StorageObjects", UUID_KEY, &values ).sql, "INSERT INTO StorageObjects ( name, age ) VALUES ( $1, $2 ) RETURNING id" );
+    assert_eq!( sql::insert( Dialect::MYSQL, "StorageObjects", UUID_KEY, &values ).sql, "INSERT INTO StorageObjects ( name, age ) VALUES ( ?, ? )" );
+
+    let id = RecordId::UUID( uuid::Uuid::new_v4() );
+    assert_eq!( sql::update( Dialect::POSTGRES, "StorageObjects", UUID_KEY, &id, &values, &[], Some( &owner ) ).unwrap().sql, "UPDATE StorageObjects SET name = $1, age = $2 WHERE id = $3 AND user_id = $4" );
+    let pending = "pending".to_string();
+    let guard = [ Filter { column: "status", op: FilterOp::EQ, value: FieldValue::STRING( &pending ) } ];
+    assert_eq!( sql::update( Dialect::SQLITE, "StorageObjects", UUID_KEY, &id, &values, &guard, None ).unwrap().sql, "UPDATE StorageObjects SET name = ?, age = ? WHERE id = ? AND status = ?" );
+
+    let shared = Owner { shared: Some( Shared { resource: "storageObjects".to_string(), key: "id", grantees: vec![ "user:user_1".to_string(), "group:staff".to_string() ], level: 2 } ), ..owner.clone() };
+    assert_eq!( sql::delete( Dialect::POSTGRES, "StorageObjects", UUID_KEY, std::slice::from_ref( &id ), Some( &shared ) ).unwrap().sql,
+        "DELETE FROM StorageObjects WHERE id IN ( $1 ) AND ( user_id = $2 OR id IN ( SELECT record_id FROM janus_grants WHERE resource = $3 AND level >= $4 AND grantee IN ( $5, $6 ) ) )" );
+
+    let ids = vec![ id.clone(), id ];
+    assert_eq!( sql::delete( Dialect::SQLITE, "StorageObjects", UUID_KEY, &ids, None ).unwrap().sql, "DELETE FROM StorageObjects WHERE id IN ( ?, ? )" );
+
+    let injected = TestObjectQueryParams { order_by: Some( "id; DROP TABLE StorageObjects".to_string() ), ..Default::default() };
+    assert_eq!( ReadQuery::parse( "StorageObjects", &injected, None ).err(), Some( StatusCode::BAD_REQUEST ) );
+}
+
+async fn sqlite_storage() -> SqlitePool {
+    let pool = SqlitePoolOptions::new().max_connections( 1 ).connect( "sqlite::memory:" ).await.unwrap();
+    sqlx::Executor::execute( &pool, TestObject::schema() ).await.unwrap();
+    pool
+}
+
+#[tokio::test]
+async fn test_sqlite_storage() {
+    let app = Janus::with_storage( sqlite_storage().await )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+
+    let mut ids = vec![];
+    for ( name, age ) in [ ( "John", 31 ), ( "Jane", 25 ), ( "Jack", 42 ) ] {
+        let response = client.post("/storageObjects").json( &TestObjectInputParams { name: name.to_string(), age } ).send().await;
+        assert_eq!( response.status(), StatusCode::OK );
+        ids.push( response.json::<uuid::Uuid>().await );
+    }
+
+    let response = client.get("/storageObjects?age_gt=30&order_by=age&order_dir=asc").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.iter().map( |object| object.name.as_str() ).collect::<Vec<_>>(), vec![ "John", "Jack" ] );
+    assert_eq!( objects[0].id, ids[0] );
+
+    let response = client.get("/storageObjects?order_by=age&limit=1&offset=1").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].name, "John" );
+
+    let response = client.put( &format!( "/storageObjects/{}", ids[1] ) ).json( &TestObjectInputParams { name: "Janet".to_string(), age: 26 } ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/storageObjects?name=Janet").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].age, 26 );
+
+    let response = client.delete( &format!( "/storageObjects/{}", ids[2] ) ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.delete( &format!( "/storageObjects/{}", ids[2] ) ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/storageObjects").send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 2 );
+}
@@ -0,0 +1,146 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, Janus};
+use crate::storage::MemoryStorage;
+use crate::testing::TestAuth;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    user_id: String,
+    org_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            name: self.name.clone(),
+            user_id: user_id.unwrap(),
+            org_id: String::new()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("user_id", FieldValue::STRING( &self.user_id )),
+            ("org_id", FieldValue::STRING( &self.org_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "TenantObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "tenantObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( verb: &EndpointVerb ) -> ObjectPermission {
+        match verb {
+            EndpointVerb::DELETE => ObjectPermission::TENANTADMIN,
+            _ => ObjectPermission::TENANT
+        }
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn tenant_column() -> &'static str {
+        "org_id"
+    }
+}
+
+#[tokio::test]
+async fn test_tenant_scoping() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let alice = TestAuth::bearer( &auth.tenant_token( "user_alice", "org_a", "org:member" ) );
+    let bob = TestAuth::bearer( &auth.tenant_token( "user_bob", "org_a", "org:admin" ) );
+    let carol = TestAuth::bearer( &auth.tenant_token( "user_carol", "org_b", "org:admin" ) );
+    let dave = TestAuth::bearer( &auth.user_token( "user_dave" ) );
+
+    let response = client.post("/tenantObjects").json( &TestObjectInputParams { name: "Shared".to_string() } ).header( "Authorization", &alice ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/tenantObjects").header( "Authorization", &bob ).send().await;
+    let objects: Vec<TestObject> = response.json().await;
+    assert_eq!( objects.len(), 1 );
+    assert_eq!( objects[0].org_id, "org_a" );
+    assert_eq!( objects[0].user_id, "user_alice" );
+
+    let response = client.get("/tenantObjects").header( "Authorization", &carol ).send().await;
+    assert!( response.json::<Vec<TestObject>>().await.is_empty() );
+
+    let response = client.put( &format!( "/tenantObjects/{}", id ) ).json( &TestObjectInputParams { name: "Taken".to_string() } ).header( "Authorization", &carol ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.put( &format!( "/tenantObjects/{}", id ) ).json( &TestObjectInputParams { name: "Renamed".to_string() } ).header( "Authorization", &bob ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/tenantObjects").header( "Authorization", &dave ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/tenantObjects/{}", id ) ).header( "Authorization", &alice ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.delete( &format!( "/tenantObjects/{}", id ) ).header( "Authorization", &carol ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.delete( &format!( "/tenantObjects/{}", id ) ).header( "Authorization", &bob ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+}
@@ -0,0 +1,222 @@
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, CrudError, KeyValue, FieldValue, Janus, RecordId, RequestContext, StateMachine, Storage, Transition};
+use crate::storage::{MemoryStorage, ReadQuery, FromStorageRow};
+use crate::testing::TestAuth;
+use crate::extractors::ACT_AS_HEADER;
+use crate::transitions::{TransitionRecord, TRANSITIONS_TABLE};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    item: String,
+    status: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    item: String,
+    status: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            item: self.item.clone(),
+            status: "draft".to_string(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("item", FieldValue::STRING( &self.item )),
+            ("status", FieldValue::STRING( &self.status )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("item", FieldValue::STRING( &self.item )),
+            ("status", FieldValue::STRING( &self.status ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "TransitionOrders"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "orders"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS TransitionOrders (
+            id UUID PRIMARY KEY,
+            item TEXT NOT NULL,
+            status TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::OWNER
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn state_machine() -> Option<StateMachine> {
+        Some( StateMachine::new( "status" )
+            .transition( Transition::new( "submit", &[ "draft" ], "submitted" ) )
+            .transition( Transition::new( "approve", &[ "submitted" ], "approved" ).access( AccessPermission::ADMIN, ObjectPermission::ALL ) )
+            .transition( Transition::new( "cancel", &[ "draft", "submitted" ], "cancelled" ) )
+            .transition( Transition::new( "hold", &[ "submitted" ], "held" ).requires( "orders:hold" ) ) )
+    }
+
+    async fn guard_transition<S: Storage>( storage: &S, context: &RequestContext, id: &RecordId, transition: &Transition, from: &str ) -> Result<(), CrudError> {
+        // stands in for another request moving the order between the state check and the write
+        if context.headers.contains_key( "X-Racing-Submit" ) {
+            let submitted = "submitted".to_string();
+            storage.update( "TransitionOrders", Self::primary_key(), id, &[ ("status", FieldValue::STRING( &submitted )) ], None ).await?;
+        }
+
+        match ( transition.name, from ) {
+            ( "cancel", "submitted" ) => Err( CrudError::conflict( "submitted orders are cancelled by support" ) ),
+            _ => Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_state_transitions() {
+    let auth = TestAuth::start().await.unwrap();
+    let storage = MemoryStorage::new();
+    let builder = Janus::with_storage( storage.clone() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>();
+
+    let info = builder.registry().get( "orders" ).unwrap();
+    assert_eq!( info.actions.iter().map( |action| action.name ).collect::<Vec<_>>(), vec![ "submit", "approve", "cancel", "hold" ] );
+    assert_eq!( info.actions[1].access, AccessPermission::ADMIN );
+
+    let client = TestClient::new( builder.build() );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let admin = TestAuth::bearer( &auth.admin_token( "user_admin" ) );
+    let input = |status: &str| TestObjectInputParams { item: "Book".to_string(), status: status.to_string() };
+
+    let response = client.post("/orders").json( &input( "approved" ) ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.put( &format!( "/orders/{}", id ) ).json( &input( "approved" ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+    assert_eq!( response.text().await, "status cannot change from 'draft' to 'approved'" );
+
+    // keeping the state is not a transition, moving it along one is
+    let response = client.put( &format!( "/orders/{}", id ) ).json( &input( "draft" ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.put( &format!( "/orders/{}", id ) ).json( &input( "submitted" ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.post( &format!( "/orders/{}/submit", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+    assert_eq!( response.text().await, "cannot submit while status is 'submitted'" );
+
+    let response = client.post( &format!( "/orders/{}/cancel", id ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+    assert_eq!( response.text().await, "submitted orders are cancelled by support" );
+
+    let response = client.put( &format!( "/orders/{}", id ) ).json( &input( "approved" ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.post( &format!( "/orders/{}/approve", id ) ).header( "Authorization", &john ).send().await;
+    assert!( response.status() == StatusCode::UNAUTHORIZED || response.status() == StatusCode::FORBIDDEN );
+
+    // an impersonating admin works as the user, admin-only transitions wait until they act as themselves
+    let impersonator = TestAuth::bearer( &auth.admin_token_with( "user_admin", &[ "read:admin", "impersonate:users" ] ) );
+    let response = client.post( &format!( "/orders/{}/approve", id ) ).header( "Authorization", &impersonator ).header( ACT_AS_HEADER, "user_john" ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.post( &format!( "/orders/{}/approve", id ) ).header( "Authorization", &admin ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert_eq!( response.json::<Value>().await, json!({ "transition": "approve", "from": "submitted", "to": "approved" }) );
+
+    let response = client.get("/orders").header( "Authorization", &john ).send().await;
+    let orders: Vec<TestObject> = response.json().await;
+    assert_eq!( orders[0].status, "approved" );
+
+    // a required permission is read off the caller's own token, admin or not
+    let response = client.post("/orders").json( &input( "draft" ) ).header( "Authorization", &john ).send().await;
+    let held = response.json::<uuid::Uuid>().await;
+    client.post( &format!( "/orders/{}/submit", held ) ).header( "Authorization", &john ).send().await;
+
+    let response = client.post( &format!( "/orders/{}/hold", held ) ).header( "Authorization", &john ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let holder = TestAuth::bearer( &auth.permission_token( "user_john", &[ "orders:hold" ] ) );
+    let response = client.post( &format!( "/orders/{}/hold", held ) ).header( "Authorization", &holder ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    // a transition whose record moved on in the meantime is refused and leaves no trace
+    let response = client.post("/orders").json( &input( "draft" ) ).header( "Authorization", &john ).send().await;
+    let raced = response.json::<uuid::Uuid>().await;
+
+    let response = client.post( &format!( "/orders/{}/cancel", raced ) ).header( "Authorization", &john ).header( "X-Racing-Submit", "1" ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+    assert_eq!( response.text().await, "status is no longer 'draft'" );
+
+    let response = client.put( &format!( "/orders/{}", raced ) ).json( &input( "cancelled" ) ).header( "Authorization", &john ).header( "X-Racing-Submit", "1" ).send().await;
+    assert_eq!( response.status(), StatusCode::CONFLICT );
+
+    let response = client.get("/orders").header( "Authorization", &john ).send().await;
+    let orders: Vec<TestObject> = response.json().await;
+    assert_eq!( orders.iter().find( |order| order.id == raced ).unwrap().status, "draft" );
+
+    let query = ReadQuery { table: TRANSITIONS_TABLE, filters: vec![], owner: None, order_by: "date_created", descending: false, limit: None, offset: None };
+    let history = storage.select( &query ).await.unwrap()
+        .into_iter()
+        .map( <TransitionRecord as FromStorageRow<MemoryStorage>>::from_storage_row )
+        .collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!( history.iter().map( |record| ( record.transition.as_str(), record.from_state.as_str(), record.to_state.as_str() ) ).collect::<Vec<_>>(),
+        vec![ ( "submit", "draft", "submitted" ), ( "approve", "submitted", "approved" ), ( "submit", "draft", "submitted" ), ( "hold", "submitted", "held" ) ] );
+    assert_eq!( history[0].object_id, id.to_string() );
+    assert_eq!( history[0].user_id.as_deref(), Some( "user_john" ) );
+    assert_eq!( history[1].user_id.as_deref(), Some( "user_admin" ) );
+}
@@ -0,0 +1,160 @@
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, KeyValue, FieldValue, FieldType, Janus};
+use crate::cli::{Command, parse_command};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+struct TestObject {
+    id: uuid::Uuid,
+    name: String,
+    date_created: chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {
+    name: Option<String>,
+    date_created_gt: Option<chrono::DateTime<chrono::Utc>>,
+    order_dir: Option<String>
+}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.name.is_empty()
+    }
+
+    fn add_set_values(&self, _user_id: Option<String>) -> TestObject {
+        TestObject { id: uuid::Uuid::new_v4(), name: self.name.clone(), date_created: chrono::Utc::now() }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("name", FieldValue::STRING( &self.name )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("id", FieldType::UUID),
+            ("name", FieldType::STRING),
+            ("date_created", FieldType::DATE)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("name", FieldValue::STRING( &self.name ))
+        ]
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING)
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![];
+
+        if let Some(name) = &self.name {
+            pairs.push( ("name", FieldValue::STRING( name )) );
+        }
+
+        if let Some(date_created_gt) = &self.date_created_gt {
+            pairs.push( ("date_created_gt", FieldValue::DATE( date_created_gt )) );
+        }
+
+        if let Some(order_dir) = &self.order_dir {
+            pairs.push( ("order_dir", FieldValue::STRING( order_dir )) );
+        }
+
+        pairs
+    }
+
+    fn field_types() -> Vec<(&'static str, FieldType)> {
+        vec![
+            ("name", FieldType::STRING),
+            ("date_created_gt", FieldType::DATE),
+            ("order_dir", FieldType::STRING)
+        ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "TypeScriptObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "typeScriptObjects"
+    }
+
+    fn schema() -> &'static str {
+        ""
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        !matches!( verb, EndpointVerb::PUT )
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+}
+
+#[test]
+fn test_generate_typescript() {
+    let builder = Janus::builder()
+        .prefix( "/restful" )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>();
+
+    let output = crate::typescript::generate_typescript( builder.registry() );
+
+    assert!( output.contains( "export interface TestObject {\n    id: string;\n    name: string;\n    date_created: string;\n}" ) );
+    assert!( output.contains( "export interface TestObjectInputParams {\n    name: string;\n}" ) );
+    assert!( output.contains( "    date_created_gt?: string | Date;" ) );
+    assert!( output.contains( "export class TestObjectFilter extends FilterBuilder<TestObjectQueryParams>" ) );
+    assert!( output.contains( "    dateCreatedGreaterThan(value: string | Date): this {\n        return this.set('date_created_gt', value);" ) );
+    assert!( output.contains( "    orderDir(value: 'asc' | 'desc'): this" ) );
+    assert!( output.contains( "headers['Authorization'] = `Bearer ${token}`;" ) );
+    assert!( output.contains( "headers['Subscription'] = `Bearer ${subscription}`;" ) );
+    assert!( output.contains( "    listTypeScriptObjects(query: TestObjectQueryParams | TestObjectFilter = {}): Promise<TestObject[]>" ) );
+    assert!( output.contains( "this.request('POST', '/restful/typeScriptObjects', input);" ) );
+    assert!( output.contains( "    deleteTestObject(id: string): Promise<void>" ) );
+    assert!( !output.contains( "updateTestObject" ) );
+}
+
+#[test]
+fn test_parse_command() {
+    let args = |values: &[&str]| values.iter().map( |value| value.to_string() ).collect::<Vec<String>>();
+
+    assert_eq!( parse_command( args( &[] ) ), Ok( Command::SERVE ) );
+    assert_eq!( parse_command( args( &[ "serve" ] ) ), Ok( Command::SERVE ) );
+    assert_eq!( parse_command( args( &[ "typescript" ] ) ), Ok( Command::TYPESCRIPT( None ) ) );
+    assert_eq!( parse_command( args( &[ "openapi", "spec.json" ] ) ), Ok( Command::OPENAPI( Some( "spec.json".into() ) ) ) );
+    assert_eq!( parse_command( args( &[ "issuer", "127.0.0.1:4100" ] ) ), Ok( Command::ISSUER( Some( "127.0.0.1:4100".to_string() ) ) ) );
+    assert!( parse_command( args( &[ "serve", "now" ] ) ).is_err() );
+    assert!( parse_command( args( &[ "deploy" ] ) ).is_err() );
+}
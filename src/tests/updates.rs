@@ -0,0 +1,287 @@
+use std::sync::Mutex;
+use axum::http::StatusCode;
+use axum_test_helper::TestClient;
+use axum::async_trait;
+use serde::{Serialize, Deserialize};
+use crate::{EndpointVerb, ObjectPermission, AccessPermission, InputSerializer, CrudConfig, CrudError, KeyValue, FieldValue, Janus, RecordId, RequestContext, SetValue};
+use crate::storage::{MemoryStorage, Storage};
+use crate::testing::TestAuth;
+
+static HOOKS: Mutex<Vec<&'static str>> = Mutex::new( vec![] );
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObject {
+    id: uuid::Uuid,
+    title: String,
+    code: String,
+    date_created: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    updated_by: String,
+    user_id: String
+}
+
+// the same rows through an endpoint whose input only carries the immutable code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CodeObject {
+    id: uuid::Uuid,
+    title: String,
+    code: String,
+    date_created: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    updated_by: String,
+    user_id: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectInputParams {
+    title: String,
+    code: String,
+    updated_by: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CodeInputParams {
+    code: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestObjectQueryParams {}
+
+impl InputSerializer<TestObject> for TestObjectInputParams {
+    fn verify(&self) -> bool {
+        !self.title.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> TestObject {
+        let now = chrono::Utc::now();
+        TestObject {
+            id: uuid::Uuid::new_v4(),
+            title: self.title.clone(),
+            code: self.code.clone(),
+            date_created: now,
+            updated_at: now,
+            updated_by: user_id.clone().unwrap(),
+            user_id: user_id.unwrap()
+        }
+    }
+
+    fn update_set_values(&self, user_id: Option<String>) -> Vec<(&'static str, SetValue)> {
+        vec![
+            ("updated_at", SetValue::DATE( chrono::Utc::now() )),
+            ("updated_by", SetValue::STRING( user_id.unwrap() ))
+        ]
+    }
+}
+
+impl InputSerializer<CodeObject> for CodeInputParams {
+    fn verify(&self) -> bool {
+        !self.code.is_empty()
+    }
+
+    fn add_set_values(&self, user_id: Option<String>) -> CodeObject {
+        let now = chrono::Utc::now();
+        CodeObject {
+            id: uuid::Uuid::new_v4(),
+            title: String::new(),
+            code: self.code.clone(),
+            date_created: now,
+            updated_at: now,
+            updated_by: user_id.clone().unwrap(),
+            user_id: user_id.unwrap()
+        }
+    }
+}
+
+impl KeyValue for TestObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("title", FieldValue::STRING( &self.title )),
+            ("code", FieldValue::STRING( &self.code )),
+            ("date_created", FieldValue::DATE( &self.date_created )),
+            ("updated_at", FieldValue::DATE( &self.updated_at )),
+            ("updated_by", FieldValue::STRING( &self.updated_by )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for CodeObject {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("title", FieldValue::STRING( &self.title )),
+            ("code", FieldValue::STRING( &self.code )),
+            ("date_created", FieldValue::DATE( &self.date_created )),
+            ("updated_at", FieldValue::DATE( &self.updated_at )),
+            ("updated_by", FieldValue::STRING( &self.updated_by )),
+            ("user_id", FieldValue::STRING( &self.user_id ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("title", FieldValue::STRING( &self.title )),
+            ("code", FieldValue::STRING( &self.code )),
+            ("updated_by", FieldValue::STRING( &self.updated_by ))
+        ]
+    }
+}
+
+impl KeyValue for CodeInputParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![
+            ("code", FieldValue::STRING( &self.code ))
+        ]
+    }
+}
+
+impl KeyValue for TestObjectQueryParams {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for TestObject {
+    fn table_name() -> &'static str {
+        "UpdateObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "updateObjects"
+    }
+
+    fn schema() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS UpdateObjects (
+            id UUID PRIMARY KEY,
+            title TEXT NOT NULL,
+            code TEXT NOT NULL,
+            date_created TIMESTAMPTZ NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL,
+            updated_by TEXT NOT NULL,
+            user_id TEXT NOT NULL
+        );"
+    }
+
+    fn include_endpoint( _verb: &EndpointVerb ) -> bool {
+        true
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn immutable_columns() -> Vec<&'static str> {
+        vec![ "code", "date_created", "user_id" ]
+    }
+}
+
+#[async_trait]
+impl CrudConfig for CodeObject {
+    fn table_name() -> &'static str {
+        "UpdateObjects"
+    }
+
+    fn endpoint_name() -> &'static str {
+        "updateCodes"
+    }
+
+    fn schema() -> &'static str {
+        TestObject::schema()
+    }
+
+    fn include_endpoint( verb: &EndpointVerb ) -> bool {
+        *verb == EndpointVerb::PUT
+    }
+
+    fn is_custom( _verb: &EndpointVerb ) -> bool {
+        false
+    }
+
+    fn get_object_permissions( _verb: &EndpointVerb ) -> ObjectPermission {
+        ObjectPermission::ALL
+    }
+
+    fn get_access_permissions( _verb: &EndpointVerb ) -> AccessPermission {
+        AccessPermission::AUTHENTICATED
+    }
+
+    fn immutable_columns() -> Vec<&'static str> {
+        TestObject::immutable_columns()
+    }
+
+    async fn before_update<S: Storage, UP: Send + Sync + KeyValue + 'static>( _storage: &S, _context: &RequestContext, _id: &RecordId, _values: &mut UP ) -> Result<(), CrudError> {
+        HOOKS.lock().unwrap().push( "before_update" );
+        Ok(())
+    }
+
+    async fn after_update<S: Storage, UP: Send + Sync + KeyValue + 'static>( _storage: &S, _context: &RequestContext, _id: &RecordId, _values: &UP ) -> Result<(), CrudError> {
+        HOOKS.lock().unwrap().push( "after_update" );
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_server_managed_updates() {
+    let auth = TestAuth::start().await.unwrap();
+    let app = Janus::with_storage( MemoryStorage::new() )
+        .auth( auth.config() )
+        .resource::<TestObject, TestObjectInputParams, TestObjectQueryParams>()
+        .resource::<CodeObject, CodeInputParams, TestObjectQueryParams>()
+        .build();
+    let client = TestClient::new( app );
+    let john = TestAuth::bearer( &auth.user_token( "user_john" ) );
+    let jane = TestAuth::bearer( &auth.user_token( "user_jane" ) );
+    let input = |title: &str, code: &str| TestObjectInputParams { title: title.to_string(), code: code.to_string(), updated_by: "someone_else".to_string() };
+
+    let response = client.post("/updateObjects").json( &input( "Plans", "A-1" ) ).header( "Authorization", &john ).send().await;
+    let id = response.json::<uuid::Uuid>().await;
+
+    let response = client.get("/updateObjects").header( "Authorization", &john ).send().await;
+    let created = response.json::<Vec<TestObject>>().await.remove( 0 );
+
+    let response = client.put( &format!( "/updateObjects/{}", id ) ).json( &input( "Final plans", "B-2" ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+    assert_eq!( response.text().await, "code cannot be changed" );
+
+    // repeating only what may not change is a no-op, trying to change it is still refused
+    let code = |code: &str| CodeInputParams { code: code.to_string() };
+    let response = client.put( &format!( "/updateCodes/{}", id ) ).json( &code( "A-1" ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+    assert_eq!( *HOOKS.lock().unwrap(), vec![ "before_update", "after_update" ] );
+
+    let response = client.put( &format!( "/updateCodes/{}", id ) ).json( &code( "B-2" ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::FORBIDDEN );
+
+    let response = client.put( &format!( "/updateCodes/{}", uuid::Uuid::new_v4() ) ).json( &code( "A-1" ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::BAD_REQUEST );
+
+    let response = client.get("/updateObjects").header( "Authorization", &john ).send().await;
+    let unchanged = response.json::<Vec<TestObject>>().await.remove( 0 );
+    assert_eq!( unchanged.updated_at, created.updated_at );
+    assert_eq!( unchanged.updated_by, "user_john" );
+
+    // repeating the stored code is fine, the stamps win over what the input claims
+    let response = client.put( &format!( "/updateObjects/{}", id ) ).json( &input( "Final plans", "A-1" ) ).header( "Authorization", &jane ).send().await;
+    assert_eq!( response.status(), StatusCode::OK );
+
+    let response = client.get("/updateObjects").header( "Authorization", &john ).send().await;
+    let updated = response.json::<Vec<TestObject>>().await.remove( 0 );
+    assert_eq!( updated.title, "Final plans" );
+    assert_eq!( updated.code, "A-1" );
+    assert_eq!( updated.updated_by, "user_jane" );
+    assert_eq!( updated.user_id, "user_john" );
+    assert_eq!( updated.date_created, created.date_created );
+    assert!( updated.updated_at > created.updated_at );
+}
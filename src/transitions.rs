@@ -0,0 +1,72 @@
+use axum::http::StatusCode;
+use serde::{ Serialize, Deserialize };
+use crate::{ FieldValue, KeyValue, SchemaTrait, Transition };
+use crate::keys::UUID_KEY;
+use crate::storage::Storage;
+
+pub const TRANSITIONS_TABLE: &str = "janus_transitions";
+
+pub struct TransitionSchema {}
+
+impl SchemaTrait for TransitionSchema {
+    fn schema() -> String {
+        format!( "CREATE TABLE IF NOT EXISTS {} (
+            id UUID PRIMARY KEY,
+            resource TEXT NOT NULL,
+            object_id TEXT NOT NULL,
+            transition TEXT NOT NULL,
+            from_state TEXT NOT NULL,
+            to_state TEXT NOT NULL,
+            user_id TEXT,
+            date_created TIMESTAMPTZ NOT NULL
+        );", TRANSITIONS_TABLE )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TransitionRecord {
+    pub id: uuid::Uuid,
+    pub resource: String,
+    pub object_id: String,
+    pub transition: String,
+    pub from_state: String,
+    pub to_state: String,
+    pub user_id: Option<String>,
+    pub date_created: chrono::DateTime<chrono::Utc>
+}
+
+impl KeyValue for TransitionRecord {
+    fn key_value_pairs<'a>(&'a self) -> Vec<(&'static str, FieldValue<'a>)> {
+        let mut pairs = vec![
+            ("id", FieldValue::UUID( &self.id )),
+            ("resource", FieldValue::STRING( &self.resource )),
+            ("object_id", FieldValue::STRING( &self.object_id )),
+            ("transition", FieldValue::STRING( &self.transition )),
+            ("from_state", FieldValue::STRING( &self.from_state )),
+            ("to_state", FieldValue::STRING( &self.to_state )),
+            ("date_created", FieldValue::DATE( &self.date_created ))
+        ];
+
+        if let Some( user_id ) = &self.user_id {
+            pairs.push( ("user_id", FieldValue::STRING( user_id )) );
+        }
+
+        pairs
+    }
+}
+
+pub async fn record_transition<S: Storage>( storage: &S, resource: &str, object_id: String, transition: &Transition, from: &str, user_id: Option<String> ) -> Result<(), StatusCode> {
+    let record = TransitionRecord {
+        id: uuid::Uuid::new_v4(),
+        resource: resource.to_string(),
+        object_id,
+        transition: transition.name.to_string(),
+        from_state: from.to_string(),
+        to_state: transition.to.to_string(),
+        user_id,
+        date_created: chrono::Utc::now()
+    };
+
+    storage.insert( TRANSITIONS_TABLE, UUID_KEY, &record.key_value_pairs() ).await?;
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use crate::{ EndpointVerb, FieldType };
+use crate::registry::{ ResourceRegistry, ResourceInfo, TypeInfo };
+
+const CLIENT_PRELUDE: &str = "export interface JanusClientOptions {
+    baseUrl: string;
+    getToken?: () => string | undefined | Promise<string | undefined>;
+    getSubscriptionToken?: () => string | undefined | Promise<string | undefined>;
+    fetch?: typeof fetch;
+}
+
+export class JanusError extends Error {
+    constructor(public status: number, public body: string) {
+        super(`Janus request failed with status ${status}: ${body}`);
+    }
+}
+
+type QueryValue = string | number | boolean | Date;
+
+function toQueryString(params: object = {}): string {
+    const search = new URLSearchParams();
+    for (const [key, value] of Object.entries(params) as [string, QueryValue | undefined | null][]) {
+        if (value === undefined || value === null) {
+            continue;
+        }
+        search.append(key, value instanceof Date ? value.toISOString() : String(value));
+    }
+    const query = search.toString();
+    return query.length > 0 ? `?${query}` : '';
+}
+
+export class FilterBuilder<Q extends object> {
+    protected params: Record<string, QueryValue> = {};
+
+    protected set(key: string, value: QueryValue): this {
+        this.params[key] = value;
+        return this;
+    }
+
+    build(): Q {
+        return { ...this.params } as Q;
+    }
+}
+";
+
+const CLIENT_REQUEST: &str = "    constructor(private options: JanusClientOptions) {}
+
+    private async request<R>(method: string, path: string, body?: unknown): Promise<R> {
+        const headers: Record<string, string> = {};
+        const token = this.options.getToken ? await this.options.getToken() : undefined;
+        if (token) {
+            headers['Authorization'] = `Bearer ${token}`;
+        }
+        const subscription = this.options.getSubscriptionToken ? await this.options.getSubscriptionToken() : undefined;
+        if (subscription) {
+            headers['Subscription'] = `Bearer ${subscription}`;
+        }
+        if (body !== undefined) {
+            headers['Content-Type'] = 'application/json';
+        }
+
+        const doFetch = this.options.fetch ?? fetch;
+        const response = await doFetch(`${this.options.baseUrl}${path}`, {
+            method,
+            headers,
+            body: body === undefined ? undefined : JSON.stringify(body),
+        });
+        const text = await response.text();
+        if (!response.ok) {
+            throw new JanusError(response.status, text);
+        }
+        return (text.length > 0 ? JSON.parse(text) : undefined) as R;
+    }
+";
+
+pub fn ts_type( field_type: FieldType ) -> &'static str {
+    match field_type {
+        FieldType::UUID => "string",
+        FieldType::STRING => "string",
+        FieldType::INTEGER | FieldType::BIGINT => "number",
+        FieldType::DATE => "string",
+        FieldType::BOOLEAN => "boolean",
+        FieldType::FLOAT => "number"
+    }
+}
+
+fn query_ts_type( field_type: FieldType ) -> &'static str {
+    match field_type {
+        FieldType::DATE => "string | Date",
+        _ => ts_type( field_type )
+    }
+}
+
+fn pascal_case( name: &str ) -> String {
+    name.split( [ '_', '-' ] )
+        .filter( |part| !part.is_empty() )
+        .map( |part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some( first ) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new()
+            }
+        } )
+        .collect()
+}
+
+fn camel_case( name: &str ) -> String {
+    let pascal = pascal_case( name );
+    let mut chars = pascal.chars();
+    match chars.next() {
+        Some( first ) => first.to_lowercase().collect::<String>() + chars.as_str(),
+        None => String::new()
+    }
+}
+
+fn interface( out: &mut String, emitted: &mut HashSet<&'static str>, info: &TypeInfo, optional: bool, query: bool ) {
+    if !emitted.insert( info.name ) {
+        return;
+    }
+
+    if info.fields.is_empty() {
+        out.push_str( &format!( "export type {} = Record<string, unknown>;\n\n", info.name ) );
+        return;
+    }
+
+    out.push_str( &format!( "export interface {} {{\n", info.name ) );
+    for ( name, field_type ) in &info.fields {
+        let ts = if query { query_ts_type( *field_type ) } else { ts_type( *field_type ) };
+        out.push_str( &format!( "    {}{}: {};\n", name, if optional { "?" } else { "" }, ts ) );
+    }
+    out.push_str( "}\n\n" );
+}
+
+fn filter_method( name: &str, field_type: FieldType ) -> String {
+    if name == "order_dir" {
+        return "    orderDir(value: 'asc' | 'desc'): this {\n        return this.set('order_dir', value);\n    }\n".to_string();
+    }
+
+    let method = match name.len() > 3 {
+        true => match name.split_at( name.len() - 3 ) {
+            ( column, "_gt" ) => format!( "{}GreaterThan", camel_case( column ) ),
+            ( column, "_lt" ) => format!( "{}LessThan", camel_case( column ) ),
+            ( column, "_ge" ) => format!( "{}AtLeast", camel_case( column ) ),
+            ( column, "_le" ) => format!( "{}AtMost", camel_case( column ) ),
+            _ => camel_case( name )
+        },
+        false => camel_case( name )
+    };
+
+    format!( "    {}(value: {}): this {{\n        return this.set('{}', value);\n    }}\n", method, query_ts_type( field_type ), name )
+}
+
+fn filter_builder( out: &mut String, resource: &ResourceInfo ) {
+    let methods = resource.query.fields.iter()
+        .map( |( name, field_type )| filter_method( name, *field_type ) )
+        .collect::<Vec<String>>();
+
+    out.push_str( &format!( "export class {}Filter extends FilterBuilder<{}> {{\n", resource.model.name, resource.query.name ) );
+    out.push_str( &methods.join( "\n" ) );
+    out.push_str( "}\n\n" );
+}
+
+// one argument per path segment of the item url, typed after its key column
+fn key_arguments( resource: &ResourceInfo ) -> ( String, String ) {
+    let names = resource.key.path_names();
+    let arguments = names.iter()
+        .zip( resource.key.columns )
+        .map( |( name, ( _, key_type ) )| format!( "{}: {}", name, ts_type( key_type.field_type() ) ) )
+        .collect::<Vec<_>>();
+    let segments = names.iter()
+        .map( |name| format!( "${{encodeURIComponent({})}}", name ) )
+        .collect::<Vec<_>>();
+
+    ( arguments.join( ", " ), segments.join( "/" ) )
+}
+
+fn created_type( resource: &ResourceInfo ) -> String {
+    match resource.key.single_type() {
+        Some( key_type ) => ts_type( key_type.field_type() ).to_string(),
+        None => format!( "[{}]", resource.key.columns.iter().map( |( _, key_type )| ts_type( key_type.field_type() ) ).collect::<Vec<_>>().join( ", " ) )
+    }
+}
+
+fn client_methods( out: &mut String, resource: &ResourceInfo ) {
+    let model = resource.model.name;
+    let input = resource.input.name;
+    let query = resource.query.name;
+    let plural = pascal_case( resource.endpoint_name );
+    let ( key_arguments, key_path ) = key_arguments( resource );
+
+    for verb in &resource.verbs {
+        let collection = format!( "{}/{}", resource.prefix, resource.endpoint_name );
+        out.push( '\n' );
+        match verb.verb {
+            EndpointVerb::GET => out.push_str( &format!(
+                "    list{}(query: {} | {}Filter = {{}}): Promise<{}[]> {{\n        const params = query instanceof {}Filter ? query.build() : query;\n        return this.request('GET', `{}${{toQueryString(params)}}`);\n    }}\n",
+                plural, query, model, model, model, collection ) ),
+            EndpointVerb::POST => out.push_str( &format!(
+                "    create{}(input: {}): Promise<{}> {{\n        return this.request('POST', '{}', input);\n    }}\n",
+                model, input, created_type( resource ), collection ) ),
+            EndpointVerb::PUT => out.push_str( &format!(
+                "    update{}({}, input: {}): Promise<void> {{\n        return this.request('PUT', `{}/{}`, input);\n    }}\n",
+                model, key_arguments, input, collection, key_path ) ),
+            EndpointVerb::DELETE => out.push_str( &format!(
+                "    delete{}({}): Promise<void> {{\n        return this.request('DELETE', `{}/{}`);\n    }}\n",
+                model, key_arguments, collection, key_path ) )
+        }
+    }
+}
+
+pub fn generate_typescript( registry: &ResourceRegistry ) -> String {
+    let mut endpoints = HashSet::new();
+    let resources = registry.resources().iter()
+        .filter( |resource| endpoints.insert( resource.endpoint_name ) )
+        .collect::<Vec<&ResourceInfo>>();
+
+    let mut out = String::from( "// Generated by janus. Do not edit by hand.\n\n" );
+    out.push_str( CLIENT_PRELUDE );
+    out.push( '\n' );
+
+    let mut emitted = HashSet::new();
+    for resource in &resources {
+        interface( &mut out, &mut emitted, &resource.model, false, false );
+        if resource.verb( EndpointVerb::POST ).is_some() || resource.verb( EndpointVerb::PUT ).is_some() {
+            interface( &mut out, &mut emitted, &resource.input, false, false );
+        }
+        if resource.verb( EndpointVerb::GET ).is_some() {
+            interface( &mut out, &mut emitted, &resource.query, true, true );
+            filter_builder( &mut out, resource );
+        }
+    }
+
+    out.push_str( "export class JanusClient {\n" );
+    out.push_str( CLIENT_REQUEST );
+    for resource in &resources {
+        client_methods( &mut out, resource );
+    }
+    out.push_str( "}\n" );
+
+    out
+}